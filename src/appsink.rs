@@ -11,11 +11,12 @@
 // Audio / Signed 16bit / 1 channel / arbitrary sample rate
 
 use bevy::asset::AssetLoader;
+use bevy::asset::HandleId;
 use bevy::asset::LoadContext;
 use bevy::asset::LoadedAsset;
-use bevy::prelude::Component;
-use bevy::prelude::Handle;
-use bevy::prelude::Image;
+use bevy::audio::AddAudioSource;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
 use bevy::reflect::TypeUuid;
 use bevy::utils::BoxedFuture;
 use gst::element_error;
@@ -23,35 +24,219 @@ use gst::prelude::*;
 
 use byte_slice_cast::*;
 
+use std::collections::HashMap;
 use std::i16;
 use std::i32;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::sync::RwLock;
+use std::time::{Duration, Instant};
 
-use anyhow::Error;
-use derive_more::{Display, Error};
+use crate::audio::{attach_audio_branch, AudioBuffer, AudioStream, AUDIO_LABEL};
+use crate::avsync::{present_av_synced_frames, AvSync};
+use crate::beat::{send_beat_events, BeatDetected};
+use crate::burst::{finish_bursts, Burst, VideoBurstCaptured};
+use crate::capabilities::insert_capabilities;
+use crate::compositor::build_compositor;
+use crate::config::{SinkImageConfig, VideoSource};
+use crate::device::{
+    start_camera_monitor, watch_devices, CameraMonitor, Placeholder, VideoDeviceBusy,
+    VideoDeviceRemoved, VideoDeviceReturned, VideoPermissionDenied, DEFAULT_DEVICE,
+};
+use crate::discover::{finish_discoveries, MediaInfo, PendingDiscovery, VideoDiscovered};
+use crate::error::VideoError;
+use crate::export::{finish_exports, FrameExport, VideoExportFinished};
+use crate::frame::{
+    send_frame_events, FrameEvent, FrameInfo, FrameQueue, FrameRejection, VideoFrameReady,
+    VideoFrameRejected,
+};
+use crate::framesync::{latch_frames, FrameLatch};
+use crate::health::{watch_degradation, DegradationConfig, VideoDegraded, VideoHealthy};
+use crate::lens::LensCalibration;
+use crate::levels::{AudioLevels, VideoAudioLevels};
+use crate::mediaclock::{update_media_clocks, MediaClock};
+use crate::missing::MissingPlugin;
+use crate::netclock::{finish_net_clocks, NetClock, NetClockConfig, PendingNetClock};
+use crate::photo::{
+    capture_photos, PhotoCapture, VideoPhotoCaptured, VideoPhotoFailed, VideoPhotoStarted,
+};
+use crate::playback::stop_pipeline;
+use crate::qos::{update_upload_qos, VideoQosStats};
+use crate::recovery::{
+    RecoveryPolicy, RecoveryState, VideoGaveUp, VideoRecovered, VideoRecovering,
+};
+use crate::sampler::VideoSampler;
+use crate::seek_preview::{
+    finish_seek_previews, PendingSeekPreview, SeekPreview, VideoSeekPreviewFailed,
+    VideoSeekPreviewReady,
+};
+use crate::spectrum::{update_spectrum_textures, AudioSpectrum, VideoAudioSpectra, SPECTRUM_LABEL};
+use crate::stats::StreamStats;
+use crate::tags::{merge_tags, VideoTagsUpdated};
+use crate::text_overlay::{TextOverlay, TimeOverlay};
+use crate::thumbnail::{update_thumbnails, Thumbnail, THUMBNAIL_LABEL};
+use crate::timeline::{Timeline, TimelineEvent};
+use crate::timeshift::{attach_recorder, play_time_shift, TimeShift};
+use crate::watchdog::{watch_stalls, VideoStalled, WatchdogConfig};
 
-#[derive(Debug, Display, Error)]
-#[display(fmt = "Missing element {}", _0)]
-struct MissingElement(#[error(not(source))] &'static str);
+/// Size of the frames handed to Bevy.
+pub const WIDTH: u32 = 176;
+pub const HEIGHT: u32 = 144;
 
-#[derive(Debug, Display, Error)]
-#[display(fmt = "Received error from {}: {} (debug: {:?})", src, error, debug)]
-pub struct ErrorMessage {
-    pub src: String,
-    pub error: String,
-    pub debug: Option<String>,
-    pub source: glib::Error,
+pub type ImageRaw = [u8; (WIDTH * HEIGHT * 4) as usize];
+
+/// Registers the `.sinkimage` asset and the systems that drive its pipeline.
+pub struct AppSinkPlugin;
+
+impl Plugin for AppSinkPlugin {
+    fn build(&self, app: &mut App) {
+        insert_capabilities(app);
+
+        app.add_asset::<AppSinkImage>()
+            .add_audio_source::<AudioStream>()
+            .init_asset_loader::<AppSinkImageLoader>()
+            .init_resource::<VideoQosStats>()
+            .init_resource::<VideoAudioLevels>()
+            .init_resource::<VideoAudioSpectra>()
+            .add_event::<MissingPluginEvent>()
+            .add_event::<VideoRecovering>()
+            .add_event::<VideoRecovered>()
+            .add_event::<VideoGaveUp>()
+            .add_event::<VideoStalled>()
+            .add_event::<VideoDeviceRemoved>()
+            .add_event::<VideoDeviceReturned>()
+            .add_event::<VideoDeviceBusy>()
+            .add_event::<VideoPermissionDenied>()
+            .add_event::<VideoFrameReady>()
+            .add_event::<VideoFrameRejected>()
+            .add_event::<VideoTagsUpdated>()
+            .add_event::<VideoDiscovered>()
+            .add_event::<VideoDegraded>()
+            .add_event::<VideoHealthy>()
+            .add_event::<VideoExportFinished>()
+            .add_event::<VideoBurstCaptured>()
+            .add_event::<VideoSeekPreviewReady>()
+            .add_event::<VideoSeekPreviewFailed>()
+            .add_event::<VideoPhotoStarted>()
+            .add_event::<VideoPhotoCaptured>()
+            .add_event::<VideoPhotoFailed>()
+            .add_event::<BeatDetected>()
+            .add_startup_system(start_camera_monitor)
+            .add_system_to_stage(CoreStage::PreUpdate, latch_frames)
+            .add_system_to_stage(CoreStage::PreUpdate, update_media_clocks)
+            .add_system(start_pipelines)
+            .add_system(poll_bus)
+            .add_system(retry_pipelines)
+            .add_system(watch_stalls)
+            .add_system(watch_devices)
+            .add_system(send_frame_events)
+            .add_system(update_upload_qos)
+            .add_system(watch_degradation)
+            .add_system(finish_exports)
+            .add_system(finish_bursts)
+            .add_system(capture_photos)
+            .add_system(play_time_shift)
+            .add_system(present_av_synced_frames)
+            .add_system(update_thumbnails)
+            .add_system(update_spectrum_textures)
+            .add_system(send_beat_events)
+            .add_system(finish_seek_previews)
+            .add_system(finish_discoveries)
+            .add_system(finish_net_clocks);
+    }
 }
 
-type ImageRaw = [u8; 176 * 144 * 4];
+/// Sent when a stream cannot run because a GStreamer plugin is not installed.
+pub struct MissingPluginEvent {
+    pub handle: Handle<AppSinkImage>,
+    pub plugin: MissingPlugin,
+}
 
 #[derive(Debug, TypeUuid)]
 #[uuid = "39cadc56-aa9c-4543-8640-a018b74b5052"]
 pub struct AppSinkImage {
-    pub pipeline: gst::Pipeline,
-    pub bus: gst::Bus,
+    pub pipeline: Option<gst::Pipeline>,
+    pub bus: Option<gst::Bus>,
     pub image_raw: Arc<RwLock<ImageRaw>>,
+    /// Last error reported while starting or running the pipeline.
+    pub error: Option<VideoError>,
+    pub recovery: RecoveryPolicy,
+    pub recovery_state: RecoveryState,
+    pub watchdog: WatchdogConfig,
+    /// Set while the watchdog considers the stream stalled.
+    pub stalled: bool,
+    /// When the appsink last handed over a sample.
+    pub last_sample: Arc<RwLock<Option<Instant>>>,
+    /// Presentation timestamp of the frame in `image_raw`, if known. Written
+    /// while `image_raw` is locked, so both always match.
+    pub frame_pts: Arc<RwLock<Option<gst::ClockTime>>>,
+    started_at: Option<Instant>,
+    pub source: VideoSource,
+    /// Probe URI sources with `GstDiscoverer` before starting them.
+    pub discover: bool,
+    /// Result of probing the source before playback, if it was requested.
+    pub media_info: Option<MediaInfo>,
+    /// Probe running in the background, the pipeline starts once it is done.
+    pub pending_discovery: Option<PendingDiscovery>,
+    /// What to show while the capture device is unplugged.
+    pub placeholder: Placeholder,
+    /// Set while the capture device is unplugged.
+    pub device_removed: bool,
+    pub stats: Arc<StreamStats>,
+    pub frames: Arc<FrameQueue>,
+    /// Tags received from the pipeline, such as title or codec.
+    pub tags: HashMap<String, String>,
+    pub degradation: DegradationConfig,
+    /// Set while too many frames are missed in a row.
+    pub degraded: bool,
+    /// Recent state changes, errors and caps of the stream.
+    pub timeline: Arc<Mutex<Timeline>>,
+    /// Image sequence being written from the stream, if any.
+    pub export: Arc<Mutex<Option<FrameExport>>>,
+    /// Burst of frames being collected, if any.
+    pub burst: Arc<Mutex<Option<Burst>>>,
+    /// History of recent frames for pausing and rewinding, if enabled.
+    pub time_shift: Arc<Mutex<Option<TimeShift>>>,
+    /// Display aspect ratio of the source before it is scaled to
+    /// `WIDTH`x`HEIGHT`, once negotiated.
+    pub source_aspect: Arc<RwLock<Option<f32>>>,
+    /// Text drawn onto the frames, if configured.
+    pub text_overlay: Option<TextOverlay>,
+    /// Buffer time or wall clock drawn onto the frames, if configured.
+    pub time_overlay: Option<TimeOverlay>,
+    /// Downscaled second output of the stream, if configured.
+    pub thumbnail: Option<Thumbnail>,
+    /// Thumbnails spread over the file, once generated.
+    pub seek_preview: Option<SeekPreview>,
+    pub pending_seek_preview: Option<PendingSeekPreview>,
+    /// Still being taken in place of streaming, if any.
+    pub photo: Option<PhotoCapture>,
+    /// Lens of the camera, undistorted by materials showing the stream.
+    pub calibration: Option<LensCalibration>,
+    /// Sampler of the textures showing the stream, the app's default if
+    /// `None`.
+    pub sampler: Option<VideoSampler>,
+    /// Decoded sound of URI sources, played by the `audio` sub-asset.
+    pub audio: Option<Arc<AudioBuffer>>,
+    /// Frames waiting for the audio to catch up, if video is synced to it.
+    pub av_sync: Arc<Mutex<Option<AvSync>>>,
+    /// Frequency bands of the audio published in `VideoAudioSpectra`, if
+    /// analyzed.
+    pub spectrum_bands: Option<u32>,
+    /// The `spectrum` sub-asset, if configured.
+    pub spectrum_texture: Option<Handle<Image>>,
+    /// Clock to share with other machines, if configured. Connected before
+    /// the pipeline starts.
+    pub net_clock_config: Option<NetClockConfig>,
+    /// The shared clock, once connected.
+    pub net_clock: Option<NetClock>,
+    pub pending_net_clock: Option<PendingNetClock>,
+    /// Frame shown by every output during the current Bevy frame.
+    pub latch: FrameLatch,
+    /// Position of the stream mapped into Bevy's time.
+    pub media_clock: Mutex<MediaClock>,
 }
 
 #[derive(Default)]
@@ -64,7 +249,45 @@ impl AssetLoader for AppSinkImageLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
         Box::pin(async move {
-            load_context.set_default_asset(LoadedAsset::new(AppSinkImage::new()));
+            let config = SinkImageConfig::parse(std::str::from_utf8(bytes)?)?;
+            let mut appsink = AppSinkImage::new();
+            appsink.discover = config.discover;
+            appsink.source = config.source;
+            appsink.text_overlay = config.text_overlay;
+            appsink.time_overlay = config.time_overlay;
+            appsink.calibration = config.calibration;
+            appsink.sampler = config.sampler;
+            appsink.net_clock_config = config.net_clock;
+            if config.audio {
+                let buffer = Arc::new(AudioBuffer::new(config.audio_format));
+                load_context.set_labeled_asset(
+                    AUDIO_LABEL,
+                    LoadedAsset::new(AudioStream {
+                        buffer: buffer.clone(),
+                    }),
+                );
+                if config.av_sync.unwrap_or(true) {
+                    let mut av_sync = AvSync::new(buffer.clone());
+                    av_sync.offset_ms = config.av_offset_ms;
+                    appsink.av_sync = Arc::new(Mutex::new(Some(av_sync)));
+                }
+                appsink.audio = Some(buffer);
+                appsink.spectrum_bands = config.spectrum;
+                if let (Some(bands), true) = (config.spectrum, config.spectrum_texture) {
+                    appsink.spectrum_texture = Some(load_context.set_labeled_asset(
+                        SPECTRUM_LABEL,
+                        LoadedAsset::new(AudioSpectrum::placeholder(bands)),
+                    ));
+                }
+            }
+            if let Some((width, height)) = config.thumbnail {
+                let image = load_context.set_labeled_asset(
+                    THUMBNAIL_LABEL,
+                    LoadedAsset::new(Thumbnail::placeholder(width, height)),
+                );
+                appsink.thumbnail = Some(Thumbnail::new(width, height, image));
+            }
+            load_context.set_default_asset(LoadedAsset::new(appsink));
             Ok(())
         })
     }
@@ -75,40 +298,601 @@ impl AssetLoader for AppSinkImageLoader {
 }
 
 impl AppSinkImage {
+    /// Creates an idle stream. The pipeline is built by [`AppSinkImage::start`],
+    /// which the plugin calls once the asset has been added.
     pub fn new() -> AppSinkImage {
-        let image_raw = Arc::new(RwLock::new([0u8; 176 * 144 * 4]));
-        let pipeline = create_pipeline(image_raw.clone()).unwrap();
-        pipeline.set_state(gst::State::Playing).unwrap();
+        AppSinkImage {
+            pipeline: None,
+            bus: None,
+            image_raw: Arc::new(RwLock::new(opaque_black())),
+            error: None,
+            recovery: RecoveryPolicy::default(),
+            recovery_state: RecoveryState::default(),
+            watchdog: WatchdogConfig::default(),
+            stalled: false,
+            last_sample: Arc::new(RwLock::new(None)),
+            frame_pts: Arc::new(RwLock::new(None)),
+            started_at: None,
+            source: VideoSource::default(),
+            discover: false,
+            media_info: None,
+            pending_discovery: None,
+            placeholder: Placeholder::default(),
+            device_removed: false,
+            stats: Arc::new(StreamStats::default()),
+            frames: Arc::new(FrameQueue::default()),
+            tags: HashMap::new(),
+            degradation: DegradationConfig::default(),
+            degraded: false,
+            timeline: Arc::new(Mutex::new(Timeline::default())),
+            export: Arc::new(Mutex::new(None)),
+            burst: Arc::new(Mutex::new(None)),
+            time_shift: Arc::new(Mutex::new(None)),
+            source_aspect: Arc::new(RwLock::new(None)),
+            text_overlay: None,
+            time_overlay: None,
+            thumbnail: None,
+            seek_preview: None,
+            pending_seek_preview: None,
+            photo: None,
+            calibration: None,
+            sampler: None,
+            audio: None,
+            av_sync: Arc::new(Mutex::new(None)),
+            spectrum_bands: None,
+            spectrum_texture: None,
+            net_clock_config: None,
+            net_clock: None,
+            pending_net_clock: None,
+            latch: FrameLatch::default(),
+            media_clock: Mutex::new(MediaClock::default()),
+        }
+    }
+
+    pub fn start(&mut self) -> Result<(), VideoError> {
+        self.stop();
+
+        *self.last_sample.write().unwrap() = None;
+        let pipeline = create_pipeline(self)?;
+        if let Some(net_clock) = &self.net_clock {
+            net_clock.apply(&pipeline);
+        }
+        set_playing(&pipeline)?;
 
         let bus = pipeline
             .bus()
             .expect("Pipeline without bus. Shouldn't happen!");
 
-        AppSinkImage {
-            pipeline: pipeline,
-            bus: bus,
-            image_raw,
+        self.pipeline = Some(pipeline);
+        self.bus = Some(bus);
+        self.error = None;
+        self.stalled = false;
+        self.started_at = Some(Instant::now());
+        self.timeline.lock().unwrap().push(TimelineEvent::Started);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if stop_pipeline(&mut self.pipeline, &mut self.bus) {
+            self.timeline.lock().unwrap().push(TimelineEvent::Stopped);
+        }
+        self.started_at = None;
+    }
+
+    /// Whether the stream waits for its source to be probed or its clock to
+    /// synchronize before starting.
+    pub fn is_preparing(&self) -> bool {
+        self.pending_discovery.is_some() || self.pending_net_clock.is_some()
+    }
+
+    /// Gives up on what the stream was waiting for, after one of them failed.
+    pub(crate) fn cancel_preparation(&mut self) {
+        self.pending_discovery = None;
+        self.pending_net_clock = None;
+    }
+
+    /// Path of the capture device, `None` if the stream does not use one.
+    pub fn device_path(&self) -> Option<&str> {
+        self.source.device_path()
+    }
+
+    /// Stops the stream after its device disappeared and shows the placeholder
+    /// until the device returns.
+    pub fn unplug(&mut self) {
+        self.stop();
+        self.device_removed = true;
+        self.recovery_state.retry_at = None;
+        self.placeholder.apply(&mut self.image_raw.write().unwrap());
+        self.stats.frame_written();
+    }
+
+    /// Writes the graph of the running pipeline to `path` in Graphviz DOT
+    /// format, like `GST_DEBUG_BIN_TO_DOT_FILE` does.
+    pub fn dump_graph(&self, path: impl AsRef<Path>) -> Result<(), VideoError> {
+        let pipeline = self.pipeline.as_ref().ok_or(VideoError::NotRunning)?;
+        let dot = gst::debug_bin_to_dot_data(pipeline, gst::DebugGraphDetails::all());
+        std::fs::write(path, dot.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes the recent history of the stream to `path`, one event per line.
+    pub fn dump_timeline(&self, path: impl AsRef<Path>) -> Result<(), VideoError> {
+        self.timeline.lock().unwrap().dump(path)
+    }
+
+    /// Copies the frame latched for this Bevy frame into `image`, if the
+    /// stream has a new one. Every output copying it in the same frame gets
+    /// the same frame, see [`FrameLatch`].
+    ///
+    /// Returns whether `image` was updated.
+    pub fn copy_to(&self, image: &mut Image) -> bool {
+        match self.latch.fresh() {
+            Some((_, frame)) => {
+                image.data.clear();
+                image.data.extend_from_slice(&frame);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Display aspect ratio of the video, the source's if known.
+    pub fn aspect_ratio(&self) -> f32 {
+        self.source_aspect
+            .read()
+            .unwrap()
+            .unwrap_or(WIDTH as f32 / HEIGHT as f32)
+    }
+
+    /// Time since the last sample arrived, or since the pipeline started if
+    /// none has arrived yet. `None` while the pipeline is not running.
+    pub fn time_since_last_sample(&self) -> Option<Duration> {
+        let last = (*self.last_sample.read().unwrap()).or(self.started_at)?;
+        self.started_at.map(|_| last.elapsed())
+    }
+}
+
+impl Drop for AppSinkImage {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Event writers and resources shared by the systems that drive stream pipelines.
+#[derive(SystemParam)]
+pub struct StreamEvents<'w, 's> {
+    pub camera_monitor: Option<Res<'w, CameraMonitor>>,
+    pub device_removed: EventWriter<'w, 's, VideoDeviceRemoved>,
+    pub device_busy: EventWriter<'w, 's, VideoDeviceBusy>,
+    pub permission_denied: EventWriter<'w, 's, VideoPermissionDenied>,
+    pub missing_plugins: EventWriter<'w, 's, MissingPluginEvent>,
+    pub recovering: EventWriter<'w, 's, VideoRecovering>,
+    pub recovered: EventWriter<'w, 's, VideoRecovered>,
+    pub gave_up: EventWriter<'w, 's, VideoGaveUp>,
+    pub tags_updated: EventWriter<'w, 's, VideoTagsUpdated>,
+    pub discovered: EventWriter<'w, 's, VideoDiscovered>,
+}
+
+fn start_pipelines(
+    time: Res<Time>,
+    mut asset_events: EventReader<AssetEvent<AppSinkImage>>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut events: StreamEvents,
+) {
+    for event in asset_events.iter() {
+        if let AssetEvent::Created { handle } = event {
+            if let Some(appsink) = appsinks.get_mut(handle) {
+                let discovering = appsink.start_discovery();
+                let connecting = appsink.start_net_clock();
+                if discovering || connecting {
+                    continue;
+                }
+                if let Err(err) = appsink.start() {
+                    handle_failure(appsink, handle, err, time.time_since_startup(), &mut events);
+                }
+            }
+        }
+    }
+}
+
+fn poll_bus(
+    time: Res<Time>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut qos_stats: ResMut<VideoQosStats>,
+    mut audio_levels: ResMut<VideoAudioLevels>,
+    mut audio_spectra: ResMut<VideoAudioSpectra>,
+    mut events: StreamEvents,
+) {
+    let ids: Vec<HandleId> = appsinks.ids().collect();
+    for id in ids {
+        let handle = Handle::weak(id);
+        let messages: Vec<gst::Message> = match appsinks.get(&handle).and_then(|a| a.bus.as_ref()) {
+            Some(bus) => bus.iter().collect(),
+            None => continue,
+        };
+
+        for msg in messages {
+            if let Some(plugin) = MissingPlugin::from_message(&msg) {
+                warn!("{}", plugin);
+                events.missing_plugins.send(MissingPluginEvent {
+                    handle: handle.clone_weak(),
+                    plugin,
+                });
+            } else if let Some(err) = VideoError::from_message(&msg) {
+                if let Some(appsink) = appsinks.get_mut(&handle) {
+                    handle_failure(
+                        appsink,
+                        &handle,
+                        err,
+                        time.time_since_startup(),
+                        &mut events,
+                    );
+                }
+                // The pipeline is gone, anything left on its bus is stale.
+                break;
+            } else if let gst::MessageView::StateChanged(change) = msg.view() {
+                let from_pipeline = msg.src().map(|s| s.is::<gst::Pipeline>()).unwrap_or(false);
+                if from_pipeline {
+                    if let Some(appsink) = appsinks.get(&handle) {
+                        appsink
+                            .timeline
+                            .lock()
+                            .unwrap()
+                            .push(TimelineEvent::StateChanged {
+                                old: change.old(),
+                                new: change.current(),
+                            });
+                    }
+                }
+                if from_pipeline && change.current() == gst::State::Playing {
+                    if let Some(appsink) = appsinks.get_mut(&handle) {
+                        let attempts = std::mem::take(&mut appsink.recovery_state).attempts;
+                        if attempts > 0 {
+                            info!("Stream recovered after {} attempt(s)", attempts);
+                            events.recovered.send(VideoRecovered {
+                                handle: handle.clone_weak(),
+                                attempts,
+                            });
+                        }
+                    }
+                }
+            } else if let gst::MessageView::Qos(qos) = msg.view() {
+                let element = msg
+                    .src()
+                    .map(|s| s.name().to_string())
+                    .unwrap_or_else(|| String::from("None"));
+                qos_stats
+                    .streams
+                    .entry(handle.clone_weak())
+                    .or_default()
+                    .elements
+                    .entry(element)
+                    .or_default()
+                    .record(&qos);
+                let (jitter, _, _) = qos.values();
+                if jitter > 0 {
+                    if let Some(appsink) = appsinks.get(&handle) {
+                        appsink.stats.frame_missed(1);
+                    }
+                }
+            } else if let gst::MessageView::Warning(warning) = msg.view() {
+                if let Some(appsink) = appsinks.get(&handle) {
+                    appsink
+                        .timeline
+                        .lock()
+                        .unwrap()
+                        .push(TimelineEvent::Warning(warning.error().to_string()));
+                }
+            } else if let gst::MessageView::Eos(..) = msg.view() {
+                if let Some(appsink) = appsinks.get(&handle) {
+                    appsink.timeline.lock().unwrap().push(TimelineEvent::Eos);
+                }
+            } else if let gst::MessageView::Tag(tag) = msg.view() {
+                if let Some(appsink) = appsinks.get_mut(&handle) {
+                    if merge_tags(&mut appsink.tags, &tag.tags()) {
+                        events.tags_updated.send(VideoTagsUpdated {
+                            handle: handle.clone_weak(),
+                        });
+                    }
+                }
+            } else if let gst::MessageView::Element(element) = msg.view() {
+                if let Some(levels) = element.structure().and_then(AudioLevels::from_structure) {
+                    audio_levels.streams.insert(handle.clone_weak(), levels);
+                } else if let Some(spectrum) =
+                    element.structure().and_then(AudioSpectrum::from_structure)
+                {
+                    audio_spectra.streams.insert(handle.clone_weak(), spectrum);
+                }
+            }
         }
     }
 }
 
-pub fn create_pipeline(image_raw: Arc<RwLock<ImageRaw>>) -> Result<gst::Pipeline, Error> {
-    gst::init()?;
+fn retry_pipelines(
+    time: Res<Time>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut events: StreamEvents,
+) {
+    let now = time.time_since_startup();
+    let due: Vec<HandleId> = appsinks
+        .iter()
+        .filter(|(_, a)| a.recovery_state.retry_at.map_or(false, |at| at <= now))
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in due {
+        let handle = Handle::weak(id);
+        if let Some(appsink) = appsinks.get_mut(&handle) {
+            appsink.recovery_state.retry_at = None;
+            // A clock that failed to synchronize is connected again first.
+            if appsink.start_net_clock() {
+                continue;
+            }
+            info!(
+                "Restarting stream (attempt {}/{})",
+                appsink.recovery_state.attempts, appsink.recovery.max_attempts
+            );
+            if let Err(err) = appsink.start() {
+                handle_failure(appsink, &handle, err, now, &mut events);
+            }
+        }
+    }
+}
+
+/// Tears down a failed stream and schedules a restart if its policy allows it.
+pub(crate) fn handle_failure(
+    appsink: &mut AppSinkImage,
+    handle: &Handle<AppSinkImage>,
+    err: VideoError,
+    now: Duration,
+    events: &mut StreamEvents,
+) {
+    error!("{}", err);
+    appsink
+        .timeline
+        .lock()
+        .unwrap()
+        .push(TimelineEvent::Error(err.to_string()));
+    if let VideoError::MissingElement(plugin) = &err {
+        events.missing_plugins.send(MissingPluginEvent {
+            handle: handle.clone_weak(),
+            plugin: plugin.clone(),
+        });
+    }
+
+    let device = appsink.device_path().unwrap_or_default().to_string();
+    match err {
+        VideoError::DeviceBusy { .. } => events.device_busy.send(VideoDeviceBusy {
+            handle: handle.clone_weak(),
+            device: device.clone(),
+        }),
+        VideoError::PermissionDenied { .. } => {
+            events.permission_denied.send(VideoPermissionDenied {
+                handle: handle.clone_weak(),
+                device: device.clone(),
+            })
+        }
+        _ => (),
+    }
+
+    // With a device monitor running, an unplugged camera is restarted as soon
+    // as it comes back instead of burning through the retry budget.
+    if let VideoError::DeviceRemoved { .. } = err {
+        if events.camera_monitor.is_some() && appsink.device_path().is_some() {
+            appsink.unplug();
+            events.device_removed.send(VideoDeviceRemoved {
+                handle: handle.clone_weak(),
+                device,
+            });
+            appsink.error = Some(err);
+            return;
+        }
+    }
+
+    appsink.stop();
+
+    let state = &mut appsink.recovery_state;
+    match state.schedule(&appsink.recovery, &err, now) {
+        Some(delay) => events.recovering.send(VideoRecovering {
+            handle: handle.clone_weak(),
+            attempt: state.attempts,
+            delay,
+            error: err.clone(),
+        }),
+        None => events.gave_up.send(VideoGaveUp {
+            handle: handle.clone_weak(),
+            attempts: state.attempts,
+            error: err.clone(),
+        }),
+    }
+
+    appsink.error = Some(err);
+}
+
+/// Creates the element `name`, describing which plugin to install if it is missing.
+pub(crate) fn make_element(name: &'static str) -> Result<gst::Element, VideoError> {
+    gst::ElementFactory::make(name, None)
+        .map_err(|_| VideoError::MissingElement(MissingPlugin::for_element(name)))
+}
+
+/// Sets `pipeline` to `Playing`, tearing it down if that fails.
+///
+/// A source failing to open synchronously, such as a busy or forbidden
+/// device, only makes the state change fail. The reason is posted on the bus,
+/// so it is returned instead of the bare state change error.
+pub(crate) fn set_playing(pipeline: &gst::Pipeline) -> Result<(), VideoError> {
+    if let Err(err) = pipeline.set_state(gst::State::Playing) {
+        let err = pipeline
+            .bus()
+            .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]))
+            .and_then(|msg| VideoError::from_message(&msg))
+            .unwrap_or(VideoError::StateChange(err));
+        let _ = pipeline.set_state(gst::State::Null);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Builds the pipeline of `stream`, writing its frames, sound and statistics
+/// into the state the stream shares with the streaming thread.
+pub fn create_pipeline(stream: &AppSinkImage) -> Result<gst::Pipeline, VideoError> {
+    let source = &stream.source;
+    let image_raw = stream.image_raw.clone();
+    let last_sample = stream.last_sample.clone();
+    let frame_pts = stream.frame_pts.clone();
+    let stats = stream.stats.clone();
+    let frames = stream.frames.clone();
+    let timeline = stream.timeline.clone();
+    let export = stream.export.clone();
+    let burst = stream.burst.clone();
+    let time_shift = stream.time_shift.clone();
+    let source_aspect = stream.source_aspect.clone();
+    let audio = stream.audio.clone();
+    let av_sync = stream.av_sync.clone();
+    let spectrum_bands = stream.spectrum_bands;
+    // Unlike `stats.received`, frame indices start over with every pipeline.
+    let frame_count = AtomicU64::new(0);
+    if let Some(export) = export.lock().unwrap().as_mut() {
+        export.restart();
+    }
+
+    gst::init().map_err(VideoError::Init)?;
 
     let pipeline = gst::Pipeline::new(None);
-    let src = gst::ElementFactory::make("v4l2src", None).map_err(|_| MissingElement("v4l2src"))?;
-    //let src = gst::ElementFactory::make("videotestsrc", None)
-    //    .map_err(|_| MissingElement("videotestsrc"))?;
-    let dec = gst::ElementFactory::make("jpegdec", None).map_err(|_| MissingElement("jpegdec"))?;
-    let sink = gst::ElementFactory::make("appsink", None).map_err(|_| MissingElement("appsink"))?;
+    let sink = make_element("appsink")?;
+    let src = match source {
+        VideoSource::Camera { .. } => {
+            let src = make_element("v4l2src")?;
+            src.set_property("device", source.device_path().unwrap_or(DEFAULT_DEVICE));
+            //let src = make_element("videotestsrc")?;
+            let dec = make_element("jpegdec")?;
+
+            pipeline.add_many(&[&src, &dec, &sink])?;
+            src.link(&dec)?;
+            dec.link(&sink)?;
+            src
+        }
+        VideoSource::Uri(uri) => {
+            let src = make_element("uridecodebin")?;
+            src.set_property("uri", uri);
+            let convert = make_element("videoconvert")?;
+            let scale = make_element("videoscale")?;
+
+            pipeline.add_many(&[&src, &convert, &scale, &sink])?;
+            gst::Element::link_many(&[&convert, &scale, &sink])?;
+
+            // Remember the shape of the decoded video, the scaler stretches
+            // it to the fixed output size.
+            if let Some(pad) = convert.static_pad("sink") {
+                pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+                    if let Some(gst::PadProbeData::Event(ref event)) = info.data {
+                        if let gst::EventView::Caps(caps) = event.view() {
+                            if let Ok(info) = gst_video::VideoInfo::from_caps(caps.caps()) {
+                                let par = info.par();
+                                let aspect = info.width() as f32 * par.numer() as f32
+                                    / (info.height() as f32 * par.denom() as f32);
+                                *source_aspect.write().unwrap() = Some(aspect);
+                            }
+                        }
+                    }
+                    gst::PadProbeReturn::Ok
+                });
+            }
+
+            // uridecodebin only exposes its pads once it knows what the URI
+            // contains, so the video pad is linked when it shows up. Audio
+            // gets a branch of its own if it was asked for.
+            let convert = convert.downgrade();
+            let weak_pipeline = pipeline.downgrade();
+            let audio_linked = std::sync::atomic::AtomicBool::new(false);
+            src.connect_pad_added(move |_, pad| {
+                let (convert, pipeline) = match (convert.upgrade(), weak_pipeline.upgrade()) {
+                    (Some(convert), Some(pipeline)) => (convert, pipeline),
+                    _ => return,
+                };
+                let media = pad
+                    .current_caps()
+                    .and_then(|caps| caps.structure(0).map(|s| s.name().to_string()))
+                    .unwrap_or_default();
+                if media.starts_with("audio/") {
+                    if let Some(audio) = &audio {
+                        if !audio_linked.swap(true, Ordering::Relaxed) {
+                            if let Err(err) =
+                                attach_audio_branch(&pipeline, pad, audio.clone(), spectrum_bands)
+                            {
+                                warn!("No sound: {}", err);
+                            }
+                        }
+                    }
+                    return;
+                }
+                let sink_pad = convert
+                    .static_pad("sink")
+                    .expect("videoconvert without sink pad. Shouldn't happen!");
+                if media.starts_with("video/") && !sink_pad.is_linked() {
+                    let _ = pad.link(&sink_pad);
+                }
+            });
+            src
+        }
+        VideoSource::Composite(inputs) => {
+            pipeline.add(&sink)?;
+            build_compositor(&pipeline, inputs, &sink)?
+        }
+    };
 
-    pipeline.add_many(&[&src, &dec, &sink])?;
-    src.link(&dec)?;
-    dec.link(&sink)?;
+    // Count the bytes leaving the source, which for network and compressed
+    // sources is the bitrate of the stream before decoding.
+    if let Some(pad) = src.static_pad("src") {
+        let stats = stats.clone();
+        pad.add_probe(gst::PadProbeType::BUFFER, move |_, info| {
+            if let Some(gst::PadProbeData::Buffer(ref buffer)) = info.data {
+                stats
+                    .bytes
+                    .fetch_add(buffer.size() as u64, Ordering::Relaxed);
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
+
+    // Before the thumbnail, so it shows the text too.
+    if let Some(text_overlay) = &stream.text_overlay {
+        text_overlay.attach(&pipeline, &sink)?;
+    }
+    if let Some(time_overlay) = &stream.time_overlay {
+        time_overlay.attach(&pipeline, &sink)?;
+    }
+    // Time shift can be enabled at any time, so the recording branch is
+    // always there. Without the JPEG plugin the stream plays without it.
+    match attach_recorder(&pipeline, &sink, time_shift.clone()) {
+        Err(VideoError::MissingElement(plugin)) => warn!("No time shift: {}", plugin),
+        result => result?,
+    }
+    if let Some(thumbnail) = &stream.thumbnail {
+        thumbnail.attach(&pipeline, &sink)?;
+    }
 
     let appsink = sink
         .dynamic_cast::<gst_app::AppSink>()
         .expect("Sink element is expected to be an appsink!");
+    // Have the sink judge buffer lateness so upstream elements post QoS messages.
+    appsink.set_property("qos", true);
+
+    // Keep track of what was actually negotiated, to make sense of renegotiations
+    // after the fact.
+    if let Some(pad) = appsink.static_pad("sink") {
+        pad.add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            if let Some(gst::PadProbeData::Event(ref event)) = info.data {
+                if let gst::EventView::Caps(caps) = event.view() {
+                    timeline
+                        .lock()
+                        .unwrap()
+                        .push(TimelineEvent::Caps(caps.caps().to_string()));
+                }
+            }
+            gst::PadProbeReturn::Ok
+        });
+    }
 
     // Tell the appsink what format we want. It will then be the audiotestsrc's job to
     // provide the format we request.
@@ -116,8 +900,8 @@ pub fn create_pipeline(image_raw: Arc<RwLock<ImageRaw>>) -> Result<gst::Pipeline
     // both elements will happen during pre-rolling of the pipeline.
     appsink.set_caps(Some(
         &gst::Caps::builder("video/x-raw")
-            .field("width", 176)
-            .field("height", 144)
+            .field("width", WIDTH as i32)
+            .field("height", HEIGHT as i32)
             .field("format", "RGB")
             .build(),
     ));
@@ -170,11 +954,100 @@ pub fn create_pipeline(image_raw: Arc<RwLock<ImageRaw>>) -> Result<gst::Pipeline
                     gst::FlowError::Error
                 })?;
 
-                let mut data = image_raw.write().unwrap();
-                for (dest_chunk, src_chunk) in data.chunks_exact_mut(4).zip(samples.chunks_exact(3))
+                // Check the buffer against the negotiated caps before touching the
+                // frame, a renegotiation or a misbehaving driver must not corrupt it.
+                let info = match sample
+                    .caps()
+                    .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+                {
+                    Some(info) => info,
+                    None => {
+                        frames.push(FrameEvent::Rejected(FrameRejection::InvalidCaps));
+                        return Ok(gst::FlowSuccess::Ok);
+                    }
+                };
+                if info.width() != WIDTH || info.height() != HEIGHT {
+                    frames.push(FrameEvent::Rejected(FrameRejection::UnexpectedSize {
+                        width: info.width(),
+                        height: info.height(),
+                    }));
+                    return Ok(gst::FlowSuccess::Ok);
+                }
+                let stride = info.stride()[0] as usize;
+                let row_bytes = WIDTH as usize * 3;
+                let expected = stride * (HEIGHT as usize - 1) + row_bytes;
+                if samples.len() < expected {
+                    frames.push(FrameEvent::Rejected(FrameRejection::ShortBuffer {
+                        expected,
+                        actual: samples.len(),
+                    }));
+                    return Ok(gst::FlowSuccess::Ok);
+                }
+
+                // Everything below gets the frame of this sample, `image_raw`
+                // holds another one while A/V synced or time shifted.
+                let mut frame = vec![0; (WIDTH * HEIGHT * 4) as usize];
+                copy_rgb_rows(&mut frame, samples, stride);
+
+                // While time shifted, the shown frame is picked from the
+                // history by `play_time_shift`.
+                let live = time_shift
+                    .lock()
+                    .unwrap()
+                    .as_ref()
+                    .map_or(true, TimeShift::is_live);
+                // Frames synced to the audio are shown by
+                // `present_av_synced_frames` once their time comes.
+                let mut av_sync = av_sync.lock().unwrap();
+                let held = match (av_sync.as_mut(), buffer.pts()) {
+                    (Some(av_sync), Some(pts)) if live => {
+                        let dropped = av_sync.push(pts, frame.clone());
+                        if dropped > 0 {
+                            stats.dropped.fetch_add(dropped, Ordering::Relaxed);
+                            stats.frame_missed(dropped);
+                        }
+                        true
+                    }
+                    _ => false,
+                };
+                drop(av_sync);
+                if live && !held {
+                    let mut image_raw = image_raw.write().unwrap();
+                    image_raw.copy_from_slice(&frame);
+                    *frame_pts.write().unwrap() = buffer.pts();
+                }
+                *last_sample.write().unwrap() = Some(Instant::now());
+                stats.received.fetch_add(1, Ordering::Relaxed);
+                let frame_index = frame_count.fetch_add(1, Ordering::Relaxed);
+                if live && !held {
+                    stats.frame_written();
+                }
+                if let Some(export) = export.lock().unwrap().as_mut() {
+                    export.offer(frame_index, buffer.pts(), &frame);
+                }
+                if let Some(burst) = burst.lock().unwrap().as_mut() {
+                    burst.offer(frame_index, buffer.pts(), &frame);
+                }
+
+                let mut latency = None;
+                if let (Some(clock), Some(base_time), Some(pts)) =
+                    (appsink.clock(), appsink.base_time(), buffer.pts())
                 {
-                    dest_chunk[..3].copy_from_slice(src_chunk);
+                    latency = clock
+                        .time()
+                        .and_then(|now| now.checked_sub(base_time))
+                        .and_then(|running_time| running_time.checked_sub(pts));
+                    if let Some(latency) = latency {
+                        stats
+                            .latency_ns
+                            .store(latency.nseconds(), Ordering::Relaxed);
+                    }
                 }
+                frames.push(FrameEvent::Ready(FrameInfo::now(
+                    buffer.pts(),
+                    frame_index,
+                    latency.map(|latency| Duration::from_nanos(latency.nseconds())),
+                )));
 
                 //println!("ok {} samples", samples.len());
 
@@ -186,7 +1059,30 @@ pub fn create_pipeline(image_raw: Arc<RwLock<ImageRaw>>) -> Result<gst::Pipeline
     Ok(pipeline)
 }
 
-fn main_loop(pipeline: gst::Pipeline) -> Result<(), Error> {
+/// Frame shown before the first sample, like
+/// [`VideoTexture::image`](crate::material::VideoTexture::image).
+fn opaque_black() -> ImageRaw {
+    let mut image_raw = [0u8; (WIDTH * HEIGHT * 4) as usize];
+    for pixel in image_raw.chunks_exact_mut(4) {
+        pixel[3] = 255;
+    }
+    image_raw
+}
+
+/// Copies tightly packed RGB rows of `stride` bytes into an opaque RGBA
+/// frame.
+fn copy_rgb_rows(dest: &mut [u8], samples: &[u8], stride: usize) {
+    let row_bytes = WIDTH as usize * 3;
+    for (y, dest_row) in dest.chunks_exact_mut(WIDTH as usize * 4).enumerate() {
+        let src_row = &samples[y * stride..y * stride + row_bytes];
+        for (dest_chunk, src_chunk) in dest_row.chunks_exact_mut(4).zip(src_row.chunks_exact(3)) {
+            dest_chunk[..3].copy_from_slice(src_chunk);
+            dest_chunk[3] = 255;
+        }
+    }
+}
+
+fn main_loop(pipeline: gst::Pipeline) -> Result<(), VideoError> {
     pipeline.set_state(gst::State::Playing)?;
 
     let bus = pipeline
@@ -196,22 +1092,12 @@ fn main_loop(pipeline: gst::Pipeline) -> Result<(), Error> {
     for msg in bus.iter_timed(gst::ClockTime::NONE) {
         use gst::MessageView;
 
-        match msg.view() {
-            MessageView::Eos(..) => break,
-            MessageView::Error(err) => {
-                pipeline.set_state(gst::State::Null)?;
-                return Err(ErrorMessage {
-                    src: msg
-                        .src()
-                        .map(|s| String::from(s.path_string()))
-                        .unwrap_or_else(|| String::from("None")),
-                    error: err.error().to_string(),
-                    debug: err.debug(),
-                    source: err.error(),
-                }
-                .into());
-            }
-            _ => (),
+        if let MessageView::Eos(..) = msg.view() {
+            break;
+        }
+        if let Some(err) = VideoError::from_message(&msg) {
+            pipeline.set_state(gst::State::Null)?;
+            return Err(err);
         }
     }
 
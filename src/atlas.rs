@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::sprite::Rect;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::material::VideoMaterial;
+
+/// Copies the streams of every [`VideoAtlas`] into its tiles.
+#[derive(Default)]
+pub struct VideoAtlasPlugin;
+
+impl Plugin for VideoAtlasPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_video_atlases);
+    }
+}
+
+/// Many streams packed as `WIDTH`x`HEIGHT` tiles into one image, so a wall of
+/// cameras binds a single texture and shares a single material.
+///
+/// Sprites use [`VideoAtlas::texture_atlas`] with the index of their stream,
+/// meshes use [`VideoAtlas::material`] with UVs from
+/// [`VideoAtlas::remap_uvs`]. A stream can be in several atlases and played
+/// by a [`VideoPlayer`](crate::player::VideoPlayer) at the same time, every
+/// output copies the same latched frame, see
+/// [`FrameLatch`](crate::framesync::FrameLatch).
+#[derive(Component, Debug, Clone)]
+pub struct VideoAtlas {
+    pub streams: Vec<Handle<AppSinkImage>>,
+    pub columns: u32,
+    pub image: Handle<Image>,
+    pub texture_atlas: Handle<TextureAtlas>,
+    pub material: Handle<VideoMaterial>,
+}
+
+impl VideoAtlas {
+    /// Creates the atlas image with `columns` tiles per row and as many rows
+    /// as `streams` needs, plus a sprite atlas and an unlit material over it.
+    pub fn new(
+        streams: Vec<Handle<AppSinkImage>>,
+        columns: u32,
+        images: &mut Assets<Image>,
+        texture_atlases: &mut Assets<TextureAtlas>,
+        materials: &mut Assets<VideoMaterial>,
+    ) -> Self {
+        let columns = columns.max(1);
+        let rows = ((streams.len() as u32 + columns - 1) / columns).max(1);
+        let image = images.add(Image::new_fill(
+            Extent3d {
+                width: WIDTH * columns,
+                height: HEIGHT * rows,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        ));
+        let texture_atlas = texture_atlases.add(TextureAtlas::from_grid(
+            image.clone(),
+            Vec2::new(WIDTH as f32, HEIGHT as f32),
+            columns as usize,
+            rows as usize,
+        ));
+        let material = materials.add(VideoMaterial {
+            texture: Some(image.clone()),
+            ..default()
+        });
+        VideoAtlas {
+            streams,
+            columns,
+            image,
+            texture_atlas,
+            material,
+        }
+    }
+
+    fn rows(&self) -> u32 {
+        ((self.streams.len() as u32 + self.columns - 1) / self.columns).max(1)
+    }
+
+    /// Tile of `stream`, also its index in [`VideoAtlas::texture_atlas`].
+    pub fn index(&self, stream: &Handle<AppSinkImage>) -> Option<usize> {
+        self.streams
+            .iter()
+            .position(|handle| handle.id == stream.id)
+    }
+
+    /// Part of the atlas showing `stream`, in UV coordinates.
+    pub fn uv_rect(&self, stream: &Handle<AppSinkImage>) -> Option<Rect> {
+        let index = self.index(stream)? as u32;
+        let size = Vec2::new(1.0 / self.columns as f32, 1.0 / self.rows() as f32);
+        let min = Vec2::new((index % self.columns) as f32, (index / self.columns) as f32) * size;
+        Some(Rect {
+            min,
+            max: min + size,
+        })
+    }
+
+    /// Maps the UVs of `mesh` from the whole texture to the tile of
+    /// `stream`. Returns `false` if the stream is not in the atlas or the mesh
+    /// has no UVs.
+    pub fn remap_uvs(&self, stream: &Handle<AppSinkImage>, mesh: &mut Mesh) -> bool {
+        let rect = match self.uv_rect(stream) {
+            Some(rect) => rect,
+            None => return false,
+        };
+        match mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => {
+                for uv in uvs.iter_mut() {
+                    let mapped = rect.min + Vec2::from(*uv) * (rect.max - rect.min);
+                    *uv = mapped.into();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl AppSinkImage {
+    /// Copies the frame latched for this Bevy frame into the tile of `image`
+    /// at `column` and `row`, if the stream has a new one.
+    ///
+    /// Returns whether `image` was updated.
+    pub fn copy_to_tile(&self, image: &mut Image, column: u32, row: u32) -> bool {
+        let frame = match self.latch.fresh() {
+            Some((_, frame)) => frame,
+            None => return false,
+        };
+        let image_width = image.texture_descriptor.size.width as usize;
+        let row_bytes = WIDTH as usize * 4;
+        let origin =
+            (row as usize * HEIGHT as usize * image_width + column as usize * WIDTH as usize) * 4;
+        for (y, src_row) in frame.chunks_exact(row_bytes).enumerate() {
+            let start = origin + y * image_width * 4;
+            if let Some(dest_row) = image.data.get_mut(start..start + row_bytes) {
+                dest_row.copy_from_slice(src_row);
+            }
+        }
+        true
+    }
+}
+
+fn update_video_atlases(
+    atlases: Query<&VideoAtlas>,
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    for atlas in atlases.iter() {
+        // Only touch the image when a tile changes, every change re-uploads
+        // the whole atlas.
+        let pending = atlas.streams.iter().any(|stream| {
+            appsinks
+                .get(stream)
+                .map_or(false, |appsink| appsink.latch.is_fresh())
+        });
+        if !pending {
+            continue;
+        }
+        let image = match images.get_mut(&atlas.image) {
+            Some(image) => image,
+            None => continue,
+        };
+        let mut updated = false;
+        for (index, stream) in atlas.streams.iter().enumerate() {
+            let index = index as u32;
+            if let Some(appsink) = appsinks.get(stream) {
+                if appsink.copy_to_tile(image, index % atlas.columns, index / atlas.columns) {
+                    appsink.latch.shown(atlas.image.id, appsink.frame_serial());
+                    updated = true;
+                }
+            }
+        }
+        // See `update_video_textures`, the material has to be touched to see
+        // the new texture.
+        if updated {
+            materials.get_mut(&atlas.material);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bevy::audio::Decodable;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use byte_slice_cast::*;
+use gst::prelude::*;
+
+use crate::appsink::make_element;
+use crate::beat::BeatDetector;
+use crate::error::VideoError;
+use crate::levels::LEVEL_INTERVAL;
+use crate::spectrum::AudioSpectrum;
+
+/// Label of the audio sub-asset, play it with
+/// `audio.play(asset_server.load("movie.sinkimage#audio"))` on an
+/// `Audio<AudioStream>`.
+pub const AUDIO_LABEL: &str = "audio";
+
+/// Format the audio is converted to by default.
+pub const AUDIO_RATE: u32 = 48_000;
+pub const AUDIO_CHANNELS: u16 = 2;
+
+/// Sample type the audio appsink receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// 16-bit integers, what `bevy_audio` plays.
+    S16,
+    /// 32-bit floats, for engines mixing in float without quantization.
+    F32,
+}
+
+impl SampleFormat {
+    fn caps_name(self) -> &'static str {
+        match self {
+            SampleFormat::S16 => "S16LE",
+            SampleFormat::F32 => "F32LE",
+        }
+    }
+
+    pub(crate) fn parse(value: &str) -> Option<SampleFormat> {
+        match value {
+            "s16" => Some(SampleFormat::S16),
+            "f32" => Some(SampleFormat::F32),
+            _ => None,
+        }
+    }
+}
+
+/// Format the decoded audio is converted and resampled to, set with the
+/// `audio_format`, `audio_rate` and `audio_channels` keys of the
+/// `.sinkimage` file. A single channel downmixes the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub sample: SampleFormat,
+    pub rate: u32,
+    pub channels: u16,
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        AudioFormat {
+            sample: SampleFormat::S16,
+            rate: AUDIO_RATE,
+            channels: AUDIO_CHANNELS,
+        }
+    }
+}
+
+/// Most audio kept waiting for playback. Older samples are dropped when
+/// playback falls behind, so the sound doesn't lag further and further.
+const MAX_BUFFERED: Duration = Duration::from_millis(500);
+
+/// Interleaved samples in the [`SampleFormat`] the audio appsink receives.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioFrames {
+    S16(Vec<i16>),
+    F32(Vec<f32>),
+}
+
+impl AudioFrames {
+    pub fn sample_format(&self) -> SampleFormat {
+        match self {
+            AudioFrames::S16(_) => SampleFormat::S16,
+            AudioFrames::F32(_) => SampleFormat::F32,
+        }
+    }
+
+    /// Number of samples, all channels counted.
+    pub fn len(&self) -> usize {
+        match self {
+            AudioFrames::S16(samples) => samples.len(),
+            AudioFrames::F32(samples) => samples.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The samples as floats from -1 to 1, converted if they are integers.
+    pub fn to_f32(&self) -> Vec<f32> {
+        match self {
+            AudioFrames::S16(samples) => samples.iter().map(|sample| s16_to_f32(*sample)).collect(),
+            AudioFrames::F32(samples) => samples.clone(),
+        }
+    }
+
+    /// The samples as 16-bit integers, converted if they are floats.
+    pub fn to_s16(&self) -> Vec<i16> {
+        match self {
+            AudioFrames::S16(samples) => samples.clone(),
+            AudioFrames::F32(samples) => samples.iter().map(|sample| f32_to_s16(*sample)).collect(),
+        }
+    }
+}
+
+fn s16_to_f32(sample: i16) -> f32 {
+    sample as f32 / -(i16::MIN as f32)
+}
+
+fn f32_to_s16(sample: f32) -> i16 {
+    (sample * i16::MAX as f32).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+/// Samples waiting in an [`AudioBuffer`], kept as received.
+#[derive(Debug)]
+enum SampleQueue {
+    S16(VecDeque<i16>),
+    F32(VecDeque<f32>),
+}
+
+impl SampleQueue {
+    fn new(format: SampleFormat) -> Self {
+        match format {
+            SampleFormat::S16 => SampleQueue::S16(VecDeque::new()),
+            SampleFormat::F32 => SampleQueue::F32(VecDeque::new()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            SampleQueue::S16(samples) => samples.len(),
+            SampleQueue::F32(samples) => samples.len(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            SampleQueue::S16(samples) => samples.clear(),
+            SampleQueue::F32(samples) => samples.clear(),
+        }
+    }
+
+    /// Appends `new`, converted if the appsink format changed under it.
+    fn extend(&mut self, new: &AudioFrames) {
+        match (self, new) {
+            (SampleQueue::S16(samples), AudioFrames::S16(new)) => samples.extend(new),
+            (SampleQueue::F32(samples), AudioFrames::F32(new)) => samples.extend(new),
+            (SampleQueue::S16(samples), new) => samples.extend(new.to_s16()),
+            (SampleQueue::F32(samples), new) => samples.extend(new.to_f32()),
+        }
+    }
+
+    /// Takes the first `count` samples.
+    fn take(&mut self, count: usize) -> AudioFrames {
+        match self {
+            SampleQueue::S16(samples) => AudioFrames::S16(samples.drain(..count).collect()),
+            SampleQueue::F32(samples) => AudioFrames::F32(samples.drain(..count).collect()),
+        }
+    }
+
+    fn drop_front(&mut self, count: usize) {
+        match self {
+            SampleQueue::S16(samples) => {
+                samples.drain(..count);
+            }
+            SampleQueue::F32(samples) => {
+                samples.drain(..count);
+            }
+        }
+    }
+}
+
+/// Decoded samples waiting to be played, filled by the audio appsink of a
+/// stream and drained by `bevy_audio`, or by another engine through
+/// [`AudioBuffer::pop_frames`]. Samples are kept in the [`SampleFormat`]
+/// of the stream, so engines mixing in float get them unquantized.
+#[derive(Debug)]
+pub struct AudioBuffer {
+    samples: Mutex<SampleQueue>,
+    /// Timestamp of the end of the last pushed samples.
+    end: Mutex<Option<gst::ClockTime>>,
+    /// Volume of each channel, set by `SpatialAudio`.
+    gains: Mutex<Vec<f32>>,
+    pub(crate) beats: Mutex<BeatDetector>,
+    pub sample_format: SampleFormat,
+    pub channels: u16,
+    pub rate: u32,
+}
+
+impl Default for AudioBuffer {
+    fn default() -> Self {
+        AudioBuffer::new(AudioFormat::default())
+    }
+}
+
+impl AudioBuffer {
+    pub fn new(format: AudioFormat) -> Self {
+        AudioBuffer {
+            samples: Mutex::new(SampleQueue::new(format.sample)),
+            end: Mutex::new(None),
+            gains: Mutex::new(vec![1.0; format.channels as usize]),
+            beats: Mutex::new(BeatDetector::default()),
+            sample_format: format.sample,
+            channels: format.channels,
+            rate: format.rate,
+        }
+    }
+
+    fn max_samples(&self) -> usize {
+        (MAX_BUFFERED.as_secs_f64() * (self.rate * self.channels as u32) as f64) as usize
+    }
+
+    pub(crate) fn push(&self, new: &AudioFrames, end: Option<gst::ClockTime>) {
+        let channels = self.channels as usize;
+        match new {
+            AudioFrames::F32(new) => self.beats.lock().unwrap().push(new, channels),
+            new => self.beats.lock().unwrap().push(&new.to_f32(), channels),
+        }
+        let mut samples = self.samples.lock().unwrap();
+        *self.end.lock().unwrap() = end;
+        samples.extend(new);
+        // Whole frames only, so channels stay in place.
+        let excess = samples.len().saturating_sub(self.max_samples());
+        let excess = (excess + channels - 1) / channels * channels;
+        samples.drop_front(excess.min(samples.len()));
+    }
+
+    /// Takes up to `frames` frames of interleaved samples in the
+    /// [`SampleFormat`] of the stream, for playing it through an engine
+    /// other than `bevy_audio`. Don't play the `audio` sub-asset at the
+    /// same time, both would get half the samples.
+    pub fn pop_frames(&self, frames: usize) -> AudioFrames {
+        let mut samples = self.samples.lock().unwrap();
+        let count = (frames * self.channels as usize).min(samples.len());
+        samples.take(count)
+    }
+
+    /// Drops everything not played yet, such as after a seek.
+    pub fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+        *self.end.lock().unwrap() = None;
+        self.beats.lock().unwrap().reset();
+    }
+
+    /// Duration of the samples waiting to be played.
+    pub fn buffered(&self) -> Duration {
+        let samples = self.samples.lock().unwrap().len();
+        Duration::from_secs_f64(samples as f64 / (self.rate * self.channels as u32) as f64)
+    }
+
+    /// Sets the volume of each channel, such as for panning.
+    pub fn set_gains(&self, gains: &[f32]) {
+        let mut current = self.gains.lock().unwrap();
+        for (current, gain) in current.iter_mut().zip(gains) {
+            *current = *gain;
+        }
+    }
+
+    /// Timestamp of the sample being played, the clock video is synced to.
+    pub fn played_position(&self) -> Option<gst::ClockTime> {
+        let end = (*self.end.lock().unwrap())?;
+        let buffered = gst::ClockTime::from_nseconds(self.buffered().as_nanos() as u64);
+        Some(end.saturating_sub(buffered))
+    }
+}
+
+/// The sound of a stream, as an audio source for `bevy_audio`.
+///
+/// Playback never ends on its own, silence is played while the stream has
+/// no audio. Stop it through the `AudioSink` returned by `Audio::play`.
+#[derive(Debug, TypeUuid)]
+#[uuid = "b5d2c0a4-3e71-4f6b-9c18-7a4e2f90d3c6"]
+pub struct AudioStream {
+    pub buffer: Arc<AudioBuffer>,
+}
+
+impl Decodable for AudioStream {
+    type Decoder = AudioStreamDecoder;
+    type DecoderItem = i16;
+
+    fn decoder(&self) -> Self::Decoder {
+        AudioStreamDecoder {
+            buffer: self.buffer.clone(),
+            frame: Vec::new(),
+            channel: 0,
+        }
+    }
+}
+
+/// Plays an [`AudioBuffer`] through rodio.
+pub struct AudioStreamDecoder {
+    buffer: Arc<AudioBuffer>,
+    /// Samples of the frame being played, with their gain applied.
+    frame: Vec<i16>,
+    /// Channel of the next sample.
+    channel: usize,
+}
+
+impl Iterator for AudioStreamDecoder {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        // Whole frames are taken at once, so channels stay in place when the
+        // buffer runs dry or is cleared.
+        if self.channel == 0 {
+            let channels = self.buffer.channels as usize;
+            let mut samples = self.buffer.samples.lock().unwrap();
+            self.frame.clear();
+            // An empty buffer plays silence, ending the source would stop
+            // the sink for good.
+            if samples.len() >= channels {
+                let gains = self.buffer.gains.lock().unwrap();
+                match samples.take(channels) {
+                    AudioFrames::S16(frame) => self.frame.extend(
+                        frame.into_iter().zip(gains.iter()).map(|(sample, gain)| {
+                            (sample as f32 * gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+                        }),
+                    ),
+                    AudioFrames::F32(frame) => self.frame.extend(
+                        frame
+                            .into_iter()
+                            .zip(gains.iter())
+                            .map(|(sample, gain)| f32_to_s16(sample * gain)),
+                    ),
+                }
+            } else {
+                self.frame.resize(channels, 0);
+            }
+        }
+        let sample = self.frame[self.channel];
+        self.channel = (self.channel + 1) % self.frame.len();
+        Some(sample)
+    }
+}
+
+impl rodio::Source for AudioStreamDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.buffer.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.buffer.rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Decodes the audio pad `pad` of `uridecodebin` into `buffer`, through
+/// elements added to `pipeline` while it is already running. Analyzes
+/// `spectrum_bands` frequency bands on the way if set.
+pub(crate) fn attach_audio_branch(
+    pipeline: &gst::Pipeline,
+    pad: &gst::Pad,
+    buffer: Arc<AudioBuffer>,
+    spectrum_bands: Option<u32>,
+) -> Result<(), VideoError> {
+    let convert = make_element("audioconvert")?;
+    let resample = make_element("audioresample")?;
+    // Posts the levels read into `VideoAudioLevels`.
+    let level = make_element("level")?;
+    level.set_property("interval", LEVEL_INTERVAL.as_nanos() as u64);
+    let spectrum = spectrum_bands.map(AudioSpectrum::element).transpose()?;
+    let sink = make_element("appsink")?;
+    let mut elements = vec![&convert, &resample, &level];
+    elements.extend(&spectrum);
+    elements.push(&sink);
+    pipeline.add_many(&elements)?;
+    gst::Element::link_many(&elements)?;
+
+    let appsink = sink
+        .dynamic_cast::<gst_app::AppSink>()
+        .expect("Sink element is expected to be an appsink!");
+    appsink.set_caps(Some(
+        &gst::Caps::builder("audio/x-raw")
+            .field("format", buffer.sample_format.caps_name())
+            .field("layout", "interleaved")
+            .field("rate", buffer.rate as i32)
+            .field("channels", buffer.channels as i32)
+            .build(),
+    ));
+    appsink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let samples = sample.buffer().ok_or(gst::FlowError::Error)?;
+                let end = samples
+                    .pts()
+                    .zip(samples.duration())
+                    .map(|(pts, duration)| pts + duration);
+                let map = samples.map_readable().map_err(|_| gst::FlowError::Error)?;
+                let samples = match buffer.sample_format {
+                    SampleFormat::S16 => AudioFrames::S16(
+                        map.as_slice_of::<i16>()
+                            .map_err(|_| gst::FlowError::Error)?
+                            .to_vec(),
+                    ),
+                    SampleFormat::F32 => AudioFrames::F32(
+                        map.as_slice_of::<f32>()
+                            .map_err(|_| gst::FlowError::Error)?
+                            .to_vec(),
+                    ),
+                };
+                buffer.push(&samples, end);
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+
+    convert.sync_state_with_parent()?;
+    resample.sync_state_with_parent()?;
+    level.sync_state_with_parent()?;
+    if let Some(spectrum) = &spectrum {
+        spectrum.sync_state_with_parent()?;
+    }
+    appsink.sync_state_with_parent()?;
+    let sink_pad = convert
+        .static_pad("sink")
+        .expect("audioconvert without sink pad. Shouldn't happen!");
+    pad.link(&sink_pad).map_err(|err| {
+        VideoError::Unsupported(format!("failed to link the audio track: {:?}", err))
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(sample: SampleFormat) -> AudioBuffer {
+        AudioBuffer::new(AudioFormat {
+            sample,
+            rate: 100,
+            channels: 2,
+        })
+    }
+
+    #[test]
+    fn pops_frames_in_the_configured_format() {
+        let ints = buffer(SampleFormat::S16);
+        ints.push(&AudioFrames::S16(vec![1, -2, 3, -4, 5, -6]), None);
+        assert_eq!(ints.pop_frames(2), AudioFrames::S16(vec![1, -2, 3, -4]));
+        assert_eq!(ints.pop_frames(2), AudioFrames::S16(vec![5, -6]));
+        assert!(ints.pop_frames(2).is_empty());
+
+        let floats = buffer(SampleFormat::F32);
+        floats.push(&AudioFrames::F32(vec![0.25, -0.5]), None);
+        assert_eq!(floats.pop_frames(1), AudioFrames::F32(vec![0.25, -0.5]));
+    }
+
+    #[test]
+    fn converts_samples_pushed_in_another_format() {
+        let floats = buffer(SampleFormat::F32);
+        floats.push(&AudioFrames::S16(vec![i16::MIN, 0]), None);
+        assert_eq!(floats.pop_frames(1), AudioFrames::F32(vec![-1.0, 0.0]));
+
+        let ints = buffer(SampleFormat::S16);
+        ints.push(&AudioFrames::F32(vec![1.0, -2.0]), None);
+        assert_eq!(
+            ints.pop_frames(1),
+            AudioFrames::S16(vec![i16::MAX, i16::MIN])
+        );
+    }
+
+    #[test]
+    fn drops_whole_frames_past_the_limit() {
+        let ints = buffer(SampleFormat::S16);
+        // 500 ms at 100 Hz in stereo.
+        let max = ints.max_samples();
+        assert_eq!(max, 100);
+        ints.push(&AudioFrames::S16((0..max as i16 + 3).collect()), None);
+        let kept = ints.pop_frames(max);
+        assert_eq!(kept.len(), max - 1);
+        assert_eq!(kept.to_s16()[0], 4);
+    }
+}
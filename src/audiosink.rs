@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::asset::{AssetLoader, BoxedFuture, HandleId, LoadContext, LoadedAsset};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use gst::prelude::*;
+
+use crate::appsink::{make_element, set_playing};
+use crate::audio::{attach_audio_branch, AudioBuffer, AudioStream, AUDIO_LABEL};
+use crate::config::{SinkImageConfig, VideoSource};
+use crate::error::VideoError;
+use crate::levels::AudioLevels;
+use crate::playback::{
+    pipeline_duration, pipeline_position, seek_pipeline, set_pipeline_state, stop_pipeline,
+};
+use crate::recovery::{RecoveryPolicy, RecoveryState};
+use crate::spectrum::AudioSpectrum;
+use crate::tags::merge_tags;
+
+/// Registers the `.sinkaudio` asset, for sources without video such as
+/// internet radio or music files.
+pub struct AppSinkAudioPlugin;
+
+impl Plugin for AppSinkAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<AppSinkAudio>()
+            .init_asset_loader::<AppSinkAudioLoader>()
+            .add_event::<AudioTagsUpdated>()
+            .add_event::<AudioRecovering>()
+            .add_event::<AudioRecovered>()
+            .add_event::<AudioGaveUp>()
+            .add_system(start_audio_pipelines)
+            .add_system(poll_audio_bus)
+            .add_system(retry_audio_pipelines);
+    }
+}
+
+/// Sent when the tags of an audio-only stream change, such as the title on
+/// an internet radio.
+pub struct AudioTagsUpdated {
+    pub handle: Handle<AppSinkAudio>,
+}
+
+/// Sent when a failed audio-only stream has been torn down and a restart is
+/// scheduled, like [`VideoRecovering`](crate::recovery::VideoRecovering).
+pub struct AudioRecovering {
+    pub handle: Handle<AppSinkAudio>,
+    pub attempt: u32,
+    pub delay: Duration,
+    pub error: VideoError,
+}
+
+/// Sent when a restarted audio-only stream plays again.
+pub struct AudioRecovered {
+    pub handle: Handle<AppSinkAudio>,
+    pub attempts: u32,
+}
+
+/// Sent when an audio-only stream failed and will not be restarted anymore.
+pub struct AudioGaveUp {
+    pub handle: Handle<AppSinkAudio>,
+    pub attempts: u32,
+    pub error: VideoError,
+}
+
+/// A stream without video, loaded from a `.sinkaudio` file holding the same
+/// keys as a `.sinkimage` file. Only `uri`, `spectrum` and the `audio_*`
+/// format keys apply:
+///
+/// ```text
+/// uri = https://radio.example.com/stream.mp3
+/// spectrum = 32
+/// ```
+///
+/// Play the sound with
+/// `audio.play(asset_server.load("radio.sinkaudio#audio"))` on an
+/// `Audio<AudioStream>`.
+///
+/// Like video streams, failed streams are restarted following `recovery`.
+/// A live stream ending, such as a radio dropping the connection, counts as
+/// a failure, while files simply stop at their end.
+#[derive(Debug, TypeUuid)]
+#[uuid = "0f6e5b2a-9d47-4c8e-b1a3-5c2d7e8f4a91"]
+pub struct AppSinkAudio {
+    pub uri: String,
+    pub pipeline: Option<gst::Pipeline>,
+    pub bus: Option<gst::Bus>,
+    pub error: Option<VideoError>,
+    pub recovery: RecoveryPolicy,
+    pub recovery_state: RecoveryState,
+    pub buffer: Arc<AudioBuffer>,
+    /// Tags received from the pipeline, such as title or artist.
+    pub tags: HashMap<String, String>,
+    /// Frequency bands analyzed into `spectrum`, if any.
+    pub spectrum_bands: Option<u32>,
+    /// Last levels measured, for VU meters.
+    pub levels: Option<AudioLevels>,
+    /// Last spectrum analyzed, with `spectrum_bands` set.
+    pub spectrum: Option<AudioSpectrum>,
+}
+
+#[derive(Default)]
+pub struct AppSinkAudioLoader;
+
+impl AssetLoader for AppSinkAudioLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let config = SinkImageConfig::parse(std::str::from_utf8(bytes)?)?;
+            let uri = match config.source {
+                VideoSource::Uri(uri) => uri,
+                _ => {
+                    return Err(VideoError::Unsupported(String::from(
+                        "audio-only streams need a `uri`",
+                    ))
+                    .into())
+                }
+            };
+            let buffer = Arc::new(AudioBuffer::new(config.audio_format));
+            load_context.set_labeled_asset(
+                AUDIO_LABEL,
+                LoadedAsset::new(AudioStream {
+                    buffer: buffer.clone(),
+                }),
+            );
+            load_context.set_default_asset(LoadedAsset::new(AppSinkAudio {
+                uri,
+                pipeline: None,
+                bus: None,
+                error: None,
+                recovery: RecoveryPolicy::default(),
+                recovery_state: RecoveryState::default(),
+                buffer,
+                tags: HashMap::new(),
+                spectrum_bands: config.spectrum,
+                levels: None,
+                spectrum: None,
+            }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sinkaudio"]
+    }
+}
+
+impl AppSinkAudio {
+    pub fn start(&mut self) -> Result<(), VideoError> {
+        self.stop();
+        gst::init().map_err(VideoError::Init)?;
+
+        let pipeline = gst::Pipeline::new(None);
+        let src = make_element("uridecodebin")?;
+        src.set_property("uri", &self.uri);
+        pipeline.add(&src)?;
+
+        // Video tracks of music files, such as cover art, are thrown away.
+        let weak_pipeline = pipeline.downgrade();
+        let buffer = self.buffer.clone();
+        let spectrum_bands = self.spectrum_bands;
+        let audio_linked = AtomicBool::new(false);
+        src.connect_pad_added(move |_, pad| {
+            let pipeline = match weak_pipeline.upgrade() {
+                Some(pipeline) => pipeline,
+                None => return,
+            };
+            let media = pad
+                .current_caps()
+                .and_then(|caps| caps.structure(0).map(|s| s.name().to_string()))
+                .unwrap_or_default();
+            let result =
+                if media.starts_with("audio/") && !audio_linked.swap(true, Ordering::Relaxed) {
+                    attach_audio_branch(&pipeline, pad, buffer.clone(), spectrum_bands)
+                } else {
+                    discard_pad(&pipeline, pad)
+                };
+            if let Err(err) = result {
+                warn!("Failed to link {}: {}", media, err);
+            }
+        });
+
+        set_playing(&pipeline)?;
+        self.bus = pipeline.bus();
+        self.pipeline = Some(pipeline);
+        self.error = None;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        stop_pipeline(&mut self.pipeline, &mut self.bus);
+        self.buffer.clear();
+    }
+
+    pub fn pause(&self) -> Result<(), VideoError> {
+        set_pipeline_state(self.pipeline.as_ref(), gst::State::Paused)
+    }
+
+    pub fn play(&self) -> Result<(), VideoError> {
+        set_pipeline_state(self.pipeline.as_ref(), gst::State::Playing)
+    }
+
+    /// Jumps to `position`, dropping the sound not played yet.
+    pub fn seek(&self, position: Duration) -> Result<(), VideoError> {
+        seek_pipeline(self.pipeline.as_ref(), position)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Current playback position, `None` if not running or unknown.
+    pub fn position(&self) -> Option<Duration> {
+        pipeline_position(self.pipeline.as_ref()?)
+    }
+
+    /// Length of the media, `None` for live sources or if unknown.
+    pub fn duration(&self) -> Option<Duration> {
+        pipeline_duration(self.pipeline.as_ref()?)
+    }
+
+    /// Tears the stream down after `err` and schedules a restart if
+    /// `recovery` allows it.
+    fn fail(
+        &mut self,
+        handle: &Handle<AppSinkAudio>,
+        err: VideoError,
+        now: Duration,
+        events: &mut AudioRecoveryEvents,
+    ) {
+        error!("Audio stream {} failed: {}", self.uri, err);
+        self.stop();
+        match self.recovery_state.schedule(&self.recovery, &err, now) {
+            Some(delay) => events.recovering.send(AudioRecovering {
+                handle: handle.clone_weak(),
+                attempt: self.recovery_state.attempts,
+                delay,
+                error: err.clone(),
+            }),
+            None => events.gave_up.send(AudioGaveUp {
+                handle: handle.clone_weak(),
+                attempts: self.recovery_state.attempts,
+                error: err.clone(),
+            }),
+        }
+        self.error = Some(err);
+    }
+}
+
+impl Drop for AppSinkAudio {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Links `pad` to a `fakesink`, so the unused stream doesn't stop the others.
+fn discard_pad(pipeline: &gst::Pipeline, pad: &gst::Pad) -> Result<(), VideoError> {
+    let sink = make_element("fakesink")?;
+    pipeline.add(&sink)?;
+    sink.sync_state_with_parent()?;
+    let sink_pad = sink
+        .static_pad("sink")
+        .expect("fakesink without sink pad. Shouldn't happen!");
+    pad.link(&sink_pad)
+        .map_err(|err| VideoError::Unsupported(format!("failed to link a track: {:?}", err)))?;
+    Ok(())
+}
+
+#[derive(SystemParam)]
+struct AudioRecoveryEvents<'w, 's> {
+    recovering: EventWriter<'w, 's, AudioRecovering>,
+    recovered: EventWriter<'w, 's, AudioRecovered>,
+    gave_up: EventWriter<'w, 's, AudioGaveUp>,
+}
+
+fn start_audio_pipelines(
+    time: Res<Time>,
+    mut asset_events: EventReader<AssetEvent<AppSinkAudio>>,
+    mut streams: ResMut<Assets<AppSinkAudio>>,
+    mut events: AudioRecoveryEvents,
+) {
+    for event in asset_events.iter() {
+        if let AssetEvent::Created { handle } = event {
+            if let Some(stream) = streams.get_mut(handle) {
+                if let Err(err) = stream.start() {
+                    stream.fail(handle, err, time.time_since_startup(), &mut events);
+                }
+            }
+        }
+    }
+}
+
+fn poll_audio_bus(
+    time: Res<Time>,
+    mut streams: ResMut<Assets<AppSinkAudio>>,
+    mut tags_updated: EventWriter<AudioTagsUpdated>,
+    mut events: AudioRecoveryEvents,
+) {
+    let ids: Vec<HandleId> = streams.ids().collect();
+    for id in ids {
+        let handle = Handle::weak(id);
+        let messages: Vec<gst::Message> = match streams.get(&handle).and_then(|s| s.bus.as_ref()) {
+            Some(bus) => bus.iter().collect(),
+            None => continue,
+        };
+        if messages.is_empty() {
+            continue;
+        }
+        let stream = match streams.get_mut(&handle) {
+            Some(stream) => stream,
+            None => continue,
+        };
+        for msg in messages {
+            let failure = match msg.view() {
+                // Files end for good, a live stream ending lost its source.
+                gst::MessageView::Eos(..) if stream.duration().is_none() => Some(VideoError::Ended),
+                _ => VideoError::from_message(&msg),
+            };
+            if let Some(err) = failure {
+                stream.fail(&handle, err, time.time_since_startup(), &mut events);
+                // The pipeline is gone, anything left on its bus is stale.
+                break;
+            } else if let gst::MessageView::StateChanged(change) = msg.view() {
+                let from_pipeline = msg.src().map(|s| s.is::<gst::Pipeline>()).unwrap_or(false);
+                if from_pipeline && change.current() == gst::State::Playing {
+                    let attempts = std::mem::take(&mut stream.recovery_state).attempts;
+                    if attempts > 0 {
+                        info!(
+                            "Audio stream {} recovered after {} attempt(s)",
+                            stream.uri, attempts
+                        );
+                        events.recovered.send(AudioRecovered {
+                            handle: handle.clone_weak(),
+                            attempts,
+                        });
+                    }
+                }
+            } else if let gst::MessageView::Tag(tag) = msg.view() {
+                if merge_tags(&mut stream.tags, &tag.tags()) {
+                    tags_updated.send(AudioTagsUpdated {
+                        handle: handle.clone_weak(),
+                    });
+                }
+            } else if let gst::MessageView::Element(element) = msg.view() {
+                if let Some(levels) = element.structure().and_then(AudioLevels::from_structure) {
+                    stream.levels = Some(levels);
+                } else if let Some(spectrum) =
+                    element.structure().and_then(AudioSpectrum::from_structure)
+                {
+                    stream.spectrum = Some(spectrum);
+                }
+            }
+        }
+    }
+}
+
+fn retry_audio_pipelines(
+    time: Res<Time>,
+    mut streams: ResMut<Assets<AppSinkAudio>>,
+    mut events: AudioRecoveryEvents,
+) {
+    let now = time.time_since_startup();
+    let due: Vec<HandleId> = streams
+        .iter()
+        .filter(|(_, s)| s.recovery_state.retry_at.map_or(false, |at| at <= now))
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in due {
+        let handle = Handle::weak(id);
+        if let Some(stream) = streams.get_mut(&handle) {
+            stream.recovery_state.retry_at = None;
+            info!(
+                "Restarting audio stream {} (attempt {}/{})",
+                stream.uri, stream.recovery_state.attempts, stream.recovery.max_attempts
+            );
+            if let Err(err) = stream.start() {
+                stream.fail(&handle, err, now, &mut events);
+            }
+        }
+    }
+}
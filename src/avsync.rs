@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use gst::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::audio::AudioBuffer;
+use crate::error::VideoError;
+
+/// Most frames held back waiting for the audio. Past it the oldest are
+/// dropped, so video never falls further behind when the audio stalls.
+const MAX_QUEUED: usize = 30;
+
+/// How early a frame may be shown, about half a frame at 25 fps.
+const TOLERANCE: Duration = Duration::from_millis(20);
+
+/// Holds decoded frames until the audio of the stream reaches their
+/// timestamp, so video stays in lip-sync with `bevy_audio` playback over
+/// long files instead of following the pace of the app.
+///
+/// Frames are dropped when the audio is ahead and the last one is repeated
+/// when it is behind. Enabled with the `audio` key of the `.sinkimage` file
+/// unless `av_sync = false`.
+///
+/// Audio backends add different output latencies, `offset_ms` shifts the
+/// video to match: positive values show frames later, negative earlier. Set
+/// it with the `av_offset_ms` key.
+#[derive(Debug)]
+pub struct AvSync {
+    /// Frames with their presentation timestamp, oldest first.
+    frames: VecDeque<(gst::ClockTime, Vec<u8>)>,
+    /// Audio whose playback the video follows.
+    audio: Arc<AudioBuffer>,
+    /// Timestamp of the frame currently shown.
+    shown: Option<gst::ClockTime>,
+    pub offset_ms: i64,
+}
+
+impl AvSync {
+    pub fn new(audio: Arc<AudioBuffer>) -> Self {
+        AvSync {
+            frames: VecDeque::new(),
+            audio,
+            shown: None,
+            offset_ms: 0,
+        }
+    }
+
+    /// Queues a frame and returns how many old ones were dropped to make
+    /// room for it.
+    pub(crate) fn push(&mut self, pts: gst::ClockTime, frame: Vec<u8>) -> u64 {
+        self.frames.push_back((pts, frame));
+        let excess = self.frames.len().saturating_sub(MAX_QUEUED);
+        self.frames.drain(..excess);
+        excess as u64
+    }
+
+    /// Drops the queued frames, such as after a seek.
+    pub(crate) fn clear(&mut self) {
+        self.frames.clear();
+        self.shown = None;
+    }
+
+    /// Timestamp of the sound being played, falling back to `position` of
+    /// the pipeline before any audio arrived.
+    fn clock(&self, position: Option<gst::ClockTime>) -> Option<gst::ClockTime> {
+        self.audio.played_position().or(position)
+    }
+
+    /// The latest frame due at the current audio position, with how many
+    /// due frames were skipped for it. `None` repeats the shown frame.
+    pub(crate) fn next_frame(
+        &mut self,
+        position: Option<gst::ClockTime>,
+    ) -> Option<(Vec<u8>, u64)> {
+        let due = match self.clock(position) {
+            Some(clock) => {
+                let limit = clock.nseconds() as i64 - self.offset_ms * 1_000_000
+                    + TOLERANCE.as_nanos() as i64;
+                self.frames
+                    .iter()
+                    .take_while(|(pts, _)| pts.nseconds() as i64 <= limit)
+                    .count()
+            }
+            // Nothing to follow, show frames as they come.
+            None => self.frames.len(),
+        };
+        let (pts, frame) = self.frames.drain(..due).last()?;
+        self.shown = Some(pts);
+        Some((frame, due as u64 - 1))
+    }
+}
+
+impl AppSinkImage {
+    /// How far the shown frame is behind (positive) or ahead of (negative)
+    /// where the audio and the A/V offset want it, in seconds. `None`
+    /// without A/V sync or audio.
+    pub fn av_drift(&self) -> Option<f64> {
+        let av_sync = self.av_sync.lock().unwrap();
+        let av_sync = av_sync.as_ref()?;
+        let clock = av_sync.audio.played_position()?;
+        let shown = av_sync.shown?;
+        Some(
+            clock.nseconds() as f64 / 1e9
+                - av_sync.offset_ms as f64 / 1e3
+                - shown.nseconds() as f64 / 1e9,
+        )
+    }
+
+    /// Shifts the video against the audio by `offset_ms`, positive values
+    /// delay the video. Fails without A/V sync.
+    pub fn set_av_offset_ms(&self, offset_ms: i64) -> Result<(), VideoError> {
+        let mut av_sync = self.av_sync.lock().unwrap();
+        let av_sync = av_sync
+            .as_mut()
+            .ok_or_else(|| VideoError::Unsupported(String::from("A/V sync is not enabled")))?;
+        av_sync.offset_ms = offset_ms;
+        Ok(())
+    }
+
+    /// The A/V offset in milliseconds, `None` without A/V sync.
+    pub fn av_offset_ms(&self) -> Option<i64> {
+        self.av_sync
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|av_sync| av_sync.offset_ms)
+    }
+}
+
+/// Shows the queued frame matching the audio of streams with A/V sync.
+pub(crate) fn present_av_synced_frames(appsinks: Res<Assets<AppSinkImage>>) {
+    for (_, appsink) in appsinks.iter() {
+        let mut av_sync = appsink.av_sync.lock().unwrap();
+        let av_sync = match av_sync.as_mut() {
+            Some(av_sync) => av_sync,
+            None => continue,
+        };
+        let position = appsink
+            .pipeline
+            .as_ref()
+            .and_then(|pipeline| pipeline.query_position::<gst::ClockTime>());
+        if let Some((frame, skipped)) = av_sync.next_frame(position) {
+            let mut image_raw = appsink.image_raw.write().unwrap();
+            image_raw.copy_from_slice(&frame);
+            *appsink.frame_pts.write().unwrap() = av_sync.shown;
+            drop(image_raw);
+            appsink.stats.frame_written();
+            if skipped > 0 {
+                appsink.stats.dropped.fetch_add(skipped, Ordering::Relaxed);
+                appsink.stats.frame_missed(skipped);
+            }
+        }
+    }
+}
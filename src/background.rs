@@ -0,0 +1,165 @@
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::view::RenderLayers;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::material::VideoTexture;
+use crate::player::VideoPlayer;
+
+/// Render layer used by background videos and their camera. Keep other
+/// entities off it.
+pub const VIDEO_BACKGROUND_LAYER: u8 = 31;
+
+/// Priority of the background camera, below any camera drawing the scene.
+const VIDEO_BACKGROUND_PRIORITY: isize = -100;
+
+/// Shows [`VideoBackgroundBundle`]s behind everything else.
+#[derive(Default)]
+pub struct VideoBackgroundPlugin;
+
+impl Plugin for VideoBackgroundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_background_cameras)
+            .add_system(despawn_background_cameras)
+            .add_system(clear_scene_cameras)
+            .add_system(fit_video_backgrounds);
+    }
+}
+
+/// How the video is scaled to the window when their aspect ratios differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundFit {
+    /// Show the whole video, with bars on the sides or top and bottom.
+    Contain,
+    /// Fill the window, cropping the video.
+    Cover,
+    /// Fill the window, distorting the video.
+    Stretch,
+}
+
+/// Fills the window with a stream, drawn before any other camera.
+///
+/// While a background exists, 3D cameras no longer clear the window so the
+/// video shows through where nothing is drawn.
+#[derive(Component, Debug, Clone)]
+pub struct VideoBackground {
+    pub fit: BackgroundFit,
+}
+
+/// The camera drawing the background of the entity `background`.
+#[derive(Component, Debug)]
+pub struct VideoBackgroundCamera {
+    pub background: Entity,
+}
+
+/// A stream shown as the window background.
+#[derive(Bundle, Clone)]
+pub struct VideoBackgroundBundle {
+    pub background: VideoBackground,
+    pub player: VideoPlayer,
+    pub texture: VideoTexture,
+    #[bundle]
+    pub sprite: SpriteBundle,
+    pub layers: RenderLayers,
+}
+
+impl VideoBackgroundBundle {
+    /// Creates the texture for `stream`.
+    pub fn new(
+        stream: Handle<AppSinkImage>,
+        fit: BackgroundFit,
+        images: &mut Assets<Image>,
+    ) -> Self {
+        let texture = images.add(VideoTexture::image());
+        VideoBackgroundBundle {
+            background: VideoBackground { fit },
+            player: VideoPlayer { stream },
+            texture: VideoTexture(texture.clone()),
+            sprite: SpriteBundle {
+                texture,
+                ..default()
+            },
+            layers: RenderLayers::layer(VIDEO_BACKGROUND_LAYER),
+        }
+    }
+}
+
+fn spawn_background_cameras(
+    mut commands: Commands,
+    backgrounds: Query<Entity, Added<VideoBackground>>,
+) {
+    for background in backgrounds.iter() {
+        commands
+            .spawn_bundle(Camera2dBundle {
+                camera: Camera {
+                    priority: VIDEO_BACKGROUND_PRIORITY,
+                    ..default()
+                },
+                ..default()
+            })
+            // The scene camera draws the UI on top, once is enough.
+            .insert(UiCameraConfig { show_ui: false })
+            .insert(RenderLayers::layer(VIDEO_BACKGROUND_LAYER))
+            .insert(VideoBackgroundCamera { background });
+    }
+}
+
+fn despawn_background_cameras(
+    mut commands: Commands,
+    removed: RemovedComponents<VideoBackground>,
+    cameras: Query<(Entity, &VideoBackgroundCamera)>,
+) {
+    for background in removed.iter() {
+        for (entity, camera) in cameras.iter() {
+            if camera.background == background {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// Stops 3D cameras drawing to a window from clearing over the background,
+/// and restores the default once the last background is gone.
+fn clear_scene_cameras(
+    backgrounds: Query<(), With<VideoBackground>>,
+    mut cameras: Query<(&Camera, &mut Camera3d)>,
+    mut showing: Local<bool>,
+) {
+    let any = !backgrounds.is_empty();
+    if any == *showing {
+        return;
+    }
+    *showing = any;
+    for (camera, mut camera_3d) in cameras.iter_mut() {
+        if !matches!(camera.target, RenderTarget::Window(_)) {
+            continue;
+        }
+        camera_3d.clear_color = if any {
+            ClearColorConfig::None
+        } else {
+            ClearColorConfig::Default
+        };
+    }
+}
+
+fn fit_video_backgrounds(
+    windows: Res<Windows>,
+    mut backgrounds: Query<(&VideoBackground, &mut Sprite)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => Vec2::new(window.width(), window.height()),
+        None => return,
+    };
+    let video = Vec2::new(WIDTH as f32, HEIGHT as f32);
+    for (background, mut sprite) in backgrounds.iter_mut() {
+        let size = match background.fit {
+            BackgroundFit::Stretch => window,
+            BackgroundFit::Contain => video * (window / video).min_element(),
+            BackgroundFit::Cover => video * (window / video).max_element(),
+        };
+        if sprite.custom_size != Some(size) {
+            sprite.custom_size = Some(size);
+        }
+    }
+}
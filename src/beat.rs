@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Frames per analysis window, about 21 ms at 48 kHz.
+const WINDOW: usize = 1024;
+
+/// Windows averaged as the local energy, about a second.
+const HISTORY: usize = 47;
+
+/// How much louder than the local energy a window must be to be a beat.
+const SENSITIVITY: f32 = 1.4;
+
+/// Windows to skip after a beat, about 100 ms, so a single hit doesn't
+/// count twice.
+const COOLDOWN: usize = 5;
+
+/// Energy below which nothing counts as a beat, to ignore noise in silence.
+const MIN_ENERGY: f32 = 1e-4;
+
+/// Sent when the audio of a stream gets suddenly louder, such as on a drum
+/// hit. Needs `audio = true` in the `.sinkimage` file.
+#[derive(Debug, Clone)]
+pub struct BeatDetected {
+    pub handle: Handle<AppSinkImage>,
+    /// Energy of the beat relative to the last second, above 1.
+    pub strength: f32,
+}
+
+/// Energy-based onset detection: a window of samples much louder than the
+/// average of the previous second is a beat.
+#[derive(Debug, Default)]
+pub struct BeatDetector {
+    /// Sum of squares of the window being filled, and its frame count.
+    energy: f32,
+    frames: usize,
+    history: VecDeque<f32>,
+    cooldown: usize,
+    /// Strength of the beats found since the last `take`.
+    beats: Vec<f32>,
+}
+
+impl BeatDetector {
+    /// Analyzes interleaved samples of `channels` channels.
+    pub(crate) fn push(&mut self, samples: &[f32], channels: usize) {
+        for frame in samples.chunks_exact(channels) {
+            let mono = frame.iter().sum::<f32>() / channels as f32;
+            self.energy += mono * mono;
+            self.frames += 1;
+            if self.frames == WINDOW {
+                self.end_window(self.energy / WINDOW as f32);
+                self.energy = 0.0;
+                self.frames = 0;
+            }
+        }
+    }
+
+    fn end_window(&mut self, energy: f32) {
+        if self.history.len() == HISTORY {
+            let average = self.history.iter().sum::<f32>() / HISTORY as f32;
+            self.cooldown = self.cooldown.saturating_sub(1);
+            if self.cooldown == 0
+                && energy > MIN_ENERGY
+                && energy > SENSITIVITY * average.max(f32::EPSILON)
+            {
+                self.beats.push(energy / average.max(f32::EPSILON));
+                self.cooldown = COOLDOWN;
+            }
+            self.history.pop_front();
+        }
+        self.history.push_back(energy);
+    }
+
+    /// Beats found since the last call.
+    pub(crate) fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.beats)
+    }
+
+    /// Forgets the past audio, such as after a seek.
+    pub(crate) fn reset(&mut self) {
+        *self = BeatDetector::default();
+    }
+}
+
+/// Sends the beats found in the audio of every stream.
+pub(crate) fn send_beat_events(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut events: EventWriter<BeatDetected>,
+) {
+    for (id, appsink) in appsinks.iter() {
+        let audio = match &appsink.audio {
+            Some(audio) => audio,
+            None => continue,
+        };
+        for strength in audio.beats.lock().unwrap().take() {
+            events.send(BeatDetected {
+                handle: Handle::weak(id),
+                strength,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET: f32 = 0.01;
+    const LOUD: f32 = 0.25;
+
+    fn windows(detector: &mut BeatDetector, energy: f32, count: usize) {
+        for _ in 0..count {
+            detector.end_window(energy);
+        }
+    }
+
+    fn assert_strengths(actual: Vec<f32>, expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < expected * 1e-4,
+                "{} != {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn waits_for_a_full_history() {
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, QUIET, HISTORY - 1);
+        windows(&mut detector, LOUD, 1);
+        assert_strengths(detector.take(), &[]);
+    }
+
+    #[test]
+    fn detects_an_impulse() {
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, QUIET, HISTORY);
+        windows(&mut detector, LOUD, 1);
+        assert_strengths(detector.take(), &[LOUD / QUIET]);
+        assert_strengths(detector.take(), &[]);
+    }
+
+    #[test]
+    fn ignores_steady_or_slightly_louder_sound() {
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, LOUD, HISTORY * 3);
+        windows(&mut detector, LOUD * (SENSITIVITY - 0.1), 1);
+        assert_strengths(detector.take(), &[]);
+    }
+
+    #[test]
+    fn skips_impulses_during_cooldown() {
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, QUIET, HISTORY);
+        windows(&mut detector, LOUD, 2);
+        windows(&mut detector, QUIET, COOLDOWN - 2);
+        windows(&mut detector, LOUD, 1);
+
+        // The second impulse is in the cooldown, the third is past it and
+        // compared to a history holding the first two.
+        let average = ((HISTORY - 2) as f32 * QUIET + 2.0 * LOUD) / HISTORY as f32;
+        assert_strengths(detector.take(), &[LOUD / QUIET, LOUD / average]);
+    }
+
+    #[test]
+    fn ignores_noise_in_silence() {
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, 0.0, HISTORY);
+        windows(&mut detector, MIN_ENERGY / 2.0, 1);
+        assert_strengths(detector.take(), &[]);
+
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, 0.0, HISTORY);
+        windows(&mut detector, MIN_ENERGY * 10.0, 1);
+        assert_strengths(detector.take(), &[MIN_ENERGY * 10.0 / f32::EPSILON]);
+    }
+
+    #[test]
+    fn mixes_channels_into_windows() {
+        let mut detector = BeatDetector::default();
+        // Opposite channels cancel out into silence.
+        let silence = [0.1f32, -0.1].repeat(WINDOW * HISTORY);
+        detector.push(&silence, 2);
+        assert_eq!(detector.history.len(), HISTORY);
+
+        let impulse = [0.5f32, 0.5].repeat(WINDOW);
+        detector.push(&impulse[..2 * (WINDOW - 1)], 2);
+        assert_strengths(detector.take(), &[]);
+        detector.push(&impulse[2 * (WINDOW - 1)..], 2);
+        assert_strengths(detector.take(), &[LOUD / f32::EPSILON]);
+    }
+
+    #[test]
+    fn reset_restarts_the_warm_up() {
+        let mut detector = BeatDetector::default();
+        windows(&mut detector, QUIET, HISTORY);
+        detector.reset();
+        windows(&mut detector, LOUD, 1);
+        assert_strengths(detector.take(), &[]);
+    }
+}
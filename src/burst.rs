@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::error::VideoError;
+use crate::snapshot::write_image;
+
+/// A frame kept by a burst capture, as tightly packed RGBA.
+#[derive(Debug, Clone)]
+pub struct BurstFrame {
+    pub frame_index: u64,
+    pub pts: Option<gst::ClockTime>,
+    pub data: Arc<Vec<u8>>,
+}
+
+/// Frames being collected by [`AppSinkImage::capture_burst`], shared with
+/// the streaming thread.
+#[derive(Debug)]
+pub struct Burst {
+    pub count: usize,
+    /// Minimum time between two kept frames. Zero keeps consecutive frames.
+    pub interval: Duration,
+    /// Also write the frames as numbered PNGs into this directory.
+    pub directory: Option<PathBuf>,
+    pub frames: Vec<BurstFrame>,
+    last_taken: Option<Instant>,
+}
+
+impl Burst {
+    pub(crate) fn offer(&mut self, frame_index: u64, pts: Option<gst::ClockTime>, data: &[u8]) {
+        if self.is_complete() {
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last) = self.last_taken {
+            if now.duration_since(last) < self.interval {
+                return;
+            }
+        }
+        self.last_taken = Some(now);
+        self.frames.push(BurstFrame {
+            frame_index,
+            pts,
+            data: Arc::new(data.to_vec()),
+        });
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.frames.len() >= self.count
+    }
+}
+
+/// Sent when a burst collected all its frames.
+pub struct VideoBurstCaptured {
+    pub handle: Handle<AppSinkImage>,
+    pub frames: Vec<BurstFrame>,
+}
+
+impl AppSinkImage {
+    /// Keeps the next `count` frames, at least `interval` apart, without
+    /// interrupting the stream. They are delivered by [`VideoBurstCaptured`].
+    pub fn capture_burst(&self, count: usize, interval: Duration) -> Result<(), VideoError> {
+        self.start_burst(count, interval, None)
+    }
+
+    /// Like [`AppSinkImage::capture_burst`], also writing the frames as
+    /// numbered PNGs into `directory`.
+    pub fn capture_burst_to(
+        &self,
+        count: usize,
+        interval: Duration,
+        directory: impl Into<PathBuf>,
+    ) -> Result<(), VideoError> {
+        let directory = directory.into();
+        std::fs::create_dir_all(&directory)?;
+        self.start_burst(count, interval, Some(directory))
+    }
+
+    fn start_burst(
+        &self,
+        count: usize,
+        interval: Duration,
+        directory: Option<PathBuf>,
+    ) -> Result<(), VideoError> {
+        let mut burst = self.burst.lock().unwrap();
+        if burst.is_some() {
+            return Err(VideoError::Unsupported(String::from(
+                "a burst is already being captured",
+            )));
+        }
+        *burst = Some(Burst {
+            count,
+            interval,
+            directory,
+            frames: Vec::with_capacity(count),
+            last_taken: None,
+        });
+        Ok(())
+    }
+}
+
+pub(crate) fn finish_bursts(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut burst_events: EventWriter<VideoBurstCaptured>,
+) {
+    let finished: Vec<(HandleId, Burst)> = appsinks
+        .iter()
+        .filter_map(|(id, appsink)| {
+            let mut burst = appsink.burst.lock().unwrap();
+            if burst.as_ref().map_or(false, Burst::is_complete) {
+                burst.take().map(|burst| (id, burst))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for (id, burst) in finished {
+        if let Some(directory) = burst.directory {
+            for (number, frame) in burst.frames.iter().enumerate() {
+                let path = directory.join(format!("burst_{:03}.png", number));
+                let data = frame.data.clone();
+                IoTaskPool::get()
+                    .spawn(async move {
+                        if let Err(err) = write_image(&path, &data, WIDTH, HEIGHT) {
+                            warn!("Failed to write {}: {}", path.display(), err);
+                        }
+                    })
+                    .detach();
+            }
+        }
+        burst_events.send(VideoBurstCaptured {
+            handle: Handle::weak(id),
+            frames: burst.frames,
+        });
+    }
+}
@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+/// What the local GStreamer installation can do, probed once at startup so
+/// applications can adapt their UI instead of failing at stream start.
+#[derive(Debug, Clone, Default)]
+pub struct VideoCapabilities {
+    pub gstreamer_version: String,
+    /// Factory names of the video source elements, such as `v4l2src`.
+    pub sources: Vec<String>,
+    /// Factory names of the video decoders.
+    pub decoders: Vec<String>,
+    /// Subset of `decoders` that use hardware acceleration.
+    pub hardware_decoders: Vec<String>,
+}
+
+impl VideoCapabilities {
+    pub fn probe() -> Result<VideoCapabilities, glib::Error> {
+        gst::init()?;
+
+        let factory_names = |factory_type: gst::ElementFactoryType| {
+            let mut names: Vec<(String, bool)> =
+                gst::ElementFactory::factories_with_type(factory_type, gst::Rank::None)
+                    .iter()
+                    .map(|factory| {
+                        (
+                            factory.name().to_string(),
+                            factory.klass().contains("Hardware"),
+                        )
+                    })
+                    .collect();
+            names.sort();
+            names
+        };
+
+        let decoders =
+            factory_names(gst::ElementFactoryType::DECODER | gst::ElementFactoryType::MEDIA_VIDEO);
+
+        Ok(VideoCapabilities {
+            gstreamer_version: gst::version_string().to_string(),
+            sources: factory_names(
+                gst::ElementFactoryType::SRC | gst::ElementFactoryType::MEDIA_VIDEO,
+            )
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect(),
+            hardware_decoders: decoders
+                .iter()
+                .filter(|(_, hardware)| *hardware)
+                .map(|(name, _)| name.clone())
+                .collect(),
+            decoders: decoders.into_iter().map(|(name, _)| name).collect(),
+        })
+    }
+
+    pub fn has_source(&self, name: &str) -> bool {
+        self.sources.iter().any(|source| source == name)
+    }
+
+    pub fn has_decoder(&self, name: &str) -> bool {
+        self.decoders.iter().any(|decoder| decoder == name)
+    }
+
+    /// Whether local capture devices can be opened.
+    pub fn has_camera_support(&self) -> bool {
+        self.has_source("v4l2src")
+    }
+
+    pub fn has_hardware_decoding(&self) -> bool {
+        !self.hardware_decoders.is_empty()
+    }
+}
+
+pub(crate) fn insert_capabilities(app: &mut App) {
+    match VideoCapabilities::probe() {
+        Ok(capabilities) => {
+            info!(
+                "{}: {} video sources, {} video decoders ({} hardware)",
+                capabilities.gstreamer_version,
+                capabilities.sources.len(),
+                capabilities.decoders.len(),
+                capabilities.hardware_decoders.len()
+            );
+            app.insert_resource(capabilities);
+        }
+        Err(err) => {
+            error!("Failed to initialize GStreamer: {}", err);
+            app.insert_resource(VideoCapabilities::default());
+        }
+    }
+}
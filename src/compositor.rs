@@ -0,0 +1,241 @@
+use gst::prelude::*;
+
+use crate::appsink::{make_element, HEIGHT, WIDTH};
+use crate::error::VideoError;
+
+/// Where an input of a [`VideoSource::Composite`](crate::config::VideoSource::Composite)
+/// is drawn, in pixels of the `WIDTH`x`HEIGHT` output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputPlacement {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub alpha: f64,
+}
+
+impl InputPlacement {
+    /// Parses `<x>,<y>,<width>x<height>` with an optional `,<alpha>`.
+    pub fn parse(value: &str) -> Option<InputPlacement> {
+        let mut parts = value.split(',').map(str::trim);
+        let x = parts.next()?.parse().ok()?;
+        let y = parts.next()?.parse().ok()?;
+        let (width, height) = parts.next()?.split_once('x')?;
+        let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+        let alpha = match parts.next() {
+            Some(alpha) => alpha
+                .parse()
+                .ok()
+                .filter(|alpha| (0.0..=1.0).contains(alpha))?,
+            None => 1.0,
+        };
+        if parts.next().is_some() || width <= 0 || height <= 0 {
+            return None;
+        }
+        Some(InputPlacement {
+            x,
+            y,
+            width,
+            height,
+            alpha,
+        })
+    }
+
+    /// Cell `index` of the smallest square-ish grid holding `count` inputs.
+    pub fn grid(index: usize, count: usize) -> InputPlacement {
+        let columns = (count as f32).sqrt().ceil().max(1.0) as usize;
+        let rows = (count + columns - 1) / columns;
+        let width = WIDTH as usize / columns;
+        let height = HEIGHT as usize / rows.max(1);
+        InputPlacement {
+            x: ((index % columns) * width) as i32,
+            y: ((index / columns) * height) as i32,
+            width: width as i32,
+            height: height as i32,
+            alpha: 1.0,
+        }
+    }
+}
+
+/// One of the sources mixed by `compositor`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositorInput {
+    pub uri: String,
+    /// Laid out on a grid with the other unplaced inputs if `None`.
+    pub placement: Option<InputPlacement>,
+}
+
+/// Decodes every input and mixes them with `compositor` into `sink`, so a
+/// fixed layout of several sources costs a single texture. Returns the
+/// compositor.
+pub(crate) fn build_compositor(
+    pipeline: &gst::Pipeline,
+    inputs: &[CompositorInput],
+    sink: &gst::Element,
+) -> Result<gst::Element, VideoError> {
+    if inputs.is_empty() {
+        return Err(VideoError::Unsupported(String::from(
+            "a composite source needs at least one input",
+        )));
+    }
+    let compositor = make_element("compositor")?;
+    compositor.set_property_from_str("background", "black");
+    let convert = make_element("videoconvert")?;
+    pipeline.add_many(&[&compositor, &convert])?;
+    gst::Element::link_many(&[&compositor, &convert, sink])?;
+
+    let unplaced = inputs
+        .iter()
+        .filter(|input| input.placement.is_none())
+        .count();
+    let mut grid_index = 0;
+    for (zorder, input) in inputs.iter().enumerate() {
+        let placement = input.placement.unwrap_or_else(|| {
+            grid_index += 1;
+            InputPlacement::grid(grid_index - 1, unplaced)
+        });
+
+        let src = make_element("uridecodebin")?;
+        src.set_property("uri", &input.uri);
+        let convert = make_element("videoconvert")?;
+        pipeline.add_many(&[&src, &convert])?;
+
+        let pad = compositor.request_pad_simple("sink_%u").ok_or_else(|| {
+            VideoError::Unsupported(String::from("compositor refused another input"))
+        })?;
+        pad.set_property("xpos", placement.x);
+        pad.set_property("ypos", placement.y);
+        pad.set_property("width", placement.width);
+        pad.set_property("height", placement.height);
+        pad.set_property("alpha", placement.alpha);
+        pad.set_property("zorder", zorder as u32);
+        convert.link_pads(Some("src"), &compositor, Some(pad.name().as_str()))?;
+
+        // Same as a single URI source, the video pad shows up once the
+        // content is known.
+        let convert = convert.downgrade();
+        src.connect_pad_added(move |_, pad| {
+            let convert = match convert.upgrade() {
+                Some(convert) => convert,
+                None => return,
+            };
+            let is_video = pad
+                .current_caps()
+                .and_then(|caps| caps.structure(0).map(|s| s.name().starts_with("video/")))
+                .unwrap_or(false);
+            let sink_pad = convert
+                .static_pad("sink")
+                .expect("videoconvert without sink pad. Shouldn't happen!");
+            if is_video && !sink_pad.is_linked() {
+                let _ = pad.link(&sink_pad);
+            }
+        });
+    }
+
+    Ok(compositor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_placements() {
+        let cases = [
+            ("0,0,176x144", Some((0, 0, 176, 144, 1.0))),
+            ("112,88,56x48,0.8", Some((112, 88, 56, 48, 0.8))),
+            (" 10 , -20 , 30x40 , 0 ", Some((10, -20, 30, 40, 0.0))),
+            ("0,0,1x1,1", Some((0, 0, 1, 1, 1.0))),
+            ("0,0,176x144,1.5", None),
+            ("0,0,176x144,-0.1", None),
+            ("0,0,0x144", None),
+            ("0,0,176x-1", None),
+            ("0,0,176", None),
+            ("0,0", None),
+            ("a,0,176x144", None),
+            ("0,0,176x144,0.5,1", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            let expected = expected.map(|(x, y, width, height, alpha)| InputPlacement {
+                x,
+                y,
+                width,
+                height,
+                alpha,
+            });
+            assert_eq!(InputPlacement::parse(value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn grid_cells() {
+        let cases = [
+            (0, 1, (0, 0, 176, 144)),
+            (1, 2, (88, 0, 88, 144)),
+            (3, 4, (88, 72, 88, 72)),
+            (4, 5, (58, 72, 58, 72)),
+            (8, 9, (116, 96, 58, 48)),
+        ];
+        for (index, count, (x, y, width, height)) in cases {
+            assert_eq!(
+                InputPlacement::grid(index, count),
+                InputPlacement {
+                    x,
+                    y,
+                    width,
+                    height,
+                    alpha: 1.0,
+                },
+                "cell {} of {}",
+                index,
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn grid_cells_fit_without_overlapping() {
+        for count in 1..=16 {
+            let cells: Vec<_> = (0..count)
+                .map(|index| InputPlacement::grid(index, count))
+                .collect();
+            for (index, cell) in cells.iter().enumerate() {
+                assert!(cell.width > 0 && cell.height > 0);
+                assert!(
+                    cell.x + cell.width <= WIDTH as i32,
+                    "{} of {}",
+                    index,
+                    count
+                );
+                assert!(
+                    cell.y + cell.height <= HEIGHT as i32,
+                    "{} of {}",
+                    index,
+                    count
+                );
+                for other in &cells[index + 1..] {
+                    let apart = cell.x + cell.width <= other.x
+                        || other.x + other.width <= cell.x
+                        || cell.y + cell.height <= other.y
+                        || other.y + other.height <= cell.y;
+                    assert!(apart, "{:?} and {:?} of {}", cell, other, count);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_grid_fills_the_output() {
+        assert_eq!(
+            InputPlacement::grid(0, 0),
+            InputPlacement {
+                x: 0,
+                y: 0,
+                width: WIDTH as i32,
+                height: HEIGHT as i32,
+                alpha: 1.0,
+            }
+        );
+    }
+}
@@ -0,0 +1,758 @@
+use std::time::Duration;
+
+use crate::audio::{AudioFormat, SampleFormat};
+use crate::compositor::{CompositorInput, InputPlacement};
+use crate::device::DEFAULT_DEVICE;
+use crate::error::VideoError;
+use crate::lens::LensCalibration;
+use crate::netclock::{NetClockConfig, NetClockRole};
+use crate::sampler::VideoSampler;
+use crate::spectrum::DEFAULT_BANDS;
+use crate::text_overlay::{OverlayPosition, TextOverlay, TimeOverlay, TimeSource};
+
+/// Format used for `time_overlay = clock` without `time_format`.
+pub const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Values accepted by the `*_position` keys, for error messages.
+const POSITIONS: &str = "`top-left`, `top-right`, `bottom-left`, `bottom-right` or `center`";
+
+/// Thumbnail size used for `thumbnail = true`.
+pub const DEFAULT_THUMBNAIL_SIZE: (u32, u32) = (128, 72);
+
+/// Where a stream gets its frames from.
+#[derive(Debug, Clone)]
+pub enum VideoSource {
+    /// A V4L2 capture device, `DEFAULT_DEVICE` if `None`.
+    Camera { device: Option<String> },
+    /// Anything `uridecodebin` can play: files, HTTP, RTSP, ...
+    Uri(String),
+    /// Several URIs mixed into one frame by `compositor`.
+    Composite(Vec<CompositorInput>),
+}
+
+impl Default for VideoSource {
+    fn default() -> Self {
+        VideoSource::Camera { device: None }
+    }
+}
+
+impl VideoSource {
+    /// Path of the capture device, `None` for URI sources.
+    pub fn device_path(&self) -> Option<&str> {
+        match self {
+            VideoSource::Camera { device } => Some(device.as_deref().unwrap_or(DEFAULT_DEVICE)),
+            VideoSource::Uri(_) | VideoSource::Composite(_) => None,
+        }
+    }
+}
+
+/// Settings read from a `.sinkimage` file.
+///
+/// The file holds one `key = value` pair per line. Empty lines and lines
+/// starting with `//` or `#` are ignored, so an empty file opens the default
+/// camera.
+///
+/// ```text
+/// uri = file:///home/me/video.mp4
+/// discover = true
+/// audio = true
+/// av_sync = true
+/// av_offset_ms = -40
+/// audio_format = f32
+/// audio_rate = 44100
+/// audio_channels = 1
+/// spectrum = 32
+/// spectrum_texture = true
+/// thumbnail = 128x72
+/// label = Lobby camera
+/// label_font = Sans Bold 12
+/// label_position = bottom-right
+/// label_outline = true
+/// time_overlay = clock
+/// time_format = %H:%M:%S
+/// calibration_size = 1920x1080
+/// intrinsics = 1050.2 1049.8 962.1 538.4
+/// distortion = -0.31 0.12 0.0004 -0.0002 -0.02
+/// filter = nearest
+/// anisotropy = 1
+/// address_mode = clamp
+/// ```
+///
+/// Repeating `input` instead of `uri` mixes the sources into one frame, on a
+/// grid unless an input is given `<x>,<y>,<width>x<height>[,<alpha>]`:
+///
+/// ```text
+/// input = file:///home/me/main.mp4 0,0,176x144
+/// input = file:///home/me/guest.mp4 112,88,56x48,0.8
+/// ```
+///
+/// Machines showing the same stream in sync share a clock, one serves it and
+/// the others follow it. `clock_base_time` and `clock_start_grid` are in
+/// seconds of the shared clock:
+///
+/// ```text
+/// clock_server = 0.0.0.0:5637
+/// clock_client = 192.168.1.10:5637
+/// clock_start_grid = 10
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SinkImageConfig {
+    pub source: VideoSource,
+    /// Probe URI sources with `GstDiscoverer` before starting them.
+    pub discover: bool,
+    /// Decode the sound of URI sources into the `audio` sub-asset.
+    pub audio: bool,
+    /// Pace the video by the audio, on by default with `audio`.
+    pub av_sync: Option<bool>,
+    /// Samples the audio is converted to, set by `audio_format` (`s16` or
+    /// `f32`), `audio_rate` and `audio_channels`.
+    pub audio_format: AudioFormat,
+    /// Delay of the video against the audio, negative to show it earlier.
+    pub av_offset_ms: i64,
+    /// Frequency bands of the audio to analyze with `audio`, see
+    /// `VideoAudioSpectra`.
+    pub spectrum: Option<u32>,
+    /// Publish the spectrum as the `spectrum` sub-asset.
+    pub spectrum_texture: bool,
+    /// Size of the downscaled preview published as the `thumbnail` sub-asset.
+    pub thumbnail: Option<(u32, u32)>,
+    /// Text burned into the frames, set by the `label*` keys.
+    pub text_overlay: Option<TextOverlay>,
+    /// Time burned into the frames, set by the `time_*` keys.
+    pub time_overlay: Option<TimeOverlay>,
+    /// Lens to undistort, set by `calibration_size`, `intrinsics` (fx, fy,
+    /// cx, cy) and `distortion` (k1, k2, p1, p2 and optionally k3).
+    pub calibration: Option<LensCalibration>,
+    /// Texture sampler, set by `filter`, `anisotropy` and `address_mode`.
+    pub sampler: Option<VideoSampler>,
+    /// Clock shared with other machines, set by `clock_server` or
+    /// `clock_client`, then `clock_base_time` and `clock_start_grid`.
+    pub net_clock: Option<NetClockConfig>,
+}
+
+impl SinkImageConfig {
+    pub fn parse(text: &str) -> Result<SinkImageConfig, VideoError> {
+        let mut config = SinkImageConfig::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: String| VideoError::Config {
+                line: index + 1,
+                message,
+            };
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| invalid(format!("expected `key = value`, got `{}`", line)))?;
+
+            match key {
+                "device" => {
+                    config.source = VideoSource::Camera {
+                        device: Some(value.to_string()),
+                    }
+                }
+                "uri" => config.source = VideoSource::Uri(value.to_string()),
+                "input" => {
+                    let (uri, placement) = match value.split_once(char::is_whitespace) {
+                        Some((uri, placement)) => {
+                            let placement =
+                                InputPlacement::parse(placement.trim()).ok_or_else(|| {
+                                    invalid(format!(
+                                        "expected `<x>,<y>,<width>x<height>[,<alpha>]`, got `{}`",
+                                        placement.trim()
+                                    ))
+                                })?;
+                            (uri, Some(placement))
+                        }
+                        None => (value, None),
+                    };
+                    let input = CompositorInput {
+                        uri: uri.to_string(),
+                        placement,
+                    };
+                    match &mut config.source {
+                        VideoSource::Composite(inputs) => inputs.push(input),
+                        source => *source = VideoSource::Composite(vec![input]),
+                    }
+                }
+                "discover" => {
+                    config.discover = parse_bool(value).ok_or_else(|| {
+                        invalid(format!("expected `true` or `false`, got `{}`", value))
+                    })?
+                }
+                "audio" => {
+                    config.audio = parse_bool(value).ok_or_else(|| {
+                        invalid(format!("expected `true` or `false`, got `{}`", value))
+                    })?
+                }
+                "av_sync" => {
+                    config.av_sync = Some(parse_bool(value).ok_or_else(|| {
+                        invalid(format!("expected `true` or `false`, got `{}`", value))
+                    })?)
+                }
+                "audio_format" => {
+                    config.audio_format.sample = SampleFormat::parse(value).ok_or_else(|| {
+                        invalid(format!("expected `s16` or `f32`, got `{}`", value))
+                    })?
+                }
+                "audio_rate" => {
+                    config.audio_format.rate = value
+                        .parse()
+                        .ok()
+                        .filter(|rate| (8_000..=192_000).contains(rate))
+                        .ok_or_else(|| {
+                            invalid(format!("expected a sample rate in Hz, got `{}`", value))
+                        })?
+                }
+                "audio_channels" => {
+                    config.audio_format.channels = value
+                        .parse()
+                        .ok()
+                        .filter(|channels| (1..=8).contains(channels))
+                        .ok_or_else(|| {
+                            invalid(format!("expected 1 to 8 channels, got `{}`", value))
+                        })?
+                }
+                "av_offset_ms" => {
+                    config.av_offset_ms = value
+                        .parse()
+                        .map_err(|_| invalid(format!("expected milliseconds, got `{}`", value)))?
+                }
+                "spectrum" => {
+                    config.spectrum = match parse_bool(value) {
+                        Some(false) => None,
+                        Some(true) => Some(DEFAULT_BANDS),
+                        None => Some(
+                            value
+                                .parse::<u32>()
+                                .ok()
+                                .filter(|bands| *bands > 0)
+                                .ok_or_else(|| {
+                                    invalid(format!("expected a number of bands, got `{}`", value))
+                                })?,
+                        ),
+                    }
+                }
+                "spectrum_texture" => {
+                    config.spectrum_texture = parse_bool(value).ok_or_else(|| {
+                        invalid(format!("expected `true` or `false`, got `{}`", value))
+                    })?;
+                    if config.spectrum_texture {
+                        config.spectrum.get_or_insert(DEFAULT_BANDS);
+                    }
+                }
+                "thumbnail" => {
+                    config.thumbnail = match parse_bool(value) {
+                        Some(false) => None,
+                        Some(true) => Some(DEFAULT_THUMBNAIL_SIZE),
+                        None => Some(parse_size(value).ok_or_else(|| {
+                            invalid(format!("expected `<width>x<height>`, got `{}`", value))
+                        })?),
+                    }
+                }
+                "label" => config.text_overlay().text = value.to_string(),
+                "label_font" => config.text_overlay().font = Some(value.to_string()),
+                "label_position" => {
+                    config.text_overlay().position =
+                        OverlayPosition::parse(value).ok_or_else(|| {
+                            invalid(format!("expected {}, got `{}`", POSITIONS, value))
+                        })?
+                }
+                "label_outline" => {
+                    config.text_overlay().outline = parse_bool(value).ok_or_else(|| {
+                        invalid(format!("expected `true` or `false`, got `{}`", value))
+                    })?
+                }
+                "time_overlay" => {
+                    config.time_overlay = match value {
+                        "buffer" => Some(TimeOverlay::buffer_time()),
+                        "clock" => Some(TimeOverlay::wall_clock(DEFAULT_TIME_FORMAT)),
+                        _ if parse_bool(value) == Some(false) => None,
+                        _ => {
+                            return Err(invalid(format!(
+                                "expected `buffer`, `clock` or `false`, got `{}`",
+                                value
+                            )))
+                        }
+                    }
+                }
+                "time_format" => match &mut config.time_overlay {
+                    Some(TimeOverlay {
+                        source: TimeSource::WallClock { format },
+                        ..
+                    }) => *format = value.to_string(),
+                    _ => {
+                        return Err(invalid(String::from(
+                            "`time_format` needs `time_overlay = clock` first",
+                        )))
+                    }
+                },
+                "time_position" => {
+                    let position = OverlayPosition::parse(value).ok_or_else(|| {
+                        invalid(format!("expected {}, got `{}`", POSITIONS, value))
+                    })?;
+                    match &mut config.time_overlay {
+                        Some(time_overlay) => time_overlay.position = position,
+                        None => {
+                            return Err(invalid(String::from(
+                                "`time_position` needs `time_overlay` first",
+                            )))
+                        }
+                    }
+                }
+                "calibration_size" => {
+                    let (width, height) = parse_size(value).ok_or_else(|| {
+                        invalid(format!("expected `<width>x<height>`, got `{}`", value))
+                    })?;
+                    let calibration = config.calibration();
+                    calibration.width = width as f32;
+                    calibration.height = height as f32;
+                }
+                "intrinsics" => match parse_floats(value)[..] {
+                    [fx, fy, cx, cy] => {
+                        let calibration = config.calibration();
+                        calibration.fx = fx;
+                        calibration.fy = fy;
+                        calibration.cx = cx;
+                        calibration.cy = cy;
+                    }
+                    _ => {
+                        return Err(invalid(format!(
+                            "expected `<fx> <fy> <cx> <cy>`, got `{}`",
+                            value
+                        )))
+                    }
+                },
+                "distortion" => {
+                    if !config.calibration().set_distortion(&parse_floats(value)) {
+                        return Err(invalid(format!(
+                            "expected `<k1> <k2> <p1> <p2> [<k3>]`, got `{}`",
+                            value
+                        )));
+                    }
+                }
+                "filter" => {
+                    config.sampler().filter =
+                        VideoSampler::parse_filter(value).ok_or_else(|| {
+                            invalid(format!("expected `nearest` or `linear`, got `{}`", value))
+                        })?
+                }
+                "anisotropy" => {
+                    config.sampler().anisotropy = value
+                        .parse()
+                        .ok()
+                        .filter(|level| [1, 2, 4, 8, 16].contains(level))
+                        .ok_or_else(|| {
+                            invalid(format!("expected 1, 2, 4, 8 or 16, got `{}`", value))
+                        })?
+                }
+                "address_mode" => {
+                    config.sampler().address_mode = VideoSampler::parse_address_mode(value)
+                        .ok_or_else(|| {
+                            invalid(format!(
+                                "expected `clamp`, `repeat` or `mirror`, got `{}`",
+                                value
+                            ))
+                        })?
+                }
+                "clock_server" | "clock_client" => {
+                    let role = match key {
+                        "clock_server" => NetClockRole::Server,
+                        _ => NetClockRole::Client,
+                    };
+                    config.net_clock =
+                        Some(NetClockConfig::parse(role, value).ok_or_else(|| {
+                            invalid(format!(
+                                "expected `<address>[:<port>]` or `[<ipv6>][:<port>]`, got `{}`",
+                                value
+                            ))
+                        })?)
+                }
+                "clock_base_time" | "clock_start_grid" => {
+                    let seconds = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|seconds| *seconds >= 0.0)
+                        .map(Duration::from_secs_f64)
+                        .ok_or_else(|| invalid(format!("expected seconds, got `{}`", value)))?;
+                    let net_clock = config.net_clock.as_mut().ok_or_else(|| {
+                        invalid(format!(
+                            "`{}` needs `clock_server` or `clock_client` first",
+                            key
+                        ))
+                    })?;
+                    match key {
+                        "clock_base_time" => net_clock.base_time = Some(seconds),
+                        _ => net_clock.start_grid = seconds,
+                    }
+                }
+                _ => return Err(invalid(format!("unknown key `{}`", key))),
+            }
+        }
+
+        if let Some(calibration) = &config.calibration {
+            if !calibration.is_valid() {
+                return Err(VideoError::Config {
+                    line: 0,
+                    message: String::from(
+                        "lens calibration needs both `calibration_size` and `intrinsics`",
+                    ),
+                });
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn text_overlay(&mut self) -> &mut TextOverlay {
+        self.text_overlay.get_or_insert_with(TextOverlay::default)
+    }
+
+    fn sampler(&mut self) -> &mut VideoSampler {
+        self.sampler.get_or_insert_with(VideoSampler::default)
+    }
+
+    fn calibration(&mut self) -> &mut LensCalibration {
+        self.calibration
+            .get_or_insert_with(LensCalibration::default)
+    }
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (width, height) = value.split_once('x')?;
+    let size = (width.trim().parse().ok()?, height.trim().parse().ok()?);
+    (size.0 > 0 && size.1 > 0).then(|| size)
+}
+
+/// Whitespace separated numbers, empty if any of them is not a number.
+fn parse_floats(value: &str) -> Vec<f32> {
+    value
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .unwrap_or_default()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::{VideoAddressMode, VideoFilter};
+
+    fn parse(text: &str) -> SinkImageConfig {
+        SinkImageConfig::parse(text).unwrap_or_else(|err| panic!("{:?}: {}", text, err))
+    }
+
+    /// Line and message of the error `text` fails with.
+    fn error(text: &str) -> (usize, String) {
+        match SinkImageConfig::parse(text) {
+            Err(VideoError::Config { line, message }) => (line, message),
+            Err(err) => panic!("{:?}: unexpected {}", text, err),
+            Ok(_) => panic!("{:?}: parsed", text),
+        }
+    }
+
+    #[test]
+    fn empty_file_opens_default_camera() {
+        for text in ["", "\n\n", "# comment", "// comment\n  \n"] {
+            let config = parse(text);
+            assert_eq!(config.source.device_path(), Some(DEFAULT_DEVICE));
+            assert!(!config.audio);
+            assert_eq!(config.audio_format, AudioFormat::default());
+        }
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!(
+            parse("device = /dev/video2").source.device_path(),
+            Some("/dev/video2")
+        );
+        match parse("uri = file:///tmp/a.mp4").source {
+            VideoSource::Uri(uri) => assert_eq!(uri, "file:///tmp/a.mp4"),
+            source => panic!("unexpected {:?}", source),
+        }
+        // The last source wins.
+        assert!(matches!(
+            parse("uri = file:///tmp/a.mp4\ndevice = /dev/video0").source,
+            VideoSource::Camera { .. }
+        ));
+    }
+
+    #[test]
+    fn composite_inputs() {
+        let config = parse(
+            "input = file:///tmp/a.mp4\n\
+             input = file:///tmp/b.mp4 112,88,56x48,0.8",
+        );
+        let inputs = match config.source {
+            VideoSource::Composite(inputs) => inputs,
+            source => panic!("unexpected {:?}", source),
+        };
+        assert_eq!(
+            inputs,
+            vec![
+                CompositorInput {
+                    uri: String::from("file:///tmp/a.mp4"),
+                    placement: None,
+                },
+                CompositorInput {
+                    uri: String::from("file:///tmp/b.mp4"),
+                    placement: Some(InputPlacement {
+                        x: 112,
+                        y: 88,
+                        width: 56,
+                        height: 48,
+                        alpha: 0.8,
+                    }),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn booleans() {
+        for (value, expected) in [
+            ("true", true),
+            ("yes", true),
+            ("on", true),
+            ("1", true),
+            ("false", false),
+            ("no", false),
+            ("off", false),
+            ("0", false),
+        ] {
+            assert_eq!(parse(&format!("audio = {}", value)).audio, expected);
+            assert_eq!(parse(&format!("discover={}", value)).discover, expected);
+        }
+    }
+
+    #[test]
+    fn audio_keys() {
+        let config = parse(
+            "uri = file:///tmp/a.mp4\n\
+             audio = true\n\
+             av_sync = false\n\
+             av_offset_ms = -40\n\
+             audio_format = f32\n\
+             audio_rate = 44100\n\
+             audio_channels = 1\n\
+             spectrum = 16",
+        );
+        assert!(config.audio);
+        assert_eq!(config.av_sync, Some(false));
+        assert_eq!(config.av_offset_ms, -40);
+        assert_eq!(
+            config.audio_format,
+            AudioFormat {
+                sample: SampleFormat::F32,
+                rate: 44_100,
+                channels: 1,
+            }
+        );
+        assert_eq!(config.spectrum, Some(16));
+        assert!(!config.spectrum_texture);
+    }
+
+    #[test]
+    fn spectrum_values() {
+        assert_eq!(parse("spectrum = true").spectrum, Some(DEFAULT_BANDS));
+        assert_eq!(parse("spectrum = false").spectrum, None);
+        assert_eq!(parse("spectrum = 64").spectrum, Some(64));
+        // The texture needs bands, and keeps the ones already set.
+        assert_eq!(
+            parse("spectrum_texture = true").spectrum,
+            Some(DEFAULT_BANDS)
+        );
+        assert_eq!(
+            parse("spectrum = 8\nspectrum_texture = true").spectrum,
+            Some(8)
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse("thumbnail = 64x36").thumbnail, Some((64, 36)));
+        assert_eq!(parse("thumbnail = 64 x 36").thumbnail, Some((64, 36)));
+        assert_eq!(
+            parse("thumbnail = true").thumbnail,
+            Some(DEFAULT_THUMBNAIL_SIZE)
+        );
+        assert_eq!(parse("thumbnail = false").thumbnail, None);
+    }
+
+    #[test]
+    fn overlays() {
+        let config = parse(
+            "label = Lobby camera\n\
+             label_font = Sans Bold 12\n\
+             label_position = bottom-right\n\
+             label_outline = true\n\
+             time_overlay = clock\n\
+             time_format = %H:%M\n\
+             time_position = top-left",
+        );
+        let text = config.text_overlay.unwrap();
+        assert_eq!(text.text, "Lobby camera");
+        assert_eq!(text.font.as_deref(), Some("Sans Bold 12"));
+        assert_eq!(text.position, OverlayPosition::BottomRight);
+        assert!(text.outline);
+        let time = config.time_overlay.unwrap();
+        assert_eq!(
+            time.source,
+            TimeSource::WallClock {
+                format: String::from("%H:%M")
+            }
+        );
+        assert_eq!(time.position, OverlayPosition::TopLeft);
+
+        assert_eq!(
+            parse("time_overlay = buffer").time_overlay.unwrap().source,
+            TimeSource::BufferTime
+        );
+        assert!(parse("time_overlay = false").time_overlay.is_none());
+    }
+
+    #[test]
+    fn calibration_and_sampler() {
+        let config = parse(
+            "calibration_size = 1920x1080\n\
+             intrinsics = 1050 1049 962 538\n\
+             distortion = -0.31 0.12 0.0004 -0.0002\n\
+             filter = nearest\n\
+             anisotropy = 4\n\
+             address_mode = mirror",
+        );
+        let calibration = config.calibration.unwrap();
+        assert_eq!((calibration.width, calibration.height), (1920.0, 1080.0));
+        assert_eq!(
+            (
+                calibration.fx,
+                calibration.fy,
+                calibration.cx,
+                calibration.cy
+            ),
+            (1050.0, 1049.0, 962.0, 538.0)
+        );
+        let sampler = config.sampler.unwrap();
+        assert_eq!(sampler.filter, VideoFilter::Nearest);
+        assert_eq!(sampler.anisotropy, 4);
+        assert_eq!(sampler.address_mode, VideoAddressMode::MirrorRepeat);
+    }
+
+    #[test]
+    fn net_clock_keys() {
+        let config = parse(
+            "clock_client = 192.168.1.10:6000\n\
+             clock_base_time = 12.5\n\
+             clock_start_grid = 10",
+        );
+        let net_clock = config.net_clock.unwrap();
+        assert_eq!(net_clock.role, NetClockRole::Client);
+        assert_eq!(net_clock.address, "192.168.1.10");
+        assert_eq!(net_clock.port, 6000);
+        assert_eq!(net_clock.base_time, Some(Duration::from_millis(12_500)));
+        assert_eq!(net_clock.start_grid, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn out_of_range_values() {
+        for text in [
+            "audio_rate = 7999",
+            "audio_rate = 192001",
+            "audio_rate = 0",
+            "audio_rate = -44100",
+            "audio_rate = fast",
+            "audio_channels = 0",
+            "audio_channels = 9",
+            "audio_channels = -1",
+            "audio_format = s24",
+            "av_offset_ms = 1.5",
+            "spectrum = 0",
+            "spectrum = -8",
+            "thumbnail = 0x72",
+            "thumbnail = 128x0",
+            "thumbnail = 128",
+            "thumbnail = -128x72",
+            "calibration_size = 0x1080",
+            "anisotropy = 3",
+            "anisotropy = 32",
+            "filter = cubic",
+            "address_mode = wrap",
+            "label_position = middle",
+            "time_overlay = sometimes",
+            "intrinsics = 1 2 3",
+            "distortion = 1 2 3",
+            "input = file:///tmp/a.mp4 0,0,0x48",
+            "input = file:///tmp/a.mp4 0,0,56x48,1.5",
+            "clock_server = :5637",
+            "clock_client = host:port",
+            "clock_client = host:70000",
+        ] {
+            let (line, message) = error(text);
+            assert_eq!(line, 1, "{:?}", text);
+            assert!(message.contains("expected"), "{:?}: {}", text, message);
+        }
+    }
+
+    #[test]
+    fn boundary_values() {
+        assert_eq!(parse("audio_rate = 8000").audio_format.rate, 8_000);
+        assert_eq!(parse("audio_rate = 192000").audio_format.rate, 192_000);
+        assert_eq!(parse("audio_channels = 8").audio_format.channels, 8);
+    }
+
+    #[test]
+    fn errors_report_line_numbers() {
+        let cases = [
+            (
+                "uri = file:///tmp/a.mp4\nbogus = 1",
+                2,
+                "unknown key `bogus`",
+            ),
+            ("# comment\n\nnot a pair", 3, "expected `key = value`"),
+            (
+                "audio = true\n\n\naudio_channels = 12",
+                4,
+                "1 to 8 channels",
+            ),
+            ("time_format = %H", 1, "needs `time_overlay = clock` first"),
+            ("time_overlay = buffer\ntime_format = %H", 2, "needs"),
+            ("\ntime_position = center", 2, "needs `time_overlay` first"),
+            ("clock_start_grid = 5", 1, "needs `clock_server` or"),
+            (
+                "clock_server = 0.0.0.0\nclock_start_grid = -1",
+                2,
+                "seconds",
+            ),
+        ];
+        for (text, expected_line, expected_message) in cases {
+            let (line, message) = error(text);
+            assert_eq!(line, expected_line, "{:?}", text);
+            assert!(
+                message.contains(expected_message),
+                "{:?}: {}",
+                text,
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn incomplete_calibration_is_rejected() {
+        let (line, message) = error("calibration_size = 1920x1080");
+        assert_eq!(line, 0);
+        assert!(message.contains("needs both"), "{}", message);
+    }
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+
+use crate::appsink::AppSinkImage;
+use crate::material::{VideoBundle, VideoMaterial};
+
+/// Which side of a [`CurvedScreen`] shows the video.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveFacing {
+    /// Seen from the center of the curve, like a cockpit or dome screen.
+    Inward,
+    /// Seen from outside, like a curved billboard.
+    Outward,
+}
+
+/// Section of a cylinder or a sphere for showing video on curved surfaces,
+/// converted into a mesh with `Mesh::from`.
+///
+/// The curve is centered on the origin with the middle of the screen on -Z,
+/// so a camera at the origin looks at an inward screen. Angles are in
+/// radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurvedScreen {
+    /// Curved horizontally only.
+    Cylinder {
+        radius: f32,
+        /// Horizontal angle covered by the screen.
+        arc: f32,
+        height: f32,
+        segments: u32,
+        facing: CurveFacing,
+    },
+    /// Curved both ways.
+    Sphere {
+        radius: f32,
+        horizontal_arc: f32,
+        vertical_arc: f32,
+        segments: u32,
+        facing: CurveFacing,
+    },
+}
+
+impl CurvedScreen {
+    /// A cylinder section whose arc is as long as a flat screen of `width`.
+    pub fn cylinder(radius: f32, width: f32, height: f32) -> Self {
+        CurvedScreen::Cylinder {
+            radius,
+            arc: width / radius,
+            height,
+            segments: 32,
+            facing: CurveFacing::Inward,
+        }
+    }
+
+    /// A sphere section whose arcs are as long as a flat screen of `width`
+    /// by `height`.
+    pub fn sphere(radius: f32, width: f32, height: f32) -> Self {
+        CurvedScreen::Sphere {
+            radius,
+            horizontal_arc: width / radius,
+            vertical_arc: (height / radius).min(std::f32::consts::PI),
+            segments: 32,
+            facing: CurveFacing::Inward,
+        }
+    }
+
+    pub fn with_facing(self, facing: CurveFacing) -> Self {
+        match self {
+            CurvedScreen::Cylinder {
+                radius,
+                arc,
+                height,
+                segments,
+                ..
+            } => CurvedScreen::Cylinder {
+                radius,
+                arc,
+                height,
+                segments,
+                facing,
+            },
+            CurvedScreen::Sphere {
+                radius,
+                horizontal_arc,
+                vertical_arc,
+                segments,
+                ..
+            } => CurvedScreen::Sphere {
+                radius,
+                horizontal_arc,
+                vertical_arc,
+                segments,
+                facing,
+            },
+        }
+    }
+
+    /// Position and normal at `u`, `v` of the video, from the top left.
+    fn vertex(&self, u: f32, v: f32) -> (Vec3, Vec3) {
+        let (radius, arc, facing) = match *self {
+            CurvedScreen::Cylinder {
+                radius,
+                arc,
+                facing,
+                ..
+            } => (radius, arc, facing),
+            CurvedScreen::Sphere {
+                radius,
+                horizontal_arc,
+                facing,
+                ..
+            } => (radius, horizontal_arc, facing),
+        };
+        // Left and right swap depending on which side the viewer is on.
+        let theta = match facing {
+            CurveFacing::Inward => (u - 0.5) * arc,
+            CurveFacing::Outward => (0.5 - u) * arc,
+        };
+        let (direction, position) = match *self {
+            CurvedScreen::Cylinder { height, .. } => {
+                let direction = Vec3::new(theta.sin(), 0.0, -theta.cos());
+                (direction, direction * radius + Vec3::Y * (0.5 - v) * height)
+            }
+            CurvedScreen::Sphere { vertical_arc, .. } => {
+                let phi = (0.5 - v) * vertical_arc;
+                let direction =
+                    Vec3::new(phi.cos() * theta.sin(), phi.sin(), -phi.cos() * theta.cos());
+                (direction, direction * radius)
+            }
+        };
+        let normal = match facing {
+            CurveFacing::Inward => -direction,
+            CurveFacing::Outward => direction,
+        };
+        (position, normal)
+    }
+}
+
+impl From<CurvedScreen> for Mesh {
+    fn from(screen: CurvedScreen) -> Self {
+        let (columns, rows) = match screen {
+            CurvedScreen::Cylinder { segments, .. } => (segments.max(1), 1),
+            CurvedScreen::Sphere { segments, .. } => (segments.max(1), segments.max(1)),
+        };
+
+        let mut positions = Vec::new();
+        let mut normals = Vec::new();
+        let mut uvs = Vec::new();
+        for y in 0..=rows {
+            let v = y as f32 / rows as f32;
+            for x in 0..=columns {
+                let u = x as f32 / columns as f32;
+                let (position, normal) = screen.vertex(u, v);
+                positions.push(position.to_array());
+                normals.push(normal.to_array());
+                uvs.push([u, v]);
+            }
+        }
+
+        // Counter-clockwise as seen by the viewer, for whom u goes right and
+        // v goes down.
+        let mut indices = Vec::new();
+        for y in 0..rows {
+            for x in 0..columns {
+                let top_left = y * (columns + 1) + x;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + columns + 1;
+                let bottom_right = bottom_left + 1;
+                indices.extend_from_slice(&[bottom_left, bottom_right, top_right]);
+                indices.extend_from_slice(&[bottom_left, top_right, top_left]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+impl VideoBundle {
+    /// A [`VideoBundle`] showing `stream` on `screen`.
+    pub fn curved(
+        stream: Handle<AppSinkImage>,
+        screen: CurvedScreen,
+        settings: VideoMaterial,
+        meshes: &mut Assets<Mesh>,
+        images: &mut Assets<Image>,
+        materials: &mut Assets<VideoMaterial>,
+    ) -> Self {
+        let mesh = meshes.add(Mesh::from(screen));
+        VideoBundle::new(stream, mesh, settings, images, materials)
+    }
+}
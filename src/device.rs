@@ -0,0 +1,213 @@
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use gst::prelude::*;
+
+use crate::appsink::{handle_failure, AppSinkImage, ImageRaw, StreamEvents};
+
+/// Device used by `v4l2src` when none is configured.
+pub const DEFAULT_DEVICE: &str = "/dev/video0";
+
+/// What a stream shows while its capture device is unplugged.
+#[derive(Debug, Clone)]
+pub enum Placeholder {
+    /// Keep showing the last frame received.
+    LastFrame,
+    /// Fill the texture with a single RGBA color.
+    Color([u8; 4]),
+    /// Show a fixed RGBA image of the stream's size.
+    Image(Box<ImageRaw>),
+}
+
+impl Default for Placeholder {
+    fn default() -> Self {
+        Placeholder::Color([32, 32, 32, 255])
+    }
+}
+
+impl Placeholder {
+    pub fn apply(&self, image_raw: &mut ImageRaw) {
+        match self {
+            Placeholder::LastFrame => (),
+            Placeholder::Color(color) => {
+                for pixel in image_raw.chunks_exact_mut(4) {
+                    pixel.copy_from_slice(color);
+                }
+            }
+            Placeholder::Image(image) => image_raw.copy_from_slice(&image[..]),
+        }
+    }
+}
+
+/// Watches for video capture devices coming and going.
+pub struct CameraMonitor {
+    monitor: gst::DeviceMonitor,
+    bus: gst::Bus,
+}
+
+impl CameraMonitor {
+    pub fn new() -> Result<CameraMonitor, glib::Error> {
+        gst::init()?;
+
+        let monitor = gst::DeviceMonitor::new();
+        monitor.add_filter(Some("Video/Source"), None);
+        monitor
+            .start()
+            .map_err(|err| glib::Error::new(gst::CoreError::Failed, &err.to_string()))?;
+        let bus = monitor.bus();
+
+        Ok(CameraMonitor { monitor, bus })
+    }
+}
+
+impl Drop for CameraMonitor {
+    fn drop(&mut self) {
+        self.monitor.stop();
+    }
+}
+
+/// Sent when the capture device of a stream disappears and the placeholder
+/// is shown instead.
+pub struct VideoDeviceRemoved {
+    pub handle: Handle<AppSinkImage>,
+    pub device: String,
+}
+
+/// Sent when the capture device of a stream is plugged back in and the
+/// stream has been restarted.
+pub struct VideoDeviceReturned {
+    pub handle: Handle<AppSinkImage>,
+    pub device: String,
+}
+
+/// Sent when the capture device of a stream is in use by another application.
+pub struct VideoDeviceBusy {
+    pub handle: Handle<AppSinkImage>,
+    pub device: String,
+}
+
+/// Sent when the application is not allowed to open the capture device of a
+/// stream.
+pub struct VideoPermissionDenied {
+    pub handle: Handle<AppSinkImage>,
+    pub device: String,
+}
+
+/// A capture device found by [`list_devices`].
+#[derive(Debug, Clone)]
+pub struct DeviceDescription {
+    pub name: String,
+    /// Device node to put in the `device` key of a `.sinkimage` file.
+    pub path: Option<String>,
+    /// Formats the device can produce, one caps structure each.
+    pub caps: Vec<String>,
+}
+
+/// Enumerates the video capture devices currently plugged in.
+pub fn list_devices() -> Result<Vec<DeviceDescription>, glib::Error> {
+    gst::init()?;
+
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(Some("Video/Source"), None);
+    monitor
+        .start()
+        .map_err(|err| glib::Error::new(gst::CoreError::Failed, &err.to_string()))?;
+    let devices = monitor
+        .devices()
+        .iter()
+        .map(|device| DeviceDescription {
+            name: device.display_name().to_string(),
+            path: device_path(device),
+            caps: device
+                .caps()
+                .map(|caps| caps.iter().map(|s| s.to_string()).collect())
+                .unwrap_or_default(),
+        })
+        .collect();
+    monitor.stop();
+
+    Ok(devices)
+}
+
+/// Path of the device node backing `device`, if the provider reports one.
+pub fn device_path(device: &gst::Device) -> Option<String> {
+    let properties = device.properties()?;
+    properties
+        .get::<String>("api.v4l2.path")
+        .or_else(|_| properties.get::<String>("device.path"))
+        .ok()
+}
+
+pub(crate) fn start_camera_monitor(mut commands: Commands) {
+    match CameraMonitor::new() {
+        Ok(monitor) => commands.insert_resource(monitor),
+        Err(err) => warn!(
+            "Device monitor unavailable, unplugged cameras will be retried blindly: {}",
+            err
+        ),
+    }
+}
+
+pub(crate) fn watch_devices(
+    time: Res<Time>,
+    monitor: Option<Res<CameraMonitor>>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut events: StreamEvents,
+    mut returned_events: EventWriter<VideoDeviceReturned>,
+) {
+    let monitor = match monitor {
+        Some(monitor) => monitor,
+        None => return,
+    };
+
+    for msg in monitor.bus.iter() {
+        let (device, added) = match msg.view() {
+            gst::MessageView::DeviceAdded(added) => (added.device(), true),
+            gst::MessageView::DeviceRemoved(removed) => (removed.device(), false),
+            _ => continue,
+        };
+        let path = match device_path(&device) {
+            Some(path) => path,
+            None => continue,
+        };
+
+        let ids: Vec<HandleId> = appsinks
+            .iter()
+            .filter(|(_, a)| a.device_path() == Some(path.as_str()))
+            .map(|(id, _)| id)
+            .collect();
+        for id in ids {
+            let handle = Handle::weak(id);
+            let appsink = match appsinks.get_mut(&handle) {
+                Some(appsink) => appsink,
+                None => continue,
+            };
+
+            if added && appsink.device_removed {
+                info!("Camera {} is back, restarting stream", path);
+                appsink.device_removed = false;
+                appsink.recovery_state = default();
+                if let Err(err) = appsink.start() {
+                    handle_failure(
+                        appsink,
+                        &handle,
+                        err,
+                        time.time_since_startup(),
+                        &mut events,
+                    );
+                    continue;
+                }
+                returned_events.send(VideoDeviceReturned {
+                    handle: handle.clone_weak(),
+                    device: path.clone(),
+                });
+            } else if !added && !appsink.device_removed {
+                warn!("Camera {} was unplugged", path);
+                appsink.unplug();
+                events.device_removed.send(VideoDeviceRemoved {
+                    handle: handle.clone_weak(),
+                    device: path.clone(),
+                });
+            }
+        }
+    }
+}
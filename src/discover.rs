@@ -0,0 +1,154 @@
+use std::fmt;
+use std::time::Duration;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use gst_pbutils::prelude::*;
+
+use crate::appsink::{handle_failure, AppSinkImage, StreamEvents};
+use crate::config::VideoSource;
+use crate::error::VideoError;
+
+/// How long discovery may take before the URI is considered unreachable.
+const DISCOVER_TIMEOUT_SECS: u64 = 10;
+
+/// What `GstDiscoverer` found out about a URI before playback.
+#[derive(Debug, Clone, Default)]
+pub struct MediaInfo {
+    pub duration: Option<Duration>,
+    pub width: u32,
+    pub height: u32,
+    /// Frames per second, `None` for still images or variable framerate.
+    pub framerate: Option<f64>,
+    pub seekable: bool,
+    /// Caps names of the video streams, such as `video/x-h264`.
+    pub video_codecs: Vec<String>,
+    /// Caps names of the audio streams, such as `audio/mpeg`.
+    pub audio_codecs: Vec<String>,
+}
+
+/// Sent when the probe of a stream finished, right before its pipeline starts.
+pub struct VideoDiscovered {
+    pub handle: Handle<AppSinkImage>,
+    pub info: MediaInfo,
+}
+
+/// Probe of a source running on the task pool.
+pub struct PendingDiscovery {
+    task: Task<Result<MediaInfo, VideoError>>,
+}
+
+impl fmt::Debug for PendingDiscovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingDiscovery").finish()
+    }
+}
+
+impl AppSinkImage {
+    /// Starts probing the source in the background if `discover` is set and
+    /// it was not probed yet. Returns whether starting the pipeline has to
+    /// wait for it.
+    pub(crate) fn start_discovery(&mut self) -> bool {
+        let uri = match (&self.source, self.discover, &self.media_info) {
+            (VideoSource::Uri(uri), true, None) => uri.clone(),
+            _ => return false,
+        };
+        let task = AsyncComputeTaskPool::get().spawn(async move { discover(&uri) });
+        self.pending_discovery = Some(PendingDiscovery { task });
+        true
+    }
+}
+
+/// Starts the streams whose probe finished, or fails them if their source
+/// can't be played.
+pub(crate) fn finish_discoveries(
+    time: Res<Time>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut events: StreamEvents,
+) {
+    let ids: Vec<HandleId> = appsinks
+        .iter()
+        .filter(|(_, appsink)| appsink.pending_discovery.is_some())
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in ids {
+        let handle = Handle::weak(id);
+        let appsink = match appsinks.get_mut(&handle) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+        let pending = appsink.pending_discovery.as_mut().unwrap();
+        let result = match future::block_on(future::poll_once(&mut pending.task)) {
+            Some(result) => result,
+            None => continue,
+        };
+        appsink.pending_discovery = None;
+
+        let started = result.and_then(|info| {
+            events.discovered.send(VideoDiscovered {
+                handle: handle.clone_weak(),
+                info: info.clone(),
+            });
+            appsink.media_info = Some(info);
+            if appsink.is_preparing() {
+                return Ok(());
+            }
+            appsink.start()
+        });
+        if let Err(err) = started {
+            appsink.cancel_preparation();
+            handle_failure(
+                appsink,
+                &handle,
+                err,
+                time.time_since_startup(),
+                &mut events,
+            );
+        }
+    }
+}
+
+/// Probes `uri` synchronously, failing if it has no video stream.
+pub fn discover(uri: &str) -> Result<MediaInfo, VideoError> {
+    gst::init().map_err(VideoError::Init)?;
+
+    let discoverer =
+        gst_pbutils::Discoverer::new(gst::ClockTime::from_seconds(DISCOVER_TIMEOUT_SECS))
+            .map_err(VideoError::Init)?;
+    let info = discoverer
+        .discover_uri(uri)
+        .map_err(|err| VideoError::Unsupported(format!("{}: {}", uri, err)))?;
+
+    let caps_name = |caps: Option<gst::Caps>| {
+        caps.and_then(|caps| caps.structure(0).map(|s| s.name().to_string()))
+    };
+
+    let video_streams = info.video_streams();
+    let video = video_streams
+        .first()
+        .ok_or_else(|| VideoError::Unsupported(format!("{} has no video stream", uri)))?;
+    let framerate = video.framerate();
+
+    Ok(MediaInfo {
+        duration: info
+            .duration()
+            .map(|duration| Duration::from_nanos(duration.nseconds())),
+        width: video.width(),
+        height: video.height(),
+        framerate: (framerate.numer() > 0 && framerate.denom() > 0)
+            .then(|| framerate.numer() as f64 / framerate.denom() as f64),
+        seekable: info.is_seekable(),
+        video_codecs: video_streams
+            .iter()
+            .filter_map(|stream| caps_name(stream.caps()))
+            .collect(),
+        audio_codecs: info
+            .audio_streams()
+            .iter()
+            .filter_map(|stream| caps_name(stream.caps()))
+            .collect(),
+    })
+}
@@ -0,0 +1,374 @@
+use std::sync::Arc;
+
+use derive_more::{Display, Error};
+use gst::prelude::*;
+
+use crate::missing::MissingPlugin;
+
+/// Everything that can go wrong while building or running a video pipeline.
+///
+/// Bus errors are classified by their GStreamer error domain so callers can
+/// match on the failure kind instead of parsing strings.
+#[derive(Debug, Clone, Display, Error)]
+pub enum VideoError {
+    #[display(fmt = "Missing element: {}", _0)]
+    MissingElement(#[error(not(source))] MissingPlugin),
+    #[display(fmt = "Device used by {} is busy: {}", src, source)]
+    DeviceBusy {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Permission denied for device used by {}: {}", src, source)]
+    PermissionDenied {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Device used by {} was removed: {}", src, source)]
+    DeviceRemoved {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Resource for {} not found: {}", src, source)]
+    NotFound {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Caps negotiation failed in {}: {}", src, source)]
+    Negotiation {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Decoding failed in {}: {}", src, source)]
+    Decode {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Network error in {}: {}", src, source)]
+    Network {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "Received error from {}: {} (debug: {:?})", src, source, debug)]
+    Pipeline {
+        src: String,
+        debug: Option<String>,
+        source: glib::Error,
+    },
+    #[display(fmt = "No frames received for {:?}", _0)]
+    Stalled(#[error(not(source))] std::time::Duration),
+    #[display(fmt = "Live stream ended")]
+    Ended,
+    #[display(fmt = "Invalid configuration on line {}: {}", line, message)]
+    Config { line: usize, message: String },
+    #[display(fmt = "Invalid LUT on line {}: {}", line, message)]
+    Lut { line: usize, message: String },
+    #[display(fmt = "Unsupported media: {}", _0)]
+    Unsupported(#[error(not(source))] String),
+    #[display(fmt = "Pipeline is not running")]
+    NotRunning,
+    #[display(fmt = "Network clock at {} did not synchronize", _0)]
+    ClockSync(#[error(not(source))] String),
+    #[display(fmt = "I/O error: {}", _0)]
+    Io(Arc<std::io::Error>),
+    #[display(fmt = "Failed to encode image: {}", _0)]
+    Image(Arc<image::ImageError>),
+    #[cfg(feature = "segmentation")]
+    #[display(fmt = "Segmentation model failed: {}", _0)]
+    Segmentation(#[error(not(source))] String),
+    #[display(fmt = "Failed to initialize GStreamer: {}", _0)]
+    Init(glib::Error),
+    #[display(fmt = "Failed to build pipeline: {}", _0)]
+    Build(glib::BoolError),
+    #[display(fmt = "Failed to change pipeline state: {}", _0)]
+    StateChange(gst::StateChangeError),
+}
+
+impl VideoError {
+    /// Whether restarting the pipeline can possibly fix the error.
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(
+            self,
+            VideoError::MissingElement(_)
+                | VideoError::PermissionDenied { .. }
+                | VideoError::Config { .. }
+                | VideoError::Lut { .. }
+                | VideoError::Unsupported(_)
+                | VideoError::Init(_)
+        )
+    }
+
+    /// Short name of the failure kind, for display in UIs.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VideoError::MissingElement(_) => "missing plugin",
+            VideoError::DeviceBusy { .. } => "device busy",
+            VideoError::PermissionDenied { .. } => "permission denied",
+            VideoError::DeviceRemoved { .. } => "device removed",
+            VideoError::NotFound { .. } => "not found",
+            VideoError::Negotiation { .. } => "negotiation",
+            VideoError::Decode { .. } => "decode",
+            VideoError::Network { .. } => "network",
+            VideoError::Pipeline { .. } => "pipeline",
+            VideoError::Stalled(_) => "stalled",
+            VideoError::Ended => "ended",
+            VideoError::Config { .. } => "configuration",
+            VideoError::Lut { .. } => "lut",
+            VideoError::Unsupported(_) => "unsupported",
+            VideoError::NotRunning => "not running",
+            VideoError::ClockSync(_) => "clock sync",
+            VideoError::Io(_) => "i/o",
+            VideoError::Image(_) => "image",
+            #[cfg(feature = "segmentation")]
+            VideoError::Segmentation(_) => "segmentation",
+            VideoError::Init(_) => "init",
+            VideoError::Build(_) => "build",
+            VideoError::StateChange(_) => "state change",
+        }
+    }
+
+    /// Converts an error message popped from the bus into a typed error.
+    ///
+    /// Returns `None` for any message that is not an error.
+    pub fn from_message(msg: &gst::MessageRef) -> Option<VideoError> {
+        match msg.view() {
+            gst::MessageView::Error(err) => {
+                let src = msg
+                    .src()
+                    .map(|s| String::from(s.path_string()))
+                    .unwrap_or_else(|| String::from("None"));
+                let network = msg
+                    .src()
+                    .and_then(|s| s.downcast_ref::<gst::Element>().and_then(|e| e.factory()))
+                    .map(|f| f.klass().contains("Network"))
+                    .unwrap_or(false);
+                Some(Self::classify(src, network, err.error(), err.debug()))
+            }
+            _ => None,
+        }
+    }
+
+    fn classify(
+        src: String,
+        network: bool,
+        source: glib::Error,
+        debug: Option<String>,
+    ) -> VideoError {
+        let mentions = |needle: &str| debug.as_deref().map_or(false, |d| d.contains(needle));
+        let not_negotiated = mentions("not-negotiated");
+        let no_device = mentions("No such device");
+        let permission_denied = mentions("Permission denied");
+        let busy = mentions("Device or resource busy");
+
+        if let Some(kind) = source.kind::<gst::ResourceError>() {
+            match kind {
+                _ if no_device => return VideoError::DeviceRemoved { src, debug, source },
+                gst::ResourceError::NotAuthorized => {
+                    return VideoError::PermissionDenied { src, debug, source }
+                }
+                _ if permission_denied => {
+                    return VideoError::PermissionDenied { src, debug, source }
+                }
+                _ if busy => return VideoError::DeviceBusy { src, debug, source },
+                gst::ResourceError::Busy => return VideoError::DeviceBusy { src, debug, source },
+                gst::ResourceError::NotFound => return VideoError::NotFound { src, debug, source },
+                _ if network => return VideoError::Network { src, debug, source },
+                _ => (),
+            }
+        }
+
+        if let Some(kind) = source.kind::<gst::StreamError>() {
+            match kind {
+                gst::StreamError::Decode | gst::StreamError::CodecNotFound => {
+                    return VideoError::Decode { src, debug, source }
+                }
+                gst::StreamError::Format | gst::StreamError::WrongType => {
+                    return VideoError::Negotiation { src, debug, source }
+                }
+                _ if not_negotiated => return VideoError::Negotiation { src, debug, source },
+                _ => (),
+            }
+        }
+
+        if let Some(gst::CoreError::Negotiation) = source.kind::<gst::CoreError>() {
+            return VideoError::Negotiation { src, debug, source };
+        }
+
+        VideoError::Pipeline { src, debug, source }
+    }
+}
+
+impl From<glib::BoolError> for VideoError {
+    fn from(err: glib::BoolError) -> Self {
+        VideoError::Build(err)
+    }
+}
+
+impl From<gst::StateChangeError> for VideoError {
+    fn from(err: gst::StateChangeError) -> Self {
+        VideoError::StateChange(err)
+    }
+}
+
+impl From<std::io::Error> for VideoError {
+    fn from(err: std::io::Error) -> Self {
+        VideoError::Io(Arc::new(err))
+    }
+}
+
+impl From<image::ImageError> for VideoError {
+    fn from(err: image::ImageError) -> Self {
+        VideoError::Image(Arc::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(error: glib::Error, debug: Option<&str>) -> VideoError {
+        VideoError::classify(
+            String::from("/pipeline0/src"),
+            false,
+            error,
+            debug.map(String::from),
+        )
+    }
+
+    #[test]
+    fn classifies_resource_errors() {
+        let cases: &[(gst::ResourceError, Option<&str>, &str)] = &[
+            (gst::ResourceError::Busy, None, "device busy"),
+            (gst::ResourceError::NotAuthorized, None, "permission denied"),
+            (gst::ResourceError::NotFound, None, "not found"),
+            (gst::ResourceError::Failed, None, "pipeline"),
+            (
+                gst::ResourceError::OpenRead,
+                Some("Cannot open device: No such device"),
+                "device removed",
+            ),
+            (
+                gst::ResourceError::OpenReadWrite,
+                Some("Could not open device: Permission denied"),
+                "permission denied",
+            ),
+            (
+                gst::ResourceError::OpenReadWrite,
+                Some("Error opening device: Device or resource busy"),
+                "device busy",
+            ),
+            // A removed device wins over whatever the code says.
+            (
+                gst::ResourceError::Busy,
+                Some("No such device"),
+                "device removed",
+            ),
+        ];
+        for (kind, debug, expected) in cases {
+            let err = classify(glib::Error::new(*kind, "failed"), *debug);
+            assert_eq!(err.kind(), *expected, "{:?} with {:?}", kind, debug);
+        }
+    }
+
+    #[test]
+    fn classifies_network_sources() {
+        let err = VideoError::classify(
+            String::from("/pipeline0/souphttpsrc0"),
+            true,
+            glib::Error::new(gst::ResourceError::Read, "failed"),
+            None,
+        );
+        assert_eq!(err.kind(), "network");
+        // Codes that say more than "network" keep their meaning.
+        let err = VideoError::classify(
+            String::from("/pipeline0/souphttpsrc0"),
+            true,
+            glib::Error::new(gst::ResourceError::NotFound, "failed"),
+            None,
+        );
+        assert_eq!(err.kind(), "not found");
+    }
+
+    #[test]
+    fn classifies_stream_and_core_errors() {
+        let cases: &[(glib::Error, Option<&str>, &str)] = &[
+            (
+                glib::Error::new(gst::StreamError::Decode, "failed"),
+                None,
+                "decode",
+            ),
+            (
+                glib::Error::new(gst::StreamError::CodecNotFound, "failed"),
+                None,
+                "decode",
+            ),
+            (
+                glib::Error::new(gst::StreamError::Format, "failed"),
+                None,
+                "negotiation",
+            ),
+            (
+                glib::Error::new(gst::StreamError::WrongType, "failed"),
+                None,
+                "negotiation",
+            ),
+            (
+                glib::Error::new(gst::StreamError::Failed, "failed"),
+                Some("streaming stopped, reason not-negotiated (-4)"),
+                "negotiation",
+            ),
+            (
+                glib::Error::new(gst::StreamError::Failed, "failed"),
+                None,
+                "pipeline",
+            ),
+            (
+                glib::Error::new(gst::CoreError::Negotiation, "failed"),
+                None,
+                "negotiation",
+            ),
+            (
+                glib::Error::new(gst::CoreError::Failed, "failed"),
+                None,
+                "pipeline",
+            ),
+        ];
+        for (error, debug, expected) in cases {
+            let err = classify(error.clone(), *debug);
+            assert_eq!(err.kind(), *expected, "{} with {:?}", error, debug);
+        }
+    }
+
+    #[test]
+    fn keeps_source_and_debug() {
+        match classify(
+            glib::Error::new(gst::ResourceError::Busy, "busy"),
+            Some("details"),
+        ) {
+            VideoError::DeviceBusy { src, debug, .. } => {
+                assert_eq!(src, "/pipeline0/src");
+                assert_eq!(debug.as_deref(), Some("details"));
+            }
+            err => panic!("unexpected {:?}", err),
+        }
+    }
+
+    #[test]
+    fn permanent_errors_are_not_recoverable() {
+        let permission = classify(
+            glib::Error::new(gst::ResourceError::NotAuthorized, ""),
+            None,
+        );
+        assert!(!permission.is_recoverable());
+        let busy = classify(glib::Error::new(gst::ResourceError::Busy, ""), None);
+        assert!(busy.is_recoverable());
+        assert!(!VideoError::Unsupported(String::new()).is_recoverable());
+        assert!(VideoError::Stalled(std::time::Duration::from_secs(1)).is_recoverable());
+    }
+}
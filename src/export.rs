@@ -0,0 +1,242 @@
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::error::VideoError;
+use crate::snapshot::write_image;
+
+/// Which frames of a stream are written to disk as an image sequence.
+#[derive(Debug, Clone)]
+pub struct ExportConfig {
+    pub directory: PathBuf,
+    /// File name prefix, followed by the zero-padded frame number.
+    pub prefix: String,
+    /// File extension, which picks the image format.
+    pub extension: String,
+    /// Export one frame out of `every`. 1 exports all of them.
+    pub every: u64,
+    /// Only export frames whose timestamp, in pipeline running time, falls in
+    /// this range. The export finishes once the end is reached.
+    pub range: Option<Range<Duration>>,
+}
+
+impl ExportConfig {
+    /// Exports every `n`th frame into `directory` until stopped.
+    pub fn every(directory: impl Into<PathBuf>, n: u64) -> Self {
+        ExportConfig {
+            directory: directory.into(),
+            prefix: String::from("frame_"),
+            extension: String::from("png"),
+            every: n.max(1),
+            range: None,
+        }
+    }
+
+    /// Exports all frames between `start` and `end` into `directory`.
+    pub fn between(directory: impl Into<PathBuf>, start: Duration, end: Duration) -> Self {
+        ExportConfig {
+            range: Some(start..end),
+            ..ExportConfig::every(directory, 1)
+        }
+    }
+}
+
+/// Progress of a running export, shared with the streaming thread.
+#[derive(Debug)]
+pub struct FrameExport {
+    pub config: ExportConfig,
+    first_index: Option<u64>,
+    /// Frames handed to the IO task pool so far.
+    pub written: u64,
+    /// Set once the end of the range has been reached.
+    pub done: bool,
+}
+
+impl FrameExport {
+    pub fn new(config: ExportConfig) -> Self {
+        FrameExport {
+            config,
+            first_index: None,
+            written: 0,
+            done: false,
+        }
+    }
+
+    /// Writes `data` if the frame is part of the export. Called by the
+    /// appsink for every accepted frame.
+    pub(crate) fn offer(&mut self, frame_index: u64, pts: Option<gst::ClockTime>, data: &[u8]) {
+        if !self.accepts(frame_index, pts) {
+            return;
+        }
+
+        let path = self.config.directory.join(format!(
+            "{}{:06}.{}",
+            self.config.prefix, self.written, self.config.extension
+        ));
+        self.written += 1;
+        let data = data.to_vec();
+        IoTaskPool::get()
+            .spawn(async move {
+                if let Err(err) = write_image(&path, &data, WIDTH, HEIGHT) {
+                    warn!("Failed to export {}: {}", path.display(), err);
+                }
+            })
+            .detach();
+    }
+
+    /// Whether the frame is part of the export, marking it done past the end
+    /// of the range.
+    fn accepts(&mut self, frame_index: u64, pts: Option<gst::ClockTime>) -> bool {
+        if self.done {
+            return false;
+        }
+        if let Some(range) = &self.config.range {
+            let pts = match pts {
+                Some(pts) => Duration::from_nanos(pts.nseconds()),
+                None => return false,
+            };
+            if pts >= range.end {
+                self.done = true;
+                return false;
+            }
+            if pts < range.start {
+                return false;
+            }
+        }
+        // Indices start over with a new pipeline, count from there then.
+        let offset = self
+            .first_index
+            .and_then(|first| frame_index.checked_sub(first))
+            .unwrap_or_else(|| {
+                self.first_index = Some(frame_index);
+                0
+            });
+        offset % self.config.every.max(1) == 0
+    }
+
+    /// Counts `every` from the next offered frame, for when the frame
+    /// indices start over with a new pipeline.
+    pub(crate) fn restart(&mut self) {
+        self.first_index = None;
+    }
+}
+
+impl AppSinkImage {
+    /// Starts writing frames to disk as described by `config`, replacing any
+    /// running export.
+    pub fn export_frames(&self, config: ExportConfig) -> Result<(), VideoError> {
+        std::fs::create_dir_all(&config.directory)?;
+        *self.export.lock().unwrap() = Some(FrameExport::new(config));
+        Ok(())
+    }
+
+    /// Stops the running export, returning the number of frames written.
+    pub fn stop_export(&self) -> Option<u64> {
+        self.export
+            .lock()
+            .unwrap()
+            .take()
+            .map(|export| export.written)
+    }
+
+    pub fn is_exporting(&self) -> bool {
+        self.export.lock().unwrap().is_some()
+    }
+}
+
+/// Sent when an export with a time range reached its end.
+pub struct VideoExportFinished {
+    pub handle: Handle<AppSinkImage>,
+    pub directory: PathBuf,
+    pub frames: u64,
+}
+
+pub(crate) fn finish_exports(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut finished_events: EventWriter<VideoExportFinished>,
+) {
+    let finished: Vec<(HandleId, FrameExport)> = appsinks
+        .iter()
+        .filter_map(|(id, appsink)| {
+            let mut export = appsink.export.lock().unwrap();
+            if export.as_ref().map_or(false, |export| export.done) {
+                export.take().map(|export| (id, export))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for (id, export) in finished {
+        info!(
+            "Exported {} frames to {}",
+            export.written,
+            export.config.directory.display()
+        );
+        finished_events.send(VideoExportFinished {
+            handle: Handle::weak(id),
+            directory: export.config.directory,
+            frames: export.written,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accepted(export: &mut FrameExport, indices: impl IntoIterator<Item = u64>) -> Vec<u64> {
+        indices
+            .into_iter()
+            .filter(|index| export.accepts(*index, None))
+            .collect()
+    }
+
+    #[test]
+    fn exports_every_nth_frame() {
+        let mut export = FrameExport::new(ExportConfig::every("frames", 3));
+        assert_eq!(accepted(&mut export, 5..15), [5, 8, 11, 14]);
+    }
+
+    #[test]
+    fn counts_again_when_indices_start_over() {
+        let mut export = FrameExport::new(ExportConfig::every("frames", 2));
+        assert_eq!(accepted(&mut export, 10..14), [10, 12]);
+        // The pipeline was restarted without `restart` being called.
+        assert_eq!(accepted(&mut export, 0..4), [0, 2]);
+
+        export.restart();
+        assert_eq!(accepted(&mut export, 7..11), [7, 9]);
+    }
+
+    #[test]
+    fn zero_every_exports_all_frames() {
+        let mut export = FrameExport::new(ExportConfig {
+            every: 0,
+            ..ExportConfig::every("frames", 1)
+        });
+        assert_eq!(accepted(&mut export, 0..3), [0, 1, 2]);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_the_range() {
+        let mut export = FrameExport::new(ExportConfig::between(
+            "frames",
+            Duration::from_secs(1),
+            Duration::from_secs(2),
+        ));
+        let at = |millis| Some(gst::ClockTime::from_mseconds(millis));
+        assert!(!export.accepts(0, at(500)));
+        assert!(!export.accepts(1, None));
+        assert!(export.accepts(2, at(1000)));
+        assert!(export.accepts(3, at(1500)));
+        assert!(!export.accepts(4, at(2000)));
+        assert!(export.done);
+        assert!(!export.accepts(5, at(1500)));
+    }
+}
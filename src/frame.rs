@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Frames queued by the streaming thread that have not been turned into
+/// events yet. Old entries are discarded if nobody drains the queue.
+const MAX_PENDING_FRAMES: usize = 64;
+
+/// Metadata of a sample accepted by the appsink.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInfo {
+    /// Presentation timestamp of the buffer, in the pipeline's running time.
+    pub pts: Option<gst::ClockTime>,
+    /// Position of the frame since the pipeline started, counting from zero.
+    pub frame_index: u64,
+    /// When the appsink handed the sample over.
+    pub received_at: Instant,
+    /// Estimated wall-clock time of capture, the arrival time minus the
+    /// pipeline latency when it is known.
+    pub capture_time: SystemTime,
+}
+
+/// Why the appsink callback skipped a sample instead of showing it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRejection {
+    /// The sample carried no caps, or caps that are not raw video.
+    InvalidCaps,
+    /// The negotiated frame size differs from the size of the texture.
+    UnexpectedSize { width: u32, height: u32 },
+    /// The buffer is smaller than the negotiated caps require.
+    ShortBuffer { expected: usize, actual: usize },
+}
+
+/// Something that happened to a sample on the streaming thread.
+#[derive(Debug, Clone, Copy)]
+pub enum FrameEvent {
+    Ready(FrameInfo),
+    Rejected(FrameRejection),
+}
+
+#[derive(Debug, Default)]
+pub struct FrameQueue(Mutex<VecDeque<FrameEvent>>);
+
+impl FrameQueue {
+    pub(crate) fn push(&self, frame: FrameEvent) {
+        let mut queue = self.0.lock().unwrap();
+        if queue.len() == MAX_PENDING_FRAMES {
+            queue.pop_front();
+        }
+        queue.push_back(frame);
+    }
+
+    pub(crate) fn drain(&self) -> Vec<FrameEvent> {
+        self.0.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl FrameInfo {
+    pub(crate) fn now(
+        pts: Option<gst::ClockTime>,
+        frame_index: u64,
+        latency: Option<Duration>,
+    ) -> FrameInfo {
+        let now = SystemTime::now();
+        FrameInfo {
+            pts,
+            frame_index,
+            received_at: Instant::now(),
+            capture_time: latency
+                .and_then(|latency| now.checked_sub(latency))
+                .unwrap_or(now),
+        }
+    }
+}
+
+/// Sent for every sample accepted by a stream's appsink.
+pub struct VideoFrameReady {
+    pub handle: Handle<AppSinkImage>,
+    pub pts: Option<gst::ClockTime>,
+    pub frame_index: u64,
+    pub received_at: Instant,
+    pub capture_time: SystemTime,
+}
+
+/// Sent when a sample was skipped because it did not match the texture.
+pub struct VideoFrameRejected {
+    pub handle: Handle<AppSinkImage>,
+    pub reason: FrameRejection,
+}
+
+pub(crate) fn send_frame_events(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut frame_events: EventWriter<VideoFrameReady>,
+    mut rejected_events: EventWriter<VideoFrameRejected>,
+) {
+    for (id, appsink) in appsinks.iter() {
+        for event in appsink.frames.drain() {
+            match event {
+                FrameEvent::Ready(frame) => frame_events.send(VideoFrameReady {
+                    handle: Handle::weak(id),
+                    pts: frame.pts,
+                    frame_index: frame.frame_index,
+                    received_at: frame.received_at,
+                    capture_time: frame.capture_time,
+                }),
+                FrameEvent::Rejected(reason) => {
+                    warn!("Skipped frame: {:?}", reason);
+                    appsink.stats.frame_missed(1);
+                    rejected_events.send(VideoFrameRejected {
+                        handle: Handle::weak(id),
+                        reason,
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::stats::StreamStats;
+
+/// The frame of a stream every output shows during the current Bevy frame.
+///
+/// The streaming thread can write a new frame at any time, so outputs
+/// copying it at different points of the frame could show different frames.
+/// Instead the frame is taken once in `PreUpdate` and every texture, atlas
+/// tile and thumbnail of the stream is updated from that copy in the same
+/// Bevy frame.
+#[derive(Debug, Default)]
+pub struct FrameLatch {
+    latched: RwLock<Latched>,
+    /// Serial of the frame last copied into each output image.
+    shown: Mutex<HashMap<HandleId, u64>>,
+}
+
+#[derive(Debug, Default)]
+struct Latched {
+    /// Incremented for every latched frame.
+    serial: u64,
+    /// Whether the frame was latched in the current Bevy frame.
+    fresh: bool,
+    frame: Arc<Vec<u8>>,
+    pts: Option<gst::ClockTime>,
+}
+
+impl FrameLatch {
+    fn latch(&self, stats: &StreamStats, image_raw: &[u8], pts: Option<gst::ClockTime>) {
+        let mut latched = self.latched.write().unwrap();
+        latched.fresh = stats.frame_uploaded();
+        if latched.fresh {
+            latched.serial += 1;
+            latched.frame = Arc::new(image_raw.to_vec());
+            latched.pts = pts;
+        }
+    }
+
+    /// The last latched frame with its serial and timestamp, `None` before
+    /// the first one.
+    pub(crate) fn latest(&self) -> Option<(u64, Option<gst::ClockTime>, Arc<Vec<u8>>)> {
+        let latched = self.latched.read().unwrap();
+        (latched.serial > 0).then(|| (latched.serial, latched.pts, latched.frame.clone()))
+    }
+
+    /// The frame latched in the current Bevy frame, `None` if the stream has
+    /// no new frame.
+    pub(crate) fn fresh(&self) -> Option<(u64, Arc<Vec<u8>>)> {
+        let latched = self.latched.read().unwrap();
+        latched
+            .fresh
+            .then(|| (latched.serial, latched.frame.clone()))
+    }
+
+    pub(crate) fn is_fresh(&self) -> bool {
+        self.latched.read().unwrap().fresh
+    }
+
+    /// Records that `image` now shows the frame `serial`.
+    pub(crate) fn shown(&self, image: HandleId, serial: u64) {
+        self.shown.lock().unwrap().insert(image, serial);
+    }
+}
+
+impl AppSinkImage {
+    /// Serial of the frame latched for the outputs, incremented for every new
+    /// frame.
+    pub fn frame_serial(&self) -> u64 {
+        self.latch.latched.read().unwrap().serial
+    }
+
+    /// Output images not showing the latest frame of the stream. Empty when
+    /// every texture, atlas and thumbnail of the stream is in sync.
+    pub fn outputs_behind(&self) -> Vec<HandleId> {
+        let serial = self.frame_serial();
+        self.latch
+            .shown
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, shown)| **shown != serial)
+            .map(|(image, _)| *image)
+            .collect()
+    }
+
+    /// Whether every output of the stream shows the same frame.
+    pub fn outputs_in_sync(&self) -> bool {
+        self.outputs_behind().is_empty()
+    }
+}
+
+/// Latches the frame of every stream for the outputs updated this frame.
+pub(crate) fn latch_frames(appsinks: Res<Assets<AppSinkImage>>, images: Res<Assets<Image>>) {
+    for (_, appsink) in appsinks.iter() {
+        let image_raw = appsink.image_raw.read().unwrap();
+        let pts = *appsink.frame_pts.read().unwrap();
+        appsink.latch.latch(&appsink.stats, &image_raw[..], pts);
+        drop(image_raw);
+        // Outputs that are gone can't fall behind.
+        appsink
+            .latch
+            .shown
+            .lock()
+            .unwrap()
+            .retain(|image, _| images.contains(*image));
+    }
+}
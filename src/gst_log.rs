@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+use bevy::utils::tracing;
+
+/// Forwards GStreamer's debug log into `tracing`, so it ends up in the same
+/// output as Bevy's own logging instead of going straight to stderr.
+///
+/// Messages are logged with the `gstreamer` target and carry the GStreamer
+/// debug category as a field.
+pub struct GstLogPlugin {
+    /// Threshold applied to all GStreamer categories, unless `GST_DEBUG` is set.
+    pub threshold: gst::DebugLevel,
+}
+
+impl Default for GstLogPlugin {
+    fn default() -> Self {
+        GstLogPlugin {
+            threshold: gst::DebugLevel::Warning,
+        }
+    }
+}
+
+impl Plugin for GstLogPlugin {
+    fn build(&self, _app: &mut App) {
+        if let Err(err) = gst::init() {
+            error!("Failed to initialize GStreamer: {}", err);
+            return;
+        }
+
+        gst::debug_remove_default_log_function();
+        if std::env::var_os("GST_DEBUG").is_none() {
+            gst::debug_set_default_threshold(self.threshold);
+        }
+        gst::debug_set_active(true);
+
+        gst::debug_add_log_function(|category, level, file, function, line, _object, message| {
+            let message = match message.get() {
+                Some(message) => message,
+                None => return,
+            };
+            let category = category.name();
+            match level {
+                gst::DebugLevel::Error => {
+                    tracing::error!(target: "gstreamer", category, file, function, line, "{}", message)
+                }
+                gst::DebugLevel::Warning | gst::DebugLevel::Fixme => {
+                    tracing::warn!(target: "gstreamer", category, file, function, line, "{}", message)
+                }
+                gst::DebugLevel::Info => {
+                    tracing::info!(target: "gstreamer", category, file, function, line, "{}", message)
+                }
+                gst::DebugLevel::Debug => {
+                    tracing::debug!(target: "gstreamer", category, file, function, line, "{}", message)
+                }
+                _ => {
+                    tracing::trace!(target: "gstreamer", category, file, function, line, "{}", message)
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,66 @@
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use std::sync::atomic::Ordering;
+
+use crate::appsink::AppSinkImage;
+
+/// When a stream is flagged as degraded.
+#[derive(Debug, Clone)]
+pub struct DegradationConfig {
+    /// Consecutive dropped, rejected or late frames that mark a stream as
+    /// degraded. Zero disables the check.
+    pub threshold: u64,
+}
+
+impl Default for DegradationConfig {
+    fn default() -> Self {
+        DegradationConfig { threshold: 10 }
+    }
+}
+
+/// Sent when a stream misses `threshold` frames in a row.
+pub struct VideoDegraded {
+    pub handle: Handle<AppSinkImage>,
+    pub consecutive_missed: u64,
+}
+
+/// Sent when a degraded stream delivers a frame on time again.
+pub struct VideoHealthy {
+    pub handle: Handle<AppSinkImage>,
+}
+
+pub(crate) fn watch_degradation(
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut degraded_events: EventWriter<VideoDegraded>,
+    mut healthy_events: EventWriter<VideoHealthy>,
+) {
+    let changed: Vec<(HandleId, u64)> = appsinks
+        .iter()
+        .filter_map(|(id, appsink)| {
+            let threshold = appsink.degradation.threshold;
+            let missed = appsink.stats.consecutive_missed.load(Ordering::Relaxed);
+            let degraded = threshold > 0 && missed >= threshold;
+            let healthy = missed == 0;
+            ((degraded && !appsink.degraded) || (healthy && appsink.degraded)).then(|| (id, missed))
+        })
+        .collect();
+
+    for (id, missed) in changed {
+        let handle = Handle::weak(id);
+        if let Some(appsink) = appsinks.get_mut(&handle) {
+            appsink.degraded = missed > 0;
+            if appsink.degraded {
+                warn!("Stream degraded: {} frames missed in a row", missed);
+                degraded_events.send(VideoDegraded {
+                    handle: handle.clone_weak(),
+                    consecutive_missed: missed,
+                });
+            } else {
+                info!("Stream healthy again");
+                healthy_events.send(VideoHealthy {
+                    handle: handle.clone_weak(),
+                });
+            }
+        }
+    }
+}
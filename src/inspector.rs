@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContext, EguiPlugin};
+
+use crate::appsink::AppSinkImage;
+use crate::timeline::TimelineEvent;
+
+/// An egui window listing every stream with playback controls, stats, caps
+/// and errors. F1 shows and hides it.
+#[derive(Default)]
+pub struct VideoInspectorPlugin;
+
+impl Plugin for VideoInspectorPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<EguiContext>() {
+            app.add_plugin(EguiPlugin);
+        }
+        app.insert_resource(VideoInspector { open: true })
+            .add_system(toggle_inspector)
+            .add_system(show_inspector);
+    }
+}
+
+/// Whether the inspector window is shown.
+#[derive(Debug, Clone)]
+pub struct VideoInspector {
+    pub open: bool,
+}
+
+fn toggle_inspector(keys: Res<Input<KeyCode>>, mut inspector: ResMut<VideoInspector>) {
+    if keys.just_pressed(KeyCode::F1) {
+        inspector.open = !inspector.open;
+    }
+}
+
+fn show_inspector(
+    mut egui_context: ResMut<EguiContext>,
+    mut inspector: ResMut<VideoInspector>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    asset_server: Res<AssetServer>,
+) {
+    let mut open = inspector.open;
+    egui::Window::new("Video streams")
+        .open(&mut open)
+        .show(egui_context.ctx_mut(), |ui| {
+            let mut ids: Vec<HandleId> = appsinks.ids().collect();
+            ids.sort();
+            if ids.is_empty() {
+                ui.label("No streams");
+            }
+            for id in ids {
+                let name = asset_server
+                    .get_handle_path(id)
+                    .map(|path| path.path().display().to_string())
+                    .unwrap_or_else(|| format!("{:?}", id));
+                if let Some(appsink) = appsinks.get_mut(id) {
+                    egui::CollapsingHeader::new(name)
+                        .id_source(id)
+                        .default_open(true)
+                        .show(ui, |ui| stream_ui(ui, appsink));
+                }
+            }
+        });
+    if open != inspector.open {
+        inspector.open = open;
+    }
+}
+
+fn stream_ui(ui: &mut egui::Ui, appsink: &mut AppSinkImage) {
+    let state = if appsink.pipeline.is_none() {
+        "stopped"
+    } else if appsink.is_paused() {
+        "paused"
+    } else if appsink.stalled {
+        "stalled"
+    } else {
+        "playing"
+    };
+    ui.label(format!("State: {}", state));
+
+    ui.horizontal(|ui| {
+        let result = if appsink.pipeline.is_none() {
+            if ui.button("Start").clicked() {
+                appsink.start()
+            } else {
+                Ok(())
+            }
+        } else if appsink.is_paused() {
+            if ui.button("Play").clicked() {
+                appsink.play()
+            } else {
+                Ok(())
+            }
+        } else if ui.button("Pause").clicked() {
+            appsink.pause()
+        } else {
+            Ok(())
+        };
+        if appsink.pipeline.is_some() && ui.button("Stop").clicked() {
+            appsink.stop();
+        }
+        if let Err(err) = result {
+            appsink.error = Some(err);
+        }
+    });
+
+    if let (Some(position), Some(duration)) = (appsink.position(), appsink.duration()) {
+        let mut seconds = position.as_secs_f64();
+        let response = ui.add(
+            egui::Slider::new(&mut seconds, 0.0..=duration.as_secs_f64())
+                .suffix(" s")
+                .text("Position"),
+        );
+        if response.changed() {
+            if let Err(err) = appsink.seek(Duration::from_secs_f64(seconds)) {
+                appsink.error = Some(err);
+            }
+        }
+    }
+
+    let stats = appsink.stats.snapshot();
+    egui::Grid::new("stats").num_columns(2).show(ui, |ui| {
+        ui.label("Received");
+        ui.label(stats.received.to_string());
+        ui.end_row();
+        ui.label("Uploaded");
+        ui.label(stats.uploaded.to_string());
+        ui.end_row();
+        ui.label("Dropped");
+        ui.label(stats.dropped.to_string());
+        ui.end_row();
+        ui.label("Latency");
+        ui.label(format!("{:.1} ms", stats.latency_ns as f64 / 1_000_000.0));
+        ui.end_row();
+        ui.label("Source bytes");
+        ui.label(stats.bytes.to_string());
+        ui.end_row();
+    });
+
+    let caps = appsink
+        .timeline
+        .lock()
+        .unwrap()
+        .entries()
+        .filter_map(|entry| match &entry.event {
+            TimelineEvent::Caps(caps) => Some(caps.clone()),
+            _ => None,
+        })
+        .last();
+    if let Some(caps) = caps {
+        ui.label(format!("Caps: {}", caps));
+    }
+
+    if let Some(error) = &appsink.error {
+        ui.colored_label(
+            egui::Color32::LIGHT_RED,
+            format!("Error ({}): {}", error.kind(), error),
+        );
+    }
+}
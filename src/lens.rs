@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::material::VideoMaterial;
+use crate::player::VideoPlayer;
+
+/// Camera intrinsics and distortion coefficients as produced by OpenCV's
+/// `calibrateCamera`, used to undo the lens distortion of a stream in
+/// [`VideoMaterial`] so wide angle footage lines up with AR overlays.
+///
+/// Set it on [`AppSinkImage::calibration`] or with the `calibration_size`,
+/// `intrinsics` and `distortion` keys of the `.sinkimage` file.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LensCalibration {
+    /// Size of the images the camera was calibrated with, which the
+    /// intrinsics are in pixels of.
+    pub width: f32,
+    pub height: f32,
+    pub fx: f32,
+    pub fy: f32,
+    pub cx: f32,
+    pub cy: f32,
+    /// Radial coefficients.
+    pub k1: f32,
+    pub k2: f32,
+    pub k3: f32,
+    /// Tangential coefficients.
+    pub p1: f32,
+    pub p2: f32,
+}
+
+impl LensCalibration {
+    /// Focal lengths and principal point as fractions of the image size.
+    pub(crate) fn normalized_intrinsics(&self) -> Vec4 {
+        Vec4::new(
+            self.fx / self.width,
+            self.fy / self.height,
+            self.cx / self.width,
+            self.cy / self.height,
+        )
+    }
+
+    /// Sets `k1`, `k2`, `p1`, `p2` and optionally `k3`, in OpenCV's order.
+    pub fn set_distortion(&mut self, coefficients: &[f32]) -> bool {
+        match *coefficients {
+            [k1, k2, p1, p2] => {
+                *self = LensCalibration {
+                    k1,
+                    k2,
+                    p1,
+                    p2,
+                    ..*self
+                }
+            }
+            [k1, k2, p1, p2, k3] => {
+                *self = LensCalibration {
+                    k1,
+                    k2,
+                    p1,
+                    p2,
+                    k3,
+                    ..*self
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Whether the intrinsics are usable.
+    pub fn is_valid(&self) -> bool {
+        self.width > 0.0 && self.height > 0.0 && self.fx > 0.0 && self.fy > 0.0
+    }
+}
+
+/// Copies the calibration of each stream into the materials showing it.
+pub(crate) fn apply_lens_calibration(
+    appsinks: Res<Assets<AppSinkImage>>,
+    players: Query<(&VideoPlayer, &Handle<VideoMaterial>)>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    for (player, material) in players.iter() {
+        let calibration = appsinks
+            .get(&player.stream)
+            .and_then(|appsink| appsink.calibration)
+            .filter(LensCalibration::is_valid);
+        let current = materials.get(material).map(|material| material.undistort);
+        if current.map_or(false, |current| current != calibration) {
+            if let Some(material) = materials.get_mut(material) {
+                material.undistort = calibration;
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// How often the `level` element of the audio branch reports, about once
+/// per displayed frame.
+pub(crate) const LEVEL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Audio levels of every stream with audio, for drawing VU meters. Updated
+/// from the `level` messages of the pipelines every frame.
+#[derive(Debug, Default)]
+pub struct VideoAudioLevels {
+    pub streams: HashMap<Handle<AppSinkImage>, AudioLevels>,
+}
+
+impl VideoAudioLevels {
+    pub fn get(&self, handle: &Handle<AppSinkImage>) -> Option<&AudioLevels> {
+        self.streams.get(handle)
+    }
+}
+
+/// Levels of each channel of a stream, in dBFS: 0 is full scale, silence is
+/// a large negative value.
+#[derive(Debug, Clone, Default)]
+pub struct AudioLevels {
+    pub channels: Vec<ChannelLevel>,
+    /// Stream time of the measured samples.
+    pub timestamp: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChannelLevel {
+    pub rms: f64,
+    pub peak: f64,
+    /// Peak falling back slowly, for peak-hold indicators.
+    pub decay: f64,
+}
+
+impl ChannelLevel {
+    /// RMS as a fraction of full scale, from 0 to 1.
+    pub fn rms_linear(&self) -> f32 {
+        db_to_linear(self.rms)
+    }
+
+    /// Peak as a fraction of full scale, from 0 to 1.
+    pub fn peak_linear(&self) -> f32 {
+        db_to_linear(self.peak)
+    }
+}
+
+fn db_to_linear(db: f64) -> f32 {
+    10f64.powf(db / 20.0).clamp(0.0, 1.0) as f32
+}
+
+impl AudioLevels {
+    /// Reads a `level` element message, `None` for any other message.
+    pub(crate) fn from_structure(structure: &gst::StructureRef) -> Option<AudioLevels> {
+        if structure.name() != "level" {
+            return None;
+        }
+        let values = |field: &str| -> Vec<f64> {
+            structure
+                .get::<glib::ValueArray>(field)
+                .map(|array| array.iter().filter_map(|v| v.get::<f64>().ok()).collect())
+                .unwrap_or_default()
+        };
+        let (rms, peak, decay) = (values("rms"), values("peak"), values("decay"));
+        let channels = rms
+            .iter()
+            .zip(&peak)
+            .zip(&decay)
+            .map(|((rms, peak), decay)| ChannelLevel {
+                rms: *rms,
+                peak: *peak,
+                decay: *decay,
+            })
+            .collect();
+        let timestamp = structure
+            .get::<u64>("stream-time")
+            .ok()
+            .map(Duration::from_nanos);
+        Some(AudioLevels {
+            channels,
+            timestamp,
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::asset::{AssetLoader, AssetPath, BoxedFuture, HandleId, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::error::VideoError;
+use crate::material::VideoMaterial;
+
+/// Handle of a 2x2x2 LUT that leaves colors unchanged, used by materials
+/// without a grade.
+pub const IDENTITY_LUT_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Image::TYPE_UUID, 0x71e4_0c9b_2a58_3df6);
+
+/// Label of the [`LutDomain`] sub-asset of a `.cube` file.
+pub const LUT_DOMAIN_LABEL: &str = "domain";
+
+/// Input range of a LUT, from `DOMAIN_MIN` and `DOMAIN_MAX` of its `.cube`
+/// file. Colors are rescaled from it to the 0 to 1 range of the texture
+/// before the lookup.
+#[derive(Debug, Clone, Copy, PartialEq, TypeUuid)]
+#[uuid = "c3a9e2d4-51f7-4b08-8e6d-2f4a7b1c9e05"]
+pub struct LutDomain {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for LutDomain {
+    fn default() -> Self {
+        LutDomain {
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+        }
+    }
+}
+
+/// Loads Adobe/Resolve `.cube` files as 3D textures for
+/// [`VideoMaterial::lut`], with their [`LutDomain`] as the `domain`
+/// sub-asset.
+#[derive(Default)]
+pub struct CubeLutLoader;
+
+impl AssetLoader for CubeLutLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let (lut, domain) = parse_cube(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(lut));
+            load_context.set_labeled_asset(LUT_DOMAIN_LABEL, LoadedAsset::new(domain));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["cube"]
+    }
+}
+
+/// A LUT of `size`³ entries that maps every color to itself.
+pub fn identity_lut(size: u32) -> Image {
+    let scale = 255.0 / (size - 1) as f32;
+    let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+    for b in 0..size {
+        for g in 0..size {
+            for r in 0..size {
+                data.extend_from_slice(&[
+                    (r as f32 * scale) as u8,
+                    (g as f32 * scale) as u8,
+                    (b as f32 * scale) as u8,
+                    255,
+                ]);
+            }
+        }
+    }
+    lut_image(size, data)
+}
+
+fn lut_image(size: u32, data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        },
+        TextureDimension::D3,
+        data,
+        TextureFormat::Rgba8Unorm,
+    )
+}
+
+/// Parses a 3D `.cube` file into its texture and input domain. Entries are
+/// output colors from 0 to 1, stored with red changing fastest, which is the
+/// texel order of a 3D texture indexed by (r, g, b).
+pub fn parse_cube(text: &str) -> Result<(Image, LutDomain), VideoError> {
+    let mut size = None;
+    let mut domain = LutDomain::default();
+    let mut data = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| VideoError::Lut {
+            line: index + 1,
+            message,
+        };
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or_default();
+        let parse_triple = |words: std::str::SplitWhitespace| -> Result<[f32; 3], VideoError> {
+            let values: Vec<f32> = words
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid(format!("expected three numbers, got `{}`", line)))?;
+            match values[..] {
+                [r, g, b] => Ok([r, g, b]),
+                _ => Err(invalid(format!("expected three numbers, got `{}`", line))),
+            }
+        };
+
+        match keyword {
+            "TITLE" => {}
+            "LUT_3D_SIZE" => {
+                let value = words
+                    .next()
+                    .and_then(|value| value.parse::<u32>().ok())
+                    .filter(|size| (2..=256).contains(size))
+                    .ok_or_else(|| invalid(format!("invalid size `{}`", line)))?;
+                size = Some(value);
+                data.reserve((value * value * value * 4) as usize);
+            }
+            "LUT_1D_SIZE" => return Err(invalid(String::from("1D LUTs are not supported"))),
+            "DOMAIN_MIN" => domain.min = Vec3::from(parse_triple(words)?),
+            "DOMAIN_MAX" => domain.max = Vec3::from(parse_triple(words)?),
+            _ => {
+                if size.is_none() {
+                    return Err(invalid(format!("expected `LUT_3D_SIZE`, got `{}`", line)));
+                }
+                let entry = parse_triple(line.split_whitespace())?;
+                for value in entry {
+                    data.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+                data.push(255);
+            }
+        }
+    }
+
+    let size = size.ok_or_else(|| VideoError::Lut {
+        line: 0,
+        message: String::from("missing `LUT_3D_SIZE`"),
+    })?;
+    if domain.min.cmpge(domain.max).any() {
+        return Err(VideoError::Lut {
+            line: 0,
+            message: format!(
+                "`DOMAIN_MIN` {} is not below `DOMAIN_MAX` {}",
+                domain.min, domain.max
+            ),
+        });
+    }
+    let expected = (size * size * size * 4) as usize;
+    if data.len() != expected {
+        return Err(VideoError::Lut {
+            line: 0,
+            message: format!("expected {} entries, got {}", expected / 4, data.len() / 4),
+        });
+    }
+    Ok((lut_image(size, data), domain))
+}
+
+/// Sets [`VideoMaterial::lut_domain`] from the `.cube` file the LUT of each
+/// material was loaded from.
+pub(crate) fn apply_lut_domains(
+    asset_server: Res<AssetServer>,
+    domains: Res<Assets<LutDomain>>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    let changed: Vec<(HandleId, LutDomain)> = materials
+        .iter()
+        .filter_map(|(id, material)| {
+            let domain = if material.lut.id == IDENTITY_LUT_HANDLE.id {
+                LutDomain::default()
+            } else {
+                // LUTs built in code keep the domain set on the material.
+                let path = asset_server.get_handle_path(&material.lut)?;
+                *domains.get(AssetPath::new_ref(path.path(), Some(LUT_DOMAIN_LABEL)))?
+            };
+            (material.lut_domain != domain).then(|| (id, domain))
+        })
+        .collect();
+    for (id, domain) in changed {
+        if let Some(material) = materials.get_mut(&Handle::weak(id)) {
+            material.lut_domain = domain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line and message of the error `text` fails with.
+    fn error(text: &str) -> (usize, String) {
+        match parse_cube(text) {
+            Err(VideoError::Lut { line, message }) => (line, message),
+            Err(err) => panic!("{:?}: unexpected {}", text, err),
+            Ok(_) => panic!("{:?}: parsed", text),
+        }
+    }
+
+    const IDENTITY: &str = "TITLE \"identity\"
+# comment
+LUT_3D_SIZE 2
+
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+
+    #[test]
+    fn parses_identity() {
+        let (image, domain) = parse_cube(IDENTITY).unwrap();
+        assert_eq!(image.data, identity_lut(2).data);
+        assert_eq!(image.texture_descriptor.dimension, TextureDimension::D3);
+        assert_eq!(domain, LutDomain::default());
+    }
+
+    #[test]
+    fn keeps_the_domain_out_of_the_entries() {
+        let text = IDENTITY.replace(
+            "LUT_3D_SIZE 2",
+            "LUT_3D_SIZE 2\nDOMAIN_MIN -0.5 0 0\nDOMAIN_MAX 1.5 1 2",
+        );
+        let (image, domain) = parse_cube(&text).unwrap();
+        assert_eq!(image.data, identity_lut(2).data);
+        assert_eq!(
+            domain,
+            LutDomain {
+                min: Vec3::new(-0.5, 0.0, 0.0),
+                max: Vec3::new(1.5, 1.0, 2.0),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_an_empty_domain() {
+        let text = IDENTITY.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 2\nDOMAIN_MAX 1 0 1");
+        assert_eq!(
+            error(&text),
+            (
+                0,
+                String::from("`DOMAIN_MIN` [0, 0, 0] is not below `DOMAIN_MAX` [1, 0, 1]")
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_a_wrong_entry_count() {
+        let text = IDENTITY.replace("1 1 1\n", "");
+        assert_eq!(error(&text), (0, String::from("expected 8 entries, got 7")));
+    }
+
+    #[test]
+    fn rejects_1d_luts() {
+        assert_eq!(
+            error("LUT_1D_SIZE 4"),
+            (1, String::from("1D LUTs are not supported"))
+        );
+    }
+
+    #[test]
+    fn rejects_a_missing_size() {
+        assert_eq!(
+            error("0 0 0"),
+            (1, String::from("expected `LUT_3D_SIZE`, got `0 0 0`"))
+        );
+        assert_eq!(error(""), (0, String::from("missing `LUT_3D_SIZE`")));
+        assert_eq!(
+            error("LUT_3D_SIZE 1"),
+            (1, String::from("invalid size `LUT_3D_SIZE 1`"))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_triples() {
+        for entry in ["0 0", "0 0 0 0", "0 x 0"] {
+            let text = format!("LUT_3D_SIZE 2\n{}", entry);
+            assert_eq!(
+                error(&text),
+                (2, format!("expected three numbers, got `{}`", entry)),
+            );
+        }
+        assert_eq!(
+            error("DOMAIN_MIN 0 0"),
+            (
+                1,
+                String::from("expected three numbers, got `DOMAIN_MIN 0 0`")
+            )
+        );
+    }
+}
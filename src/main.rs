@@ -1,8 +1,24 @@
 //! Renders a 2D scene containing a single, moving sprite.
 
-use appsink::{AppSinkImage, AppSinkImageLoader};
-use gst::traits::GstObjectExt;
+use appsink::{AppSinkImage, AppSinkPlugin};
+use atlas::VideoAtlasPlugin;
+use audiosink::AppSinkAudioPlugin;
+use background::VideoBackgroundPlugin;
+use beat::BeatDetected;
+use export::ExportConfig;
+use gst_log::GstLogPlugin;
+use material::{VideoBundle, VideoMaterial, VideoMaterialPlugin};
+use output::{VideoOutputCamera, VideoOutputPlugin};
+use overlay::ErrorOverlayPlugin;
+use projector::VideoProjectorPlugin;
+use security::SecurityGridPlugin;
+use spatial_audio::{AudioListener, SpatialAudioPlugin};
+use stats::VideoDiagnosticsPlugin;
 use std::f32::consts::PI;
+use ui::VideoUiPlugin;
+use wall::VideoWallPlugin;
+use warp::VideoWarpPlugin;
+use window::VideoWindowPlugin;
 
 use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
@@ -12,67 +28,169 @@ use bevy::{
         render_resource::{
             Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
         },
+        texture::BevyDefault,
         view::RenderLayers,
     },
 };
 mod appsink;
+mod atlas;
+mod audio;
+mod audiosink;
+mod avsync;
+mod background;
+mod beat;
+mod burst;
+mod capabilities;
+mod compositor;
+mod config;
+mod curved;
+mod device;
+mod discover;
+mod error;
+mod export;
+mod frame;
+mod framesync;
+mod gst_log;
+mod health;
+#[cfg(feature = "egui")]
+mod inspector;
+mod lens;
+mod levels;
+mod lut;
+mod material;
+mod mediaclock;
+mod missing;
+mod netclock;
+mod output;
+mod overlay;
+mod photo;
+mod playback;
+mod player;
+mod projector;
+mod qos;
+mod rawframe;
+mod recovery;
+mod sampler;
+mod security;
+mod seek_preview;
+#[cfg(feature = "segmentation")]
+mod segmentation;
+mod snapshot;
+mod spatial_audio;
+mod spectrum;
+mod stats;
+mod tags;
+mod text_overlay;
+mod thumbnail;
+mod timeline;
+mod timeshift;
+mod transition;
+mod ui;
+mod wall;
+mod warp;
+mod watchdog;
+mod webrtc;
+mod window;
 
 #[derive(Default)]
 struct State {
     appsink_handle: Handle<AppSinkImage>,
-    image_handle: Handle<Image>,
-    material_handle: Handle<StandardMaterial>,
 }
 
-impl State {
-    fn copy_image(&self, appsinks: Res<Assets<AppSinkImage>>, mut images: ResMut<Assets<Image>>) {
-        if let (Some(imagesink), Some(image)) = (
-            appsinks.get(&self.appsink_handle),
-            images.get_mut(&self.image_handle),
-        ) {
-            if let Ok(vide_image) = imagesink.image_raw.read() {
-                image.data = vide_image.to_vec();
-            }
-        } else {
-            println!("Not loaded")
-        }
-        images.set_changed();
+fn main() {
+    if std::env::args().any(|arg| arg == "--list-devices") {
+        print_devices();
+        return;
     }
 
-    fn update_material(
-        &self,
-        images: Res<Assets<Image>>,
-        mut materials: ResMut<Assets<StandardMaterial>>,
-    ) {
-        if let (Some(image), Some(material)) = (
-            images.get(&self.image_handle),
-            materials.get_mut(&self.material_handle),
-        ) {
-            material.base_color_texture = Some(self.image_handle.clone_weak());
-        }
-        materials.set_changed();
-    }
-}
+    // `--split a.sinkimage b.sinkimage ...` shows several streams side by
+    // side instead of the default scene.
+    let split: Vec<String> = std::env::args()
+        .skip_while(|arg| arg != "--split")
+        .skip(1)
+        .collect();
 
-fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
         .insert_resource(State::default())
-        .add_asset::<AppSinkImage>()
-        .init_asset_loader::<AppSinkImageLoader>()
-        .add_startup_system(setup)
-        .add_system(copy_texture)
-        .add_system(update_material)
+        .add_plugin(GstLogPlugin::default())
+        .add_plugin(AppSinkPlugin)
+        .add_plugin(AppSinkAudioPlugin)
+        .add_plugin(VideoDiagnosticsPlugin)
+        .add_plugin(ErrorOverlayPlugin)
+        .add_plugin(VideoOutputPlugin)
+        .add_plugin(VideoMaterialPlugin)
+        .add_plugin(VideoProjectorPlugin)
+        .add_plugin(VideoBackgroundPlugin)
+        .add_plugin(VideoUiPlugin)
+        .add_plugin(VideoAtlasPlugin)
+        .add_plugin(VideoWallPlugin)
+        .add_plugin(VideoWindowPlugin)
+        .add_plugin(SecurityGridPlugin)
+        .add_plugin(VideoWarpPlugin)
+        .add_plugin(SpatialAudioPlugin)
         .add_system(cube_rotator_system)
-        //.add_system(monitor_bus)
-        .run();
+        .add_system(pulse_cube_on_beat)
+        .add_system(dump_debug_on_key)
+        .add_system(toggle_recording)
+        .add_system(take_photo_on_key);
+    if split.is_empty() {
+        app.add_startup_system(setup);
+    } else {
+        app.insert_resource(SplitScreen { paths: split })
+            .add_startup_system(setup_split);
+    }
+    #[cfg(feature = "egui")]
+    app.add_plugin(inspector::VideoInspectorPlugin);
+    app.run();
 }
+/// Prints the capture devices and their formats, for `--list-devices`.
+fn print_devices() {
+    let devices = match device::list_devices() {
+        Ok(devices) => devices,
+        Err(err) => {
+            eprintln!("Failed to list devices: {}", err);
+            std::process::exit(1);
+        }
+    };
+    if devices.is_empty() {
+        println!("No capture devices found");
+    }
+    for device in devices {
+        println!("{}", device.name);
+        if let Some(path) = device.path {
+            println!("  device = {}", path);
+        }
+        for caps in device.caps {
+            println!("    {}", caps);
+        }
+    }
+}
+
 fn cube_rotator_system(time: Res<Time>, mut query: Query<&mut Transform, With<MainPassCube>>) {
     for mut transform in &mut query {
         transform.rotate_x(1.0 * time.delta_seconds());
         transform.rotate_y(0.7 * time.delta_seconds());
     }
 }
+
+/// Bumps the size of the cube on every beat of the stream's audio, easing
+/// back in between.
+fn pulse_cube_on_beat(
+    time: Res<Time>,
+    mut beats: EventReader<BeatDetected>,
+    mut query: Query<&mut Transform, With<MainPassCube>>,
+) {
+    let strength = beats.iter().map(|beat| beat.strength).fold(0.0, f32::max);
+    for mut transform in &mut query {
+        if strength > 0.0 {
+            transform.scale = Vec3::splat(1.0 + 0.15 * strength.min(3.0));
+        } else {
+            let ease = (8.0 * time.delta_seconds()).min(1.0);
+            transform.scale = transform.scale.lerp(Vec3::ONE, ease);
+        }
+    }
+}
 #[derive(Component)]
 enum Direction {
     Up,
@@ -82,6 +200,14 @@ enum Direction {
 #[derive(Component)]
 struct MainPassCube;
 
+// Marks the camera rendering into the texture that can be recorded.
+#[derive(Component)]
+struct CaptureCamera;
+
+/// Size of the texture rendered by the capture camera.
+const CAPTURE_WIDTH: u32 = 640;
+const CAPTURE_HEIGHT: u32 = 360;
+
 // fn setup(
 //     mut commands: Commands,
 //     mut state: ResMut<State>,
@@ -168,131 +294,217 @@ fn setup(
     asset_server: Res<AssetServer>,
     mut images: ResMut<Assets<Image>>,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
 ) {
-    let size = Extent3d {
-        width: 176,
-        height: 144,
+    let appsink_handle = asset_server.load("test.sinkimage");
+
+    //3d stuff
+    let cube_size = 4.0;
+    let cube_handle = meshes.add(Mesh::from(shape::Box::new(cube_size, cube_size, cube_size)));
+
+    // Light
+    // NOTE: Currently lights are shared between passes - see https://github.com/bevyengine/bevy/issues/3462
+    commands.spawn_bundle(PointLightBundle {
+        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
         ..default()
-    };
+    });
+
+    // Main pass cube, lit by the scene with a little glow so it stays
+    // readable on the dark side.
+    let mut cube = VideoBundle::new(
+        appsink_handle.clone(),
+        cube_handle,
+        VideoMaterial::lit(0.3),
+        &mut images,
+        &mut materials,
+    );
+    cube.mesh.transform =
+        Transform::from_xyz(0.0, 0.0, 1.5).with_rotation(Quat::from_rotation_x(-PI / 5.0));
+    let image_handle = cube.texture.0.clone();
+    commands.spawn_bundle(cube).insert(MainPassCube);
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            texture: image_handle,
+            transform: Transform::from_xyz(100., 0., 0.),
+            ..default()
+        })
+        .insert(Direction::Up);
+
+    // The main pass camera.
+    commands
+        .spawn_bundle(Camera3dBundle {
+            transform: Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
+            ..default()
+        })
+        .insert(AudioListener);
 
-    let mut image = Image {
+    // A second camera renders the same scene into a texture that can be
+    // recorded with R.
+    let capture_size = Extent3d {
+        width: CAPTURE_WIDTH,
+        height: CAPTURE_HEIGHT,
+        ..default()
+    };
+    let mut capture = Image {
         texture_descriptor: TextureDescriptor {
             label: None,
-            size,
+            size: capture_size,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Rgba8UnormSrgb,
+            format: TextureFormat::bevy_default(),
             mip_level_count: 1,
             sample_count: 1,
             usage: TextureUsages::TEXTURE_BINDING
-                | TextureUsages::COPY_DST
+                | TextureUsages::COPY_SRC
                 | TextureUsages::RENDER_ATTACHMENT,
         },
         ..default()
     };
+    capture.resize(capture_size);
+    let capture_handle = images.add(capture);
 
-    image.resize(size);
-    let image_handle = images.add(image);
-
-    //commands.spawn_bundle(Camera2dBundle::default());
     commands
-        .spawn_bundle(SpriteBundle {
-            texture: image_handle.clone_weak(),
-            transform: Transform::from_xyz(100., 0., 0.),
+        .spawn_bundle(Camera3dBundle {
+            camera: Camera {
+                target: RenderTarget::Image(capture_handle),
+                priority: -1,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
             ..default()
         })
-        .insert(Direction::Up);
+        .insert(CaptureCamera);
 
-    //3d stuff
-    let cube_size = 4.0;
+    state.appsink_handle = appsink_handle;
+}
+
+/// Streams shown side by side, from `--split`.
+struct SplitScreen {
+    paths: Vec<String>,
+}
+
+/// Spawns one spinning cube per stream in a row. The debug keys act on the
+/// first stream.
+fn setup_split(
+    split: Res<SplitScreen>,
+    mut state: ResMut<State>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    let cube_size = 2.0;
+    let spacing = 1.0;
     let cube_handle = meshes.add(Mesh::from(shape::Box::new(cube_size, cube_size, cube_size)));
 
-    // This material has the texture that has been rendered.
-    let material_handle = materials.add(StandardMaterial {
-        base_color_texture: Some(image_handle.clone_weak()),
-        reflectance: 0.02,
-        unlit: false,
-        ..default()
-    });
+    let count = split.paths.len() as f32;
+    let step = cube_size + spacing;
+    for (index, path) in split.paths.iter().enumerate() {
+        let appsink_handle: Handle<AppSinkImage> = asset_server.load(path.as_str());
+        if index == 0 {
+            state.appsink_handle = appsink_handle.clone();
+        }
+        let mut cube = VideoBundle::new(
+            appsink_handle,
+            cube_handle.clone(),
+            VideoMaterial::lit(0.3),
+            &mut images,
+            &mut materials,
+        );
+        cube.mesh.transform =
+            Transform::from_xyz((index as f32 - (count - 1.0) / 2.0) * step, 0.0, 0.0);
+        commands.spawn_bundle(cube).insert(MainPassCube);
+    }
 
-    // Light
-    // NOTE: Currently lights are shared between passes - see https://github.com/bevyengine/bevy/issues/3462
     commands.spawn_bundle(PointLightBundle {
         transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
         ..default()
     });
 
-    // Main pass cube, with material containing the rendered first pass texture.
-    commands
-        .spawn_bundle(PbrBundle {
-            mesh: cube_handle,
-            material: material_handle.clone_weak(),
-            transform: Transform::from_xyz(0.0, 0.0, 1.5)
-                .with_rotation(Quat::from_rotation_x(-PI / 5.0)),
-            ..default()
-        })
-        .insert(MainPassCube);
-
-    commands.spawn_bundle(SpriteBundle {
-        texture: image_handle.clone_weak(),
-        transform: Transform::from_xyz(100., 0., 0.),
-        ..default()
-    });
-
-    // The main pass camera.
+    // Back off far enough for the whole row to fit.
+    let distance = (count * step).max(8.0) * 1.2;
     commands.spawn_bundle(Camera3dBundle {
-        transform: Transform::from_xyz(0.0, 0.0, 15.0).looking_at(Vec3::ZERO, Vec3::Y),
+        transform: Transform::from_xyz(0.0, 0.0, distance).looking_at(Vec3::ZERO, Vec3::Y),
         ..default()
     });
-
-    state.appsink_handle = asset_server.load("test.sinkimage");
-    state.image_handle = image_handle;
-    state.material_handle = material_handle;
 }
 
-/// The sprite is animated by changing its translation depending on the time that has passed since
-/// the last frame.
-fn copy_texture(
+/// Writes the pipeline graph to `pipeline.dot` when F12 is pressed, the
+/// stream timeline to `timeline.txt` when F11 is pressed, and the current
+/// frame to `snapshot.png` when F10 is pressed. F9 starts or stops exporting
+/// every tenth frame to `frames/`.
+fn dump_debug_on_key(
+    keys: Res<Input<KeyCode>>,
     state: Res<State>,
     appsinks: Res<Assets<AppSinkImage>>,
-    images: ResMut<Assets<Image>>,
 ) {
-    state.copy_image(appsinks, images);
+    let appsink = match appsinks.get(&state.appsink_handle) {
+        Some(appsink) => appsink,
+        None => return,
+    };
+    if keys.just_pressed(KeyCode::F12) {
+        match appsink.dump_graph("pipeline.dot") {
+            Ok(()) => info!("Pipeline graph written to pipeline.dot"),
+            Err(err) => error!("{}", err),
+        }
+    }
+    if keys.just_pressed(KeyCode::F11) {
+        match appsink.dump_timeline("timeline.txt") {
+            Ok(()) => info!("Stream timeline written to timeline.txt"),
+            Err(err) => error!("{}", err),
+        }
+    }
+    if keys.just_pressed(KeyCode::F9) {
+        if let Some(frames) = appsink.stop_export() {
+            info!("Exported {} frames to frames/", frames);
+        } else if let Err(err) = appsink.export_frames(ExportConfig::every("frames", 10)) {
+            error!("{}", err);
+        }
+    }
+    if keys.just_pressed(KeyCode::F10) {
+        match appsink.save_snapshot("snapshot.png") {
+            Ok(task) => task.detach(),
+            Err(err) => error!("{}", err),
+        }
+    }
 }
 
-fn update_material(
+/// Takes a full resolution photo from the camera when P is pressed.
+fn take_photo_on_key(
+    keys: Res<Input<KeyCode>>,
     state: Res<State>,
-    images: Res<Assets<Image>>,
-    materials: ResMut<Assets<StandardMaterial>>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
 ) {
-    state.update_material(images, materials);
+    if !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+    if let Some(appsink) = appsinks.get_mut(&state.appsink_handle) {
+        if let Err(err) = appsink.capture_photo("photo.jpg") {
+            error!("{}", err);
+        }
+    }
 }
 
-use appsink::ErrorMessage;
-
-fn monitor_bus(state: Res<State>, appsinks: Res<Assets<AppSinkImage>>) {
-    if let Some(appsink) = appsinks.get(&state.appsink_handle) {
-        if let Some(msg) = appsink.bus.timed_pop(gst::ClockTime::NONE) {
-            use gst::MessageView;
-
-            match msg.view() {
-                MessageView::Eos(..) => println!("eos"),
-                MessageView::Error(err) => {
-                    println!(
-                        "{:?}",
-                        ErrorMessage {
-                            src: msg
-                                .src()
-                                .map(|s| String::from(s.path_string()))
-                                .unwrap_or_else(|| String::from("None")),
-                            error: err.error().to_string(),
-                            debug: err.debug(),
-                            source: err.error(),
-                        }
-                    );
-                }
-                _ => (),
-            }
+/// Starts or stops recording the capture camera to `recording.mp4` when R is
+/// pressed.
+fn toggle_recording(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    cameras: Query<(Entity, Option<&VideoOutputCamera>), With<CaptureCamera>>,
+) {
+    if !keys.just_pressed(KeyCode::R) {
+        return;
+    }
+    for (entity, output) in &cameras {
+        if output.is_some() {
+            commands.entity(entity).remove::<VideoOutputCamera>();
+            info!("Recording written to recording.mp4");
+        } else {
+            commands
+                .entity(entity)
+                .insert(VideoOutputCamera::record("recording.mp4"));
+            info!("Recording to recording.mp4");
         }
     }
 }
@@ -0,0 +1,753 @@
+use std::collections::HashMap;
+
+use bevy::asset::{load_internal_asset, HandleId};
+use bevy::pbr::{MaterialPipeline, MaterialPipelineKey};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{
+    AsBindGroup, AsBindGroupShaderType, Extent3d, RenderPipelineDescriptor, ShaderRef, ShaderType,
+    SpecializedMeshPipelineError, TextureDimension, TextureFormat,
+};
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::lens::{apply_lens_calibration, LensCalibration};
+use crate::lut::{apply_lut_domains, identity_lut, CubeLutLoader, LutDomain, IDENTITY_LUT_HANDLE};
+use crate::player::VideoPlayer;
+use crate::sampler::apply_video_samplers;
+use crate::transition::{advance_transitions, start_transitions, TransitionKind};
+
+const VIDEO_MATERIAL_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x2b9e_61c4_d03a_7f15);
+const VIDEO_MATERIAL_FUNCTIONS_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x8d17_f3a0_4e6c_b259);
+
+/// Registers [`VideoMaterial`] and keeps the textures of [`VideoBundle`]s and
+/// [`VideoPbrBundle`]s in sync with their streams.
+#[derive(Default)]
+pub struct VideoMaterialPlugin;
+
+impl Plugin for VideoMaterialPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            VIDEO_MATERIAL_SHADER_HANDLE,
+            "video_material.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            VIDEO_MATERIAL_FUNCTIONS_HANDLE,
+            "video_material_functions.wgsl",
+            Shader::from_wgsl
+        );
+        #[cfg(feature = "segmentation")]
+        app.add_plugin(crate::segmentation::SegmentationPlugin);
+        app.world
+            .resource_mut::<Assets<Image>>()
+            .set_untracked(IDENTITY_LUT_HANDLE, identity_lut(2));
+        app.add_asset::<LutDomain>()
+            .init_asset_loader::<CubeLutLoader>()
+            .add_plugin(MaterialPlugin::<VideoMaterial>::default())
+            .add_system(bind_material_slots)
+            .add_system(start_transitions)
+            .add_system(advance_transitions.after(start_transitions))
+            .add_system(update_effect_time)
+            .add_system(apply_video_balance)
+            .add_system(apply_lens_calibration)
+            .add_system(apply_lut_domains)
+            .add_system(apply_video_samplers.after(bind_material_slots))
+            .add_system(
+                update_video_textures
+                    .after(bind_material_slots)
+                    .after(start_transitions),
+            );
+    }
+}
+
+/// Displays a video texture, either lit by the scene like
+/// [`StandardMaterial`] or unlit at full brightness.
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "6f0c1f2e-8d4b-4c57-9a0e-3b7d2c1e5f48"]
+#[uniform(0, VideoMaterialUniform)]
+#[bind_group_data(VideoMaterialKey)]
+pub struct VideoMaterial {
+    /// Multiplied with every pixel of the video.
+    pub tint: Color,
+    /// Ignore scene lighting, so the video looks like a screen.
+    pub unlit: bool,
+    /// How much of the video is added as emitted light on top of the lit
+    /// result. Zero disables it. Ignored when `unlit` is set.
+    pub emissive: f32,
+    /// Makes pixels close to a color transparent, for green screen footage.
+    pub chroma_key: Option<ChromaKey>,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Option<Handle<Image>>,
+    /// One channel image multiplied with the alpha of the video, such as the
+    /// output of a segmentation model.
+    #[texture(3)]
+    #[sampler(4)]
+    pub matte: Option<Handle<Image>>,
+    /// Frame blended with `texture` while a [`VideoTransition`] plays.
+    #[texture(5)]
+    #[sampler(6)]
+    pub previous: Option<Handle<Image>>,
+    /// Kind and progress from 0 to 1 of the running transition, set by
+    /// [`VideoTransition`].
+    pub transition: Option<(TransitionKind, f32)>,
+    /// Color grade applied to the video, load one with
+    /// `asset_server.load("grade.cube")`. [`IDENTITY_LUT_HANDLE`] leaves the
+    /// colors unchanged.
+    #[texture(7, dimension = "3d")]
+    #[sampler(8)]
+    pub lut: Handle<Image>,
+    /// Input range of `lut`, set from the `.cube` file it was loaded from.
+    pub lut_domain: LutDomain,
+    /// Fragment shader replacing the default one, see [`effect_shader`].
+    pub effect: Option<Handle<Shader>>,
+    /// Passed to `effect` as `material.effect_params`, with no meaning to the
+    /// material itself.
+    pub effect_params: Vec4,
+    /// Seconds since startup, passed to `effect` as `material.time`. Kept up
+    /// to date while `effect` is set.
+    pub time: f32,
+    /// Exposure and color correction, set from the entity's [`VideoBalance`].
+    pub balance: VideoBalance,
+    /// Shape cut out of the video, such as a circle for webcam bubbles.
+    pub mask: Option<VideoMask>,
+    /// Image whose alpha is multiplied with the alpha of the video, for
+    /// masks that aren't a [`VideoMask`] shape.
+    #[texture(9)]
+    #[sampler(10)]
+    pub mask_texture: Option<Handle<Image>>,
+    /// Lens distortion removed from the video, set from the
+    /// [`AppSinkImage::calibration`] of the stream.
+    pub undistort: Option<LensCalibration>,
+}
+
+impl Default for VideoMaterial {
+    fn default() -> Self {
+        VideoMaterial {
+            tint: Color::WHITE,
+            unlit: true,
+            emissive: 0.0,
+            chroma_key: None,
+            texture: None,
+            matte: None,
+            previous: None,
+            transition: None,
+            lut: IDENTITY_LUT_HANDLE.typed(),
+            lut_domain: LutDomain::default(),
+            effect: None,
+            effect_params: Vec4::ZERO,
+            time: 0.0,
+            balance: VideoBalance::default(),
+            mask: None,
+            mask_texture: None,
+            undistort: None,
+        }
+    }
+}
+
+impl VideoMaterial {
+    /// A material affected by scene lighting, with `emissive` added on top.
+    pub fn lit(emissive: f32) -> Self {
+        VideoMaterial {
+            unlit: false,
+            emissive,
+            ..default()
+        }
+    }
+
+    /// Keys out `chroma_key.color`, blending the rest of the video with the
+    /// scene.
+    pub fn with_chroma_key(self, chroma_key: ChromaKey) -> Self {
+        VideoMaterial {
+            chroma_key: Some(chroma_key),
+            ..self
+        }
+    }
+
+    /// Cuts `mask` out of the video, blending the rest with the scene.
+    pub fn with_mask(self, mask: VideoMask) -> Self {
+        VideoMaterial {
+            mask: Some(mask),
+            ..self
+        }
+    }
+}
+
+/// Shapes [`VideoMaterial::mask`] can cut out of the video, in UV space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoMask {
+    /// The ellipse touching the edges of the texture, a circle on square
+    /// meshes.
+    Circle { softness: f32 },
+    /// The whole texture with corners rounded by `radius`, in UV units.
+    RoundedRect { radius: f32, softness: f32 },
+}
+
+impl VideoMask {
+    /// A circle with an antialiased edge.
+    pub fn circle() -> Self {
+        VideoMask::Circle { softness: 0.01 }
+    }
+
+    /// A rectangle with corners rounded by `radius` and an antialiased edge.
+    pub fn rounded_rect(radius: f32) -> Self {
+        VideoMask::RoundedRect {
+            radius,
+            softness: 0.01,
+        }
+    }
+}
+
+/// Brightness, contrast, saturation and hue adjustments with the ranges of
+/// GStreamer's `videobalance`, for correcting badly exposed cameras.
+///
+/// Add it next to a [`VideoPlayer`] with a [`VideoMaterial`] and change it
+/// at any time.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct VideoBalance {
+    /// Added to the luma, from -1 to 1.
+    pub brightness: f32,
+    /// Luma multiplier, from 0 to 2.
+    pub contrast: f32,
+    /// Chroma multiplier, from 0 (gray) to 2.
+    pub saturation: f32,
+    /// Hue rotation, from -1 to 1 for -180° to 180°.
+    pub hue: f32,
+}
+
+impl Default for VideoBalance {
+    fn default() -> Self {
+        VideoBalance {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+        }
+    }
+}
+
+/// Copies changed [`VideoBalance`]s into the materials of their entities.
+fn apply_video_balance(
+    query: Query<
+        (&VideoBalance, &Handle<VideoMaterial>),
+        Or<(Changed<VideoBalance>, Changed<Handle<VideoMaterial>>)>,
+    >,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    for (balance, material) in query.iter() {
+        if let Some(material) = materials.get_mut(material) {
+            material.balance = *balance;
+        }
+    }
+}
+
+/// Settings for removing a background color from the video.
+///
+/// Colors are compared by hue and saturation only, so shadows on the screen
+/// are keyed out as well.
+#[derive(Debug, Clone, Copy)]
+pub struct ChromaKey {
+    pub color: Color,
+    /// Distance to `color` below which pixels are fully transparent.
+    pub similarity: f32,
+    /// Width of the transition from transparent to opaque above
+    /// `similarity`.
+    pub smoothness: f32,
+    /// Width of the range above `similarity` in which the key color bleeding
+    /// onto the subject is desaturated. Zero disables it.
+    pub spill: f32,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        ChromaKey::green()
+    }
+}
+
+impl ChromaKey {
+    /// Settings suited to a typical green screen.
+    pub fn green() -> Self {
+        ChromaKey {
+            color: Color::rgb(0.0, 1.0, 0.0),
+            similarity: 0.4,
+            smoothness: 0.08,
+            spill: 0.1,
+        }
+    }
+
+    /// Settings suited to a typical blue screen.
+    pub fn blue() -> Self {
+        ChromaKey {
+            color: Color::rgb(0.0, 0.0, 1.0),
+            ..ChromaKey::green()
+        }
+    }
+}
+
+const VIDEO_MATERIAL_UNLIT: u32 = 1;
+const VIDEO_MATERIAL_CHROMA_KEY: u32 = 2;
+const VIDEO_MATERIAL_MATTE: u32 = 4;
+const VIDEO_MATERIAL_LUT: u32 = 8;
+const VIDEO_MATERIAL_BALANCE: u32 = 16;
+const VIDEO_MATERIAL_MASK_TEXTURE: u32 = 32;
+const VIDEO_MATERIAL_UNDISTORT: u32 = 64;
+
+const VIDEO_TRANSITION_NONE: u32 = 0;
+const VIDEO_TRANSITION_CROSSFADE: u32 = 1;
+const VIDEO_TRANSITION_WIPE: u32 = 2;
+const VIDEO_TRANSITION_DIP_TO_BLACK: u32 = 3;
+
+const VIDEO_MASK_NONE: u32 = 0;
+const VIDEO_MASK_CIRCLE: u32 = 1;
+const VIDEO_MASK_ROUNDED_RECT: u32 = 2;
+
+/// GPU representation of the [`VideoMaterial`] settings.
+#[derive(Clone, Default, ShaderType)]
+pub struct VideoMaterialUniform {
+    pub tint: Vec4,
+    pub key_color: Vec4,
+    pub effect_params: Vec4,
+    /// Brightness, contrast, saturation and hue.
+    pub balance: Vec4,
+    /// Focal lengths and principal point in UV units.
+    pub lens_intrinsics: Vec4,
+    /// k1, k2, p1 and p2.
+    pub lens_distortion: Vec4,
+    /// Input range of the LUT, the last component is unused.
+    pub lut_domain_min: Vec4,
+    pub lut_domain_max: Vec4,
+    pub emissive: f32,
+    pub similarity: f32,
+    pub smoothness: f32,
+    pub spill: f32,
+    pub transition: u32,
+    pub transition_progress: f32,
+    pub time: f32,
+    pub flags: u32,
+    pub mask: u32,
+    pub mask_radius: f32,
+    pub mask_softness: f32,
+    pub lens_k3: f32,
+}
+
+impl AsBindGroupShaderType<VideoMaterialUniform> for VideoMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> VideoMaterialUniform {
+        let mut flags = 0;
+        if self.unlit {
+            flags |= VIDEO_MATERIAL_UNLIT;
+        }
+        let key = self.chroma_key.unwrap_or_default();
+        if self.chroma_key.is_some() {
+            flags |= VIDEO_MATERIAL_CHROMA_KEY;
+        }
+        if self.matte.is_some() {
+            flags |= VIDEO_MATERIAL_MATTE;
+        }
+        if self.lut.id != IDENTITY_LUT_HANDLE.id {
+            flags |= VIDEO_MATERIAL_LUT;
+        }
+        if self.balance != VideoBalance::default() {
+            flags |= VIDEO_MATERIAL_BALANCE;
+        }
+        if self.mask_texture.is_some() {
+            flags |= VIDEO_MATERIAL_MASK_TEXTURE;
+        }
+        let lens = self.undistort.unwrap_or_default();
+        if self.undistort.is_some() {
+            flags |= VIDEO_MATERIAL_UNDISTORT;
+        }
+        let (mask, mask_radius, mask_softness) = match self.mask {
+            Some(VideoMask::Circle { softness }) => (VIDEO_MASK_CIRCLE, 0.5, softness),
+            Some(VideoMask::RoundedRect { radius, softness }) => {
+                (VIDEO_MASK_ROUNDED_RECT, radius.clamp(0.0, 0.5), softness)
+            }
+            None => (VIDEO_MASK_NONE, 0.0, 0.0),
+        };
+        let (transition, transition_progress) = match (self.transition, &self.previous) {
+            (Some((kind, progress)), Some(_)) => {
+                let kind = match kind {
+                    TransitionKind::Crossfade => VIDEO_TRANSITION_CROSSFADE,
+                    TransitionKind::Wipe => VIDEO_TRANSITION_WIPE,
+                    TransitionKind::DipToBlack => VIDEO_TRANSITION_DIP_TO_BLACK,
+                };
+                (kind, progress)
+            }
+            _ => (VIDEO_TRANSITION_NONE, 1.0),
+        };
+        VideoMaterialUniform {
+            tint: self.tint.as_linear_rgba_f32().into(),
+            key_color: key.color.as_rgba_f32().into(),
+            effect_params: self.effect_params,
+            balance: Vec4::new(
+                self.balance.brightness,
+                self.balance.contrast,
+                self.balance.saturation,
+                self.balance.hue,
+            ),
+            lens_intrinsics: if self.undistort.is_some() {
+                lens.normalized_intrinsics()
+            } else {
+                Vec4::ONE
+            },
+            lens_distortion: Vec4::new(lens.k1, lens.k2, lens.p1, lens.p2),
+            lut_domain_min: self.lut_domain.min.extend(0.0),
+            lut_domain_max: self.lut_domain.max.extend(1.0),
+            emissive: self.emissive,
+            similarity: key.similarity,
+            smoothness: key.smoothness.max(f32::EPSILON),
+            spill: key.spill,
+            transition,
+            transition_progress,
+            time: self.time,
+            flags,
+            mask,
+            mask_radius,
+            mask_softness: mask_softness.max(f32::EPSILON),
+            lens_k3: lens.k3,
+        }
+    }
+}
+
+/// Pipeline key of [`VideoMaterial`], materials with different effects need
+/// different pipelines.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VideoMaterialKey {
+    effect: Option<Handle<Shader>>,
+}
+
+impl From<&VideoMaterial> for VideoMaterialKey {
+    fn from(material: &VideoMaterial) -> Self {
+        VideoMaterialKey {
+            effect: material.effect.clone(),
+        }
+    }
+}
+
+impl Material for VideoMaterial {
+    fn fragment_shader() -> ShaderRef {
+        VIDEO_MATERIAL_SHADER_HANDLE.typed().into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let (Some(effect), Some(fragment)) =
+            (key.bind_group_data.effect, descriptor.fragment.as_mut())
+        {
+            fragment.shader = effect;
+        }
+        Ok(())
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        if self.chroma_key.is_some()
+            || self.matte.is_some()
+            || self.mask.is_some()
+            || self.mask_texture.is_some()
+        {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        }
+    }
+}
+
+/// Texture the frames of the stream of a [`VideoPlayer`] are copied into.
+#[derive(Component, Debug, Clone)]
+pub struct VideoTexture(pub Handle<Image>);
+
+impl VideoTexture {
+    /// A texture matching the size of the stream's frames.
+    pub fn image() -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+}
+
+/// A mesh showing a stream with a [`VideoMaterial`].
+#[derive(Bundle, Clone)]
+pub struct VideoBundle {
+    pub player: VideoPlayer,
+    pub texture: VideoTexture,
+    #[bundle]
+    pub mesh: MaterialMeshBundle<VideoMaterial>,
+}
+
+impl VideoBundle {
+    /// Creates the texture and a material from `settings` for `stream`.
+    pub fn new(
+        stream: Handle<AppSinkImage>,
+        mesh: Handle<Mesh>,
+        settings: VideoMaterial,
+        images: &mut Assets<Image>,
+        materials: &mut Assets<VideoMaterial>,
+    ) -> Self {
+        let texture = images.add(VideoTexture::image());
+        let material = materials.add(VideoMaterial {
+            texture: Some(texture.clone()),
+            ..settings
+        });
+        VideoBundle {
+            player: VideoPlayer { stream },
+            texture: VideoTexture(texture),
+            mesh: MaterialMeshBundle {
+                mesh,
+                material,
+                ..default()
+            },
+        }
+    }
+}
+
+/// Builds a fragment shader for [`VideoMaterial::effect`] from `body`, the
+/// body of a function returning the color at `uv: vec2<f32>`.
+///
+/// The body can sample the frame anywhere with `video_sample(uv)` and read
+/// the material with `material`. Keying, grading and lighting are applied to
+/// its result. A pixelation effect:
+///
+/// ```text
+/// let cells = material.effect_params.xy;
+/// return video_sample((floor(uv * cells) + 0.5) / cells);
+/// ```
+///
+/// For full control, write a shader importing `itsamecube::video_material`
+/// and defining its own `fragment` entry point instead.
+pub fn effect_shader(body: &str) -> Shader {
+    Shader::from_wgsl(format!(
+        "#import itsamecube::video_material
+
+fn video_effect(uv: vec2<f32>) -> vec4<f32> {{
+{}
+}}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {{
+    return video_shade(in, video_process(video_effect(in.uv), in.uv));
+}}
+",
+        body
+    ))
+}
+
+/// Keeps `time` of materials with an effect up to date.
+fn update_effect_time(time: Res<Time>, mut materials: ResMut<Assets<VideoMaterial>>) {
+    let ids: Vec<HandleId> = materials
+        .iter()
+        .filter(|(_, material)| material.effect.is_some())
+        .map(|(id, _)| id)
+        .collect();
+    for id in ids {
+        if let Some(material) = materials.get_mut(id) {
+            material.time = time.seconds_since_startup() as f32;
+        }
+    }
+}
+
+/// A texture slot of [`StandardMaterial`] a video can be shown in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaterialSlot {
+    BaseColor,
+    Emissive,
+    /// Tangent space normals, the mesh needs tangents.
+    Normal,
+    /// Roughness in green and metallic in blue.
+    MetallicRoughness,
+    Occlusion,
+}
+
+impl MaterialSlot {
+    /// Whether the slot holds colors. Other slots hold data and need the
+    /// texture to be sampled without sRGB conversion.
+    pub fn is_color(&self) -> bool {
+        matches!(self, MaterialSlot::BaseColor | MaterialSlot::Emissive)
+    }
+
+    fn texture_mut<'a>(&self, material: &'a mut StandardMaterial) -> &'a mut Option<Handle<Image>> {
+        match self {
+            MaterialSlot::BaseColor => &mut material.base_color_texture,
+            MaterialSlot::Emissive => &mut material.emissive_texture,
+            MaterialSlot::Normal => &mut material.normal_map_texture,
+            MaterialSlot::MetallicRoughness => &mut material.metallic_roughness_texture,
+            MaterialSlot::Occlusion => &mut material.occlusion_texture,
+        }
+    }
+
+    const ALL: [MaterialSlot; 5] = [
+        MaterialSlot::BaseColor,
+        MaterialSlot::Emissive,
+        MaterialSlot::Normal,
+        MaterialSlot::MetallicRoughness,
+        MaterialSlot::Occlusion,
+    ];
+}
+
+/// The slots of the entity's [`StandardMaterial`] the [`VideoTexture`] is
+/// bound to. Slots removed from the list are cleared.
+#[derive(Component, Debug, Clone)]
+pub struct VideoMaterialSlots(pub Vec<MaterialSlot>);
+
+impl VideoMaterialSlots {
+    fn is_color(&self) -> bool {
+        self.0.is_empty() || self.0.iter().any(MaterialSlot::is_color)
+    }
+}
+
+/// A mesh showing a stream in one or more slots of a [`StandardMaterial`],
+/// e.g. as an animated emissive map.
+#[derive(Bundle, Clone)]
+pub struct VideoPbrBundle {
+    pub player: VideoPlayer,
+    pub texture: VideoTexture,
+    pub slots: VideoMaterialSlots,
+    #[bundle]
+    pub pbr: PbrBundle,
+}
+
+impl VideoPbrBundle {
+    /// Creates the texture for `stream` and binds it to `slots` of `material`.
+    pub fn new(
+        stream: Handle<AppSinkImage>,
+        mesh: Handle<Mesh>,
+        material: StandardMaterial,
+        slots: Vec<MaterialSlot>,
+        images: &mut Assets<Image>,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Self {
+        let slots = VideoMaterialSlots(slots);
+        let mut image = VideoTexture::image();
+        if !slots.is_color() {
+            image.texture_descriptor.format = TextureFormat::Rgba8Unorm;
+        }
+        let texture = images.add(image);
+        let mut material = material;
+        for slot in &slots.0 {
+            *slot.texture_mut(&mut material) = Some(texture.clone());
+        }
+        VideoPbrBundle {
+            player: VideoPlayer { stream },
+            texture: VideoTexture(texture),
+            slots,
+            pbr: PbrBundle {
+                mesh,
+                material: materials.add(material),
+                ..default()
+            },
+        }
+    }
+}
+
+/// Puts the [`VideoTexture`] into the chosen slots when they change.
+fn bind_material_slots(
+    query: Query<
+        (
+            &VideoTexture,
+            &VideoMaterialSlots,
+            &Handle<StandardMaterial>,
+        ),
+        Or<(
+            Changed<VideoMaterialSlots>,
+            Changed<VideoTexture>,
+            Changed<Handle<StandardMaterial>>,
+        )>,
+    >,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (texture, slots, handle) in query.iter() {
+        let material = match materials.get_mut(handle) {
+            Some(material) => material,
+            None => continue,
+        };
+        for slot in MaterialSlot::ALL {
+            let bound = slot.texture_mut(material);
+            if slots.0.contains(&slot) {
+                *bound = Some(texture.0.clone());
+            } else if bound.as_ref() == Some(&texture.0) {
+                *bound = None;
+            }
+        }
+        if let Some(image) = images.get_mut(&texture.0) {
+            image.texture_descriptor.format = if slots.is_color() {
+                TextureFormat::Rgba8UnormSrgb
+            } else {
+                TextureFormat::Rgba8Unorm
+            };
+        }
+    }
+}
+
+/// Copies new frames into the [`VideoTexture`] of every player. Streams shown
+/// by several players are read once.
+fn update_video_textures(
+    players: Query<(
+        &VideoPlayer,
+        &VideoTexture,
+        Option<&Handle<VideoMaterial>>,
+        Option<&Handle<StandardMaterial>>,
+    )>,
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut images: ResMut<Assets<Image>>,
+    mut video_materials: ResMut<Assets<VideoMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let mut textures: HashMap<HandleId, Vec<_>> = HashMap::new();
+    for (player, texture, video_material, standard_material) in players.iter() {
+        textures.entry(player.stream.id).or_default().push((
+            &texture.0,
+            video_material,
+            standard_material,
+        ));
+    }
+
+    for (stream, textures) in textures {
+        let appsink = match appsinks.get(stream) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+        let first = match textures.first() {
+            Some((first, _, _)) => *first,
+            None => continue,
+        };
+        let data = match images.get_mut(first) {
+            Some(image) if appsink.copy_to(image) => image.data.clone(),
+            _ => continue,
+        };
+        for (texture, _, _) in textures.iter().skip(1) {
+            if let Some(image) = images.get_mut(texture) {
+                image.data.clone_from(&data);
+            }
+        }
+        let serial = appsink.frame_serial();
+        for (texture, _, _) in &textures {
+            appsink.latch.shown(texture.id, serial);
+        }
+        // Re-uploading an image replaces its GPU texture, materials only pick
+        // up the new one when they are modified too.
+        for (_, video_material, standard_material) in &textures {
+            if let Some(material) = video_material {
+                video_materials.get_mut(material);
+            }
+            if let Some(material) = standard_material {
+                standard_materials.get_mut(material);
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Fraction of the difference with the pipeline corrected every frame.
+const SMOOTHING: f64 = 0.05;
+
+/// Difference with the pipeline, in seconds, past which the clock jumps
+/// instead of easing, such as after a seek.
+const MAX_DRIFT: f64 = 0.25;
+
+/// Maps the position of a stream into Bevy's time, so gameplay can be keyed
+/// to moments of the video.
+///
+/// Sampling the pipeline once per frame gives a position that stutters with
+/// the frame times. Instead the offset between the pipeline position and
+/// `Time::time_since_startup` is tracked, and slowly corrected as the two
+/// clocks drift apart.
+#[derive(Debug, Default)]
+pub struct MediaClock {
+    /// Position minus Bevy time, in seconds.
+    offset: Option<f64>,
+    /// Position while paused.
+    paused_at: Option<f64>,
+    /// Media time at the previous and the last update.
+    previous: Option<f64>,
+    current: Option<f64>,
+}
+
+impl MediaClock {
+    fn update(&mut self, now: f64, position: Option<f64>, paused: bool) {
+        let position = match position {
+            Some(position) => position,
+            None => {
+                *self = MediaClock::default();
+                return;
+            }
+        };
+        let measured = position - now;
+        self.offset = match self.offset {
+            Some(offset) if !paused && (measured - offset).abs() < MAX_DRIFT => {
+                Some(offset + (measured - offset) * SMOOTHING)
+            }
+            _ => Some(measured),
+        };
+        self.paused_at = paused.then(|| position);
+        self.previous = self.current;
+        self.current = self.at(now);
+    }
+
+    fn at(&self, now: f64) -> Option<f64> {
+        let time = self.paused_at.or_else(|| Some(now + self.offset?))?;
+        Some(time.max(0.0))
+    }
+
+    /// Whether the media time went past `at` between the last two updates.
+    fn crossed(&self, at: f64) -> bool {
+        match (self.previous, self.current) {
+            (Some(previous), Some(current)) => previous < at && at <= current,
+            _ => false,
+        }
+    }
+}
+
+impl AppSinkImage {
+    /// Position of the stream at the current Bevy time, smooth from frame to
+    /// frame. `None` while the pipeline doesn't know its position.
+    pub fn media_time(&self, time: &Time) -> Option<Duration> {
+        self.media_clock
+            .lock()
+            .unwrap()
+            .at(time.seconds_since_startup())
+            .map(Duration::from_secs_f64)
+    }
+
+    /// Whether the stream passed `at` during the last frame, to trigger
+    /// events at a moment of the video. Seeking back and playing over `at`
+    /// again triggers it again.
+    pub fn media_time_crossed(&self, at: Duration) -> bool {
+        self.media_clock.lock().unwrap().crossed(at.as_secs_f64())
+    }
+}
+
+/// Samples the position of every stream into its [`MediaClock`].
+pub(crate) fn update_media_clocks(time: Res<Time>, appsinks: Res<Assets<AppSinkImage>>) {
+    let now = time.seconds_since_startup();
+    for (_, appsink) in appsinks.iter() {
+        let position = appsink.position().map(|position| position.as_secs_f64());
+        appsink
+            .media_clock
+            .lock()
+            .unwrap()
+            .update(now, position, appsink.is_paused());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("no media time");
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn eases_small_drift() {
+        let mut clock = MediaClock::default();
+        clock.update(0.0, Some(10.0), false);
+        assert_near(clock.at(0.0), 10.0);
+
+        // The pipeline is 0.1s ahead, only a fraction of it is corrected.
+        clock.update(1.0, Some(11.1), false);
+        assert_near(clock.at(1.0), 11.0 + 0.1 * SMOOTHING);
+        assert_near(clock.at(2.0), 12.0 + 0.1 * SMOOTHING);
+    }
+
+    #[test]
+    fn jumps_after_a_seek() {
+        let mut clock = MediaClock::default();
+        clock.update(0.0, Some(10.0), false);
+        clock.update(1.0, Some(11.0 + MAX_DRIFT), false);
+        assert_near(clock.at(1.0), 11.0 + MAX_DRIFT);
+
+        clock.update(2.0, Some(30.0), false);
+        assert_near(clock.at(2.0), 30.0);
+        clock.update(3.0, Some(5.0), false);
+        assert_near(clock.at(3.0), 5.0);
+    }
+
+    #[test]
+    fn holds_while_paused() {
+        let mut clock = MediaClock::default();
+        clock.update(0.0, Some(10.0), false);
+        clock.update(1.0, Some(10.5), true);
+        assert_near(clock.at(1.0), 10.5);
+        assert_near(clock.at(5.0), 10.5);
+
+        // Playing again resumes from the paused position.
+        clock.update(6.0, Some(10.5), false);
+        assert_near(clock.at(6.0), 10.5);
+        assert_near(clock.at(7.0), 11.5);
+    }
+
+    #[test]
+    fn resets_without_position() {
+        let mut clock = MediaClock::default();
+        assert_eq!(clock.at(0.0), None);
+        clock.update(0.0, Some(10.0), false);
+        clock.update(1.0, None, false);
+        assert_eq!(clock.at(1.0), None);
+        assert!(!clock.crossed(10.5));
+    }
+
+    #[test]
+    fn never_goes_negative() {
+        let mut clock = MediaClock::default();
+        clock.update(5.0, Some(0.0), false);
+        assert_near(clock.at(4.0), 0.0);
+    }
+
+    #[test]
+    fn crosses_once() {
+        let mut clock = MediaClock::default();
+        clock.update(0.0, Some(10.0), false);
+        assert!(!clock.crossed(10.0));
+
+        clock.update(1.0, Some(11.0), false);
+        assert!(clock.crossed(10.5));
+        assert!(clock.crossed(11.0));
+        assert!(!clock.crossed(10.0));
+
+        clock.update(2.0, Some(12.0), false);
+        assert!(!clock.crossed(10.5));
+        assert!(clock.crossed(11.5));
+    }
+
+    #[test]
+    fn crosses_again_after_seeking_back() {
+        let mut clock = MediaClock::default();
+        clock.update(0.0, Some(10.0), false);
+        clock.update(1.0, Some(11.0), false);
+        assert!(clock.crossed(10.5));
+
+        clock.update(2.0, Some(10.0), false);
+        assert!(!clock.crossed(10.5));
+        clock.update(3.0, Some(11.0), false);
+        assert!(clock.crossed(10.5));
+    }
+}
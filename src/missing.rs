@@ -0,0 +1,135 @@
+use std::fmt;
+
+/// Elements this crate may instantiate, with the plugin that provides them and
+/// the upstream module that ships that plugin.
+const KNOWN_ELEMENTS: &[(&str, &str, &str)] = &[
+    ("capsfilter", "coreelements", "gstreamer"),
+    ("fakesink", "coreelements", "gstreamer"),
+    ("filesink", "coreelements", "gstreamer"),
+    ("queue", "coreelements", "gstreamer"),
+    ("tee", "coreelements", "gstreamer"),
+    ("valve", "coreelements", "gstreamer"),
+    ("appsink", "app", "gst-plugins-base"),
+    ("appsrc", "app", "gst-plugins-base"),
+    ("videotestsrc", "videotestsrc", "gst-plugins-base"),
+    ("videoconvert", "videoconvert", "gst-plugins-base"),
+    ("videoscale", "videoscale", "gst-plugins-base"),
+    ("uridecodebin", "playback", "gst-plugins-base"),
+    ("decodebin", "playback", "gst-plugins-base"),
+    ("audioconvert", "audioconvert", "gst-plugins-base"),
+    ("audioresample", "audioresample", "gst-plugins-base"),
+    ("compositor", "compositor", "gst-plugins-base"),
+    ("textoverlay", "pango", "gst-plugins-base"),
+    ("timeoverlay", "pango", "gst-plugins-base"),
+    ("clockoverlay", "pango", "gst-plugins-base"),
+    ("opusenc", "opus", "gst-plugins-base"),
+    ("v4l2src", "video4linux2", "gst-plugins-good"),
+    ("jpegdec", "jpeg", "gst-plugins-good"),
+    ("jpegenc", "jpeg", "gst-plugins-good"),
+    ("level", "level", "gst-plugins-good"),
+    ("spectrum", "spectrum", "gst-plugins-good"),
+    ("autoaudiosrc", "autodetect", "gst-plugins-good"),
+    ("pulsesrc", "pulseaudio", "gst-plugins-good"),
+    ("vp8enc", "vpx", "gst-plugins-good"),
+    ("vp9enc", "vpx", "gst-plugins-good"),
+    ("aacparse", "audioparsers", "gst-plugins-good"),
+    ("mp4mux", "isomp4", "gst-plugins-good"),
+    ("matroskamux", "matroska", "gst-plugins-good"),
+    ("webmmux", "matroska", "gst-plugins-good"),
+    ("flvmux", "flv", "gst-plugins-good"),
+    ("splitmuxsink", "multifile", "gst-plugins-good"),
+    ("rtph264pay", "rtp", "gst-plugins-good"),
+    ("rtpvp8pay", "rtp", "gst-plugins-good"),
+    ("rtpvp9pay", "rtp", "gst-plugins-good"),
+    ("rtpopuspay", "rtp", "gst-plugins-good"),
+    ("h264parse", "videoparsersbad", "gst-plugins-bad"),
+    ("mpegtsmux", "mpegtsmux", "gst-plugins-bad"),
+    ("rtmpsink", "rtmp", "gst-plugins-bad"),
+    ("srtsink", "srt", "gst-plugins-bad"),
+    ("webrtcbin", "webrtc", "gst-plugins-bad"),
+    ("nvh264enc", "nvcodec", "gst-plugins-bad"),
+    ("x264enc", "x264", "gst-plugins-ugly"),
+    ("avenc_aac", "libav", "gst-libav"),
+    ("vaapih264enc", "vaapi", "gstreamer-vaapi"),
+];
+
+/// Details about an element that could not be created, with enough
+/// information to tell the user what to install.
+#[derive(Debug, Clone)]
+pub struct MissingPlugin {
+    /// Factory name of the element that was requested.
+    pub element: String,
+    /// Name of the GStreamer plugin that provides the element, if known.
+    pub plugin: Option<&'static str>,
+    /// Upstream module (distribution package family) that ships the plugin.
+    pub package: Option<&'static str>,
+    /// Whether the plugin is in the registry even though the element is not,
+    /// which usually means it failed to load and was blacklisted.
+    pub blacklisted: bool,
+    /// Detail string suitable for `gst_install_plugins_async`.
+    pub installer_detail: String,
+}
+
+impl MissingPlugin {
+    /// Looks up why `element` could not be created.
+    ///
+    /// GStreamer must already be initialized.
+    pub fn for_element(element: &str) -> MissingPlugin {
+        let known = KNOWN_ELEMENTS.iter().find(|(name, _, _)| *name == element);
+        let plugin = known.map(|(_, plugin, _)| *plugin);
+        let blacklisted = plugin
+            .and_then(|plugin| gst::Registry::get().find_plugin(plugin))
+            .is_some();
+
+        MissingPlugin {
+            element: element.to_string(),
+            plugin,
+            package: known.map(|(_, _, package)| *package),
+            blacklisted,
+            installer_detail: gst_pbutils::missing_element_installer_detail_new(element)
+                .to_string(),
+        }
+    }
+
+    /// Extracts the missing-plugin details that elements such as decodebin
+    /// post on the bus when they cannot find a decoder.
+    pub fn from_message(msg: &gst::Message) -> Option<MissingPlugin> {
+        if !gst_pbutils::is_missing_plugin_message(msg) {
+            return None;
+        }
+
+        Some(MissingPlugin {
+            element: gst_pbutils::missing_plugin_message_get_description(msg).to_string(),
+            plugin: None,
+            package: None,
+            blacklisted: false,
+            installer_detail: gst_pbutils::missing_plugin_message_get_installer_detail(msg)
+                .to_string(),
+        })
+    }
+
+    /// Human readable advice on how to fix the problem.
+    pub fn hint(&self) -> String {
+        match (self.plugin, self.package) {
+            (Some(plugin), _) if self.blacklisted => format!(
+                "element `{}` is missing although plugin `{}` is installed; it probably failed \
+                 to load, run `gst-inspect-1.0 {}` to see why",
+                self.element, plugin, plugin
+            ),
+            (Some(plugin), Some(package)) => format!(
+                "element `{}` is missing: install the `{}` plugin from {}",
+                self.element, plugin, package
+            ),
+            _ => format!(
+                "element `{}` is missing (installer detail: {})",
+                self.element, self.installer_detail
+            ),
+        }
+    }
+}
+
+impl fmt::Display for MissingPlugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.hint())
+    }
+}
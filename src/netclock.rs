@@ -0,0 +1,289 @@
+use std::fmt;
+use std::time::Duration;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use gst::prelude::*;
+
+use crate::appsink::{handle_failure, AppSinkImage, StreamEvents};
+use crate::error::VideoError;
+
+/// Port used when an address is given without one.
+pub const DEFAULT_CLOCK_PORT: u16 = 5637;
+
+/// How long to wait for a remote clock to synchronize before starting.
+const SYNC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Latency every synchronized pipeline renders with, long enough to absorb
+/// the network and the decoding, so all machines present frames at the same
+/// clock time.
+const NET_LATENCY: Duration = Duration::from_millis(500);
+
+/// Which side of the clock sharing a stream is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetClockRole {
+    /// Publishes the system clock on `address` for the other machines.
+    Server,
+    /// Follows the clock published at `address`.
+    Client,
+}
+
+/// Sharing of the pipeline clock between machines, set with the
+/// `clock_server` or `clock_client` keys of the `.sinkimage` file.
+///
+/// Every machine playing the same stream with the same clock and
+/// `base_time` presents the same frame at the same time, for multi-screen
+/// installations. Without `base_time`, pipelines start at the next multiple
+/// of `start_grid` on the shared clock, so machines started within the same
+/// window line up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetClockConfig {
+    pub role: NetClockRole,
+    pub address: String,
+    pub port: u16,
+    /// Shared clock time the streams start from.
+    pub base_time: Option<Duration>,
+    pub start_grid: Duration,
+}
+
+impl NetClockConfig {
+    pub fn new(role: NetClockRole, address: impl Into<String>, port: u16) -> Self {
+        NetClockConfig {
+            role,
+            address: address.into(),
+            port,
+            base_time: None,
+            start_grid: Duration::from_secs(5),
+        }
+    }
+
+    /// Parses `<address>[:<port>]`. IPv6 addresses go in brackets, as in
+    /// `[fe80::1]:5637`, since their colons can't be told from the port's.
+    pub(crate) fn parse(role: NetClockRole, value: &str) -> Option<NetClockConfig> {
+        let (address, rest) = match value.strip_prefix('[') {
+            Some(bracketed) => {
+                let (address, rest) = bracketed.split_once(']')?;
+                if !address.contains(':') {
+                    return None;
+                }
+                (address, rest)
+            }
+            None => match value.find(':') {
+                Some(colon) => value.split_at(colon),
+                None => (value, ""),
+            },
+        };
+        let port = rest_port(rest)?.unwrap_or(DEFAULT_CLOCK_PORT);
+        if address.is_empty() {
+            return None;
+        }
+        Some(NetClockConfig::new(role, address, port))
+    }
+}
+
+/// Parses what follows the address: nothing, or `:<port>`.
+fn rest_port(rest: &str) -> Option<Option<u16>> {
+    if rest.is_empty() {
+        return Some(None);
+    }
+    rest.strip_prefix(':')?.parse().ok().map(Some)
+}
+
+/// The clock shared by a stream, kept alive as long as the stream.
+#[derive(Debug)]
+pub struct NetClock {
+    pub config: NetClockConfig,
+    clock: gst::Clock,
+    /// Answers the clients while serving.
+    _provider: Option<gst_net::NetTimeProvider>,
+}
+
+impl NetClock {
+    /// Starts serving the clock or connects to the server, waiting for the
+    /// remote clock to synchronize. Blocks for up to a few seconds.
+    pub fn connect(config: NetClockConfig) -> Result<NetClock, VideoError> {
+        gst::init().map_err(VideoError::Init)?;
+        let (clock, provider) = match config.role {
+            NetClockRole::Server => {
+                let clock = gst::SystemClock::obtain();
+                let provider = gst_net::NetTimeProvider::new(
+                    &clock,
+                    Some(&config.address),
+                    config.port as i32,
+                );
+                (clock, Some(provider))
+            }
+            NetClockRole::Client => {
+                let clock = gst_net::NetClientClock::new(
+                    None,
+                    &config.address,
+                    config.port as i32,
+                    gst::ClockTime::ZERO,
+                );
+                let timeout = gst::ClockTime::from_nseconds(SYNC_TIMEOUT.as_nanos() as u64);
+                if clock.wait_for_sync(Some(timeout)).is_err() {
+                    return Err(VideoError::ClockSync(format!(
+                        "{}:{}",
+                        config.address, config.port
+                    )));
+                }
+                (clock.upcast(), None)
+            }
+        };
+        Ok(NetClock {
+            config,
+            clock,
+            _provider: provider,
+        })
+    }
+
+    /// Makes `pipeline` run on the shared clock from the shared base time.
+    pub(crate) fn apply(&self, pipeline: &gst::Pipeline) {
+        pipeline.use_clock(Some(&self.clock));
+        pipeline.set_start_time(gst::ClockTime::NONE);
+        pipeline.set_base_time(self.base_time());
+        pipeline.set_latency(Some(gst::ClockTime::from_nseconds(
+            NET_LATENCY.as_nanos() as u64
+        )));
+    }
+
+    fn base_time(&self) -> gst::ClockTime {
+        if let Some(base_time) = self.config.base_time {
+            return gst::ClockTime::from_nseconds(base_time.as_nanos() as u64);
+        }
+        let now = self.clock.time().map_or(0, |now| now.nseconds());
+        let grid = (self.config.start_grid.as_nanos() as u64).max(1);
+        gst::ClockTime::from_nseconds((now / grid + 1) * grid)
+    }
+
+    /// Time of the shared clock.
+    pub fn time(&self) -> Option<Duration> {
+        self.clock
+            .time()
+            .map(|time| Duration::from_nanos(time.nseconds()))
+    }
+}
+
+/// Connection to a shared clock running on the task pool.
+pub struct PendingNetClock {
+    task: Task<Result<NetClock, VideoError>>,
+}
+
+impl fmt::Debug for PendingNetClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingNetClock").finish()
+    }
+}
+
+impl AppSinkImage {
+    /// Starts connecting to the shared clock in the background if one is
+    /// configured and not connected yet. Returns whether starting the
+    /// pipeline has to wait for it.
+    pub(crate) fn start_net_clock(&mut self) -> bool {
+        let config = match (&self.net_clock_config, &self.net_clock) {
+            (Some(config), None) => config.clone(),
+            _ => return false,
+        };
+        let task = AsyncComputeTaskPool::get().spawn(async move { NetClock::connect(config) });
+        self.pending_net_clock = Some(PendingNetClock { task });
+        true
+    }
+}
+
+/// Starts the streams whose clock is synchronized, or fails them if it
+/// could not be.
+pub(crate) fn finish_net_clocks(
+    time: Res<Time>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut events: StreamEvents,
+) {
+    let ids: Vec<HandleId> = appsinks
+        .iter()
+        .filter(|(_, appsink)| appsink.pending_net_clock.is_some())
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in ids {
+        let handle = Handle::weak(id);
+        let appsink = match appsinks.get_mut(&handle) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+        let pending = appsink.pending_net_clock.as_mut().unwrap();
+        let result = match future::block_on(future::poll_once(&mut pending.task)) {
+            Some(result) => result,
+            None => continue,
+        };
+        appsink.pending_net_clock = None;
+
+        let started = result.and_then(|net_clock| {
+            appsink.net_clock = Some(net_clock);
+            if appsink.is_preparing() {
+                return Ok(());
+            }
+            appsink.start()
+        });
+        if let Err(err) = started {
+            appsink.cancel_preparation();
+            handle_failure(
+                appsink,
+                &handle,
+                err,
+                time.time_since_startup(),
+                &mut events,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_addresses() {
+        let cases = [
+            ("192.168.1.10:6000", Some(("192.168.1.10", 6000))),
+            ("192.168.1.10", Some(("192.168.1.10", DEFAULT_CLOCK_PORT))),
+            ("clock.local:1", Some(("clock.local", 1))),
+            ("0.0.0.0:65535", Some(("0.0.0.0", 65535))),
+            ("[::1]:5637", Some(("::1", 5637))),
+            ("[fe80::1]", Some(("fe80::1", DEFAULT_CLOCK_PORT))),
+            ("[fe80::1]:6000", Some(("fe80::1", 6000))),
+            // Bare IPv6 addresses are ambiguous with the port.
+            ("fe80::1", None),
+            ("::1:5637", None),
+            ("[fe80::1", None),
+            ("[fe80::1]6000", None),
+            ("[fe80::1]:", None),
+            ("[]:5637", None),
+            ("[clock.local]:5637", None),
+            ("", None),
+            (":5637", None),
+            ("host:", None),
+            ("host:port", None),
+            ("host:65536", None),
+            ("host:-1", None),
+        ];
+        for (value, expected) in cases {
+            let expected = expected
+                .map(|(address, port)| NetClockConfig::new(NetClockRole::Client, address, port));
+            assert_eq!(
+                NetClockConfig::parse(NetClockRole::Client, value),
+                expected,
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_role_and_defaults() {
+        let config = NetClockConfig::parse(NetClockRole::Server, "0.0.0.0").unwrap();
+        assert_eq!(config.role, NetClockRole::Server);
+        assert_eq!(config.base_time, None);
+        assert_eq!(config.start_grid, Duration::from_secs(5));
+    }
+}
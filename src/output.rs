@@ -0,0 +1,816 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{
+    BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+    ImageDataLayout, MapMode, TextureFormat,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::{RenderApp, RenderStage};
+use gst::prelude::*;
+
+use crate::appsink::make_element;
+use crate::error::VideoError;
+use crate::webrtc::{Signaller, WebRtcSession};
+
+/// Pushes the contents of render target images through GStreamer encoding
+/// pipelines, the inverse of the `.sinkimage` input path.
+///
+/// Every frame, the image of each active output is copied back from the GPU
+/// and handed to an `appsrc`. The readback waits for the GPU, so expect some
+/// frame time to go into it.
+pub struct VideoOutputPlugin;
+
+impl Plugin for VideoOutputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VideoOutputs>()
+            .init_resource::<CameraOutputIds>()
+            .add_event::<VideoSegmentFinished>()
+            .add_event::<VideoOutputError>()
+            .add_plugin(ExtractResourcePlugin::<VideoOutputs>::default())
+            .add_system(start_camera_outputs)
+            .add_system_to_stage(CoreStage::PostUpdate, stop_camera_outputs)
+            .add_system(poll_output_buses);
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.add_system_to_stage(RenderStage::Cleanup, read_back_outputs);
+        }
+    }
+}
+
+/// Identifies an output started with [`VideoOutputs::start`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputId(u32);
+
+/// Encoder settings shared by all output sinks.
+#[derive(Debug, Clone)]
+pub struct EncoderConfig {
+    /// Encoder element, such as `x264enc` or `vp8enc`.
+    pub element: String,
+    pub bitrate_kbps: u32,
+    /// Nominal framerate announced to the encoder.
+    pub framerate: u32,
+    /// Audio to mux with the video, if any.
+    pub audio: Option<AudioSource>,
+    pub audio_bitrate_kbps: u32,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        EncoderConfig {
+            element: String::from("x264enc"),
+            bitrate_kbps: 4000,
+            framerate: 30,
+            audio: None,
+            audio_bitrate_kbps: 128,
+        }
+    }
+}
+
+/// Where the audio of an output comes from.
+///
+/// Audio sources are live and provide the pipeline clock, which the `appsrc`
+/// timestamps video frames against, so both stay in sync.
+#[derive(Debug, Clone)]
+pub enum AudioSource {
+    /// The system's default microphone.
+    Microphone,
+    /// A PulseAudio or PipeWire source by name. The `.monitor` source of an
+    /// output device captures what the application plays.
+    Device(String),
+}
+
+impl AudioSource {
+    /// The source element, with the device set as a property so names with
+    /// quotes or spaces reach PulseAudio unchanged.
+    fn element(&self) -> Result<gst::Element, VideoError> {
+        match self {
+            AudioSource::Microphone => make_element("autoaudiosrc"),
+            AudioSource::Device(device) => {
+                let src = make_element("pulsesrc")?;
+                src.set_property("device", device);
+                Ok(src)
+            }
+        }
+    }
+}
+
+impl EncoderConfig {
+    /// Settings for live streaming at `bitrate_kbps`.
+    pub fn streaming(bitrate_kbps: u32) -> Self {
+        EncoderConfig {
+            bitrate_kbps,
+            ..default()
+        }
+    }
+
+    /// Settings for WebRTC, using VP8 which every browser can decode.
+    pub fn webrtc(bitrate_kbps: u32) -> Self {
+        EncoderConfig {
+            element: String::from("vp8enc"),
+            bitrate_kbps,
+            ..default()
+        }
+    }
+
+    fn is_h264(&self) -> bool {
+        self.element.contains("264")
+    }
+
+    /// RTP payloader for the encoder's output, if there is one.
+    fn payloader(&self) -> Option<&'static str> {
+        match self.element.as_str() {
+            "vp8enc" => Some("rtpvp8pay"),
+            "vp9enc" => Some("rtpvp9pay"),
+            _ if self.is_h264() => Some("rtph264pay config-interval=-1"),
+            _ => None,
+        }
+    }
+
+    /// Launch description of the encoder, tuned for low latency where the
+    /// element is known.
+    pub fn description(&self) -> String {
+        match self.element.as_str() {
+            "x264enc" => format!(
+                "x264enc tune=zerolatency speed-preset=veryfast bitrate={} key-int-max={}",
+                self.bitrate_kbps,
+                self.framerate * 2
+            ),
+            "vp8enc" | "vp9enc" => format!(
+                "{} deadline=1 target-bitrate={}",
+                self.element,
+                self.bitrate_kbps * 1000
+            ),
+            "nvh264enc" | "vaapih264enc" => {
+                format!("{} bitrate={}", self.element, self.bitrate_kbps)
+            }
+            element => element.to_string(),
+        }
+    }
+}
+
+/// Where encoded frames end up.
+#[derive(Debug, Clone)]
+pub enum OutputSink {
+    /// A video file, muxed with `muxer`.
+    File { path: PathBuf, muxer: String },
+    /// Consecutive files named after `pattern`, which contains a `%05d`
+    /// style placeholder for the segment number. A new file is started when
+    /// the current one reaches `max_duration` or `max_bytes`.
+    Segments {
+        pattern: String,
+        muxer: String,
+        max_duration: Option<Duration>,
+        max_bytes: Option<u64>,
+    },
+    /// An RTMP ingest point such as `rtmp://live.twitch.tv/app/<key>`.
+    /// Requires an H.264 encoder.
+    Rtmp { location: String },
+    /// An SRT endpoint such as `srt://host:port`, carrying MPEG-TS.
+    Srt { uri: String, latency_ms: u32 },
+    /// A browser or other WebRTC peer, negotiated through `signaller`.
+    WebRtc {
+        signaller: Arc<dyn Signaller>,
+        stun_server: Option<String>,
+    },
+}
+
+impl OutputSink {
+    /// Records to `path`, picking the muxer from the file extension.
+    pub fn file(path: impl AsRef<Path>) -> OutputSink {
+        let path = path.as_ref().to_path_buf();
+        let muxer = match path.extension().and_then(|e| e.to_str()) {
+            Some("mkv") => "matroskamux",
+            Some("webm") => "webmmux",
+            Some("ts") => "mpegtsmux",
+            _ => "mp4mux",
+        };
+        OutputSink::File {
+            path,
+            muxer: String::from(muxer),
+        }
+    }
+
+    /// Records to files of at most `max_duration` each, see
+    /// [`OutputSink::Segments`].
+    pub fn segments(pattern: impl Into<String>, max_duration: Duration) -> OutputSink {
+        let pattern = pattern.into();
+        let muxer = match OutputSink::file(&pattern) {
+            OutputSink::File { muxer, .. } => muxer,
+            _ => unreachable!(),
+        };
+        OutputSink::Segments {
+            pattern,
+            muxer,
+            max_duration: Some(max_duration),
+            max_bytes: None,
+        }
+    }
+
+    pub fn rtmp(location: impl Into<String>) -> OutputSink {
+        OutputSink::Rtmp {
+            location: location.into(),
+        }
+    }
+
+    pub fn srt(uri: impl Into<String>) -> OutputSink {
+        OutputSink::Srt {
+            uri: uri.into(),
+            latency_ms: 125,
+        }
+    }
+
+    pub fn webrtc(signaller: Arc<dyn Signaller>) -> OutputSink {
+        OutputSink::WebRtc {
+            signaller,
+            stun_server: Some(String::from("stun://stun.l.google.com:19302")),
+        }
+    }
+
+    /// Whether frames are sent over the network rather than to disk.
+    pub fn is_live(&self) -> bool {
+        matches!(
+            self,
+            OutputSink::Rtmp { .. } | OutputSink::Srt { .. } | OutputSink::WebRtc { .. }
+        )
+    }
+
+    /// Checks that the sink can carry what `encoder` produces.
+    fn check(&self, encoder: &EncoderConfig) -> Result<(), VideoError> {
+        match self {
+            OutputSink::Rtmp { .. } if !encoder.is_h264() => Err(VideoError::Unsupported(format!(
+                "RTMP requires an H.264 encoder, not {}",
+                encoder.element
+            ))),
+            OutputSink::Segments { .. } if !encoder.is_h264() => {
+                Err(VideoError::Unsupported(format!(
+                    "segmented recording requires an H.264 encoder, not {}",
+                    encoder.element
+                )))
+            }
+            OutputSink::WebRtc { .. } if encoder.payloader().is_none() => Err(
+                VideoError::Unsupported(format!("no RTP payloader for {}", encoder.element)),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Audio encoder the muxer or protocol of the sink accepts.
+    fn audio_encoder(&self, bitrate_kbps: u32) -> String {
+        let bitrate = bitrate_kbps * 1000;
+        match self {
+            OutputSink::File { muxer, .. } | OutputSink::Segments { muxer, .. }
+                if muxer == "webmmux" || muxer == "matroskamux" =>
+            {
+                format!("opusenc bitrate={}", bitrate)
+            }
+            OutputSink::WebRtc { .. } => format!("opusenc bitrate={} ! rtpopuspay", bitrate),
+            _ => format!("avenc_aac bitrate={} ! aacparse", bitrate),
+        }
+    }
+
+    /// Name of the element the audio branch links to.
+    fn audio_target(&self) -> &'static str {
+        match self {
+            OutputSink::WebRtc { .. } => "webrtc",
+            _ => "mux",
+        }
+    }
+
+    fn description(&self, encoder: &EncoderConfig) -> String {
+        match self {
+            OutputSink::File { muxer, .. } => format!("{} name=mux ! filesink name=sink", muxer),
+            OutputSink::Segments {
+                muxer,
+                max_duration,
+                max_bytes,
+                ..
+            } => format!(
+                "h264parse ! splitmuxsink name=mux muxer-factory={} max-size-time={} \
+                 max-size-bytes={}",
+                muxer,
+                max_duration.map_or(0, |duration| duration.as_nanos() as u64),
+                max_bytes.unwrap_or(0)
+            ),
+            OutputSink::Rtmp { .. } => {
+                String::from("h264parse ! flvmux name=mux streamable=true ! rtmpsink name=sink")
+            }
+            OutputSink::Srt { latency_ms, .. } => format!(
+                "mpegtsmux name=mux alignment=7 ! srtsink name=sink latency={} \
+                 wait-for-connection=false",
+                latency_ms
+            ),
+            OutputSink::WebRtc { stun_server, .. } => format!(
+                "{} ! webrtcbin name=webrtc bundle-policy=max-bundle{}",
+                encoder.payloader().unwrap_or_default(),
+                stun_server
+                    .as_ref()
+                    .map(|server| format!(" stun-server={}", server))
+                    .unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Sets the properties left out of [`OutputSink::description`], paths
+    /// and addresses that would need quoting in a launch description.
+    fn configure(&self, pipeline: &gst::Pipeline) {
+        let element = |name| {
+            pipeline
+                .by_name(name)
+                .expect("Output element missing from its description. Shouldn't happen!")
+        };
+        match self {
+            OutputSink::File { path, .. } => {
+                element("sink").set_property("location", path.to_string_lossy().as_ref())
+            }
+            OutputSink::Segments { pattern, .. } => {
+                element("mux").set_property("location", pattern)
+            }
+            OutputSink::Rtmp { location } => {
+                element("sink").set_property("location", format!("{} live=1", location))
+            }
+            OutputSink::Srt { uri, .. } => element("sink").set_property("uri", uri),
+            _ => {}
+        }
+    }
+}
+
+/// A running output pipeline fed from `image`.
+#[derive(Debug, Clone)]
+pub struct VideoOutput {
+    pub id: OutputId,
+    /// Render target the frames are read from. It needs `COPY_SRC` usage.
+    pub image: Handle<Image>,
+    pub width: u32,
+    pub height: u32,
+    pipeline: gst::Pipeline,
+    appsrc: gst_app::AppSrc,
+    webrtc: Option<WebRtcSession>,
+    paused: bool,
+}
+
+impl VideoOutput {
+    fn new(
+        id: OutputId,
+        image: Handle<Image>,
+        size: Extent3d,
+        format: TextureFormat,
+        encoder: &EncoderConfig,
+        sink: &OutputSink,
+    ) -> Result<VideoOutput, VideoError> {
+        gst::init().map_err(VideoError::Init)?;
+        sink.check(encoder)?;
+
+        let caps_format = match format {
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => "BGRA",
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => "RGBA",
+            other => {
+                return Err(VideoError::Unsupported(format!(
+                    "cannot encode {:?} textures",
+                    other
+                )))
+            }
+        };
+
+        // Live sinks drop old frames instead of stalling the render loop when
+        // the network cannot keep up.
+        let queue = if sink.is_live() {
+            "queue leaky=downstream max-size-buffers=2 ! "
+        } else {
+            ""
+        };
+        let mut description = format!(
+            "appsrc name=src ! {}videoconvert ! {} ! {}",
+            queue,
+            encoder.description(),
+            sink.description(encoder)
+        );
+        // The audio source is linked to the branch once the pipeline is built.
+        if encoder.audio.is_some() {
+            description.push_str(&format!(
+                " queue name=audioqueue ! audioconvert ! audioresample ! {} ! queue ! {}.",
+                sink.audio_encoder(encoder.audio_bitrate_kbps),
+                sink.audio_target()
+            ));
+        }
+        let pipeline = gst::parse_launch(&description)
+            .map_err(|err| VideoError::Unsupported(format!("{}: {}", description, err)))?
+            .downcast::<gst::Pipeline>()
+            .expect("Launch description without pipeline. Shouldn't happen!");
+        let appsrc = pipeline
+            .by_name("src")
+            .and_then(|src| src.downcast::<gst_app::AppSrc>().ok())
+            .expect("Source element is expected to be an appsrc!");
+        sink.configure(&pipeline);
+        if let Some(audio) = &encoder.audio {
+            let src = audio.element()?;
+            pipeline.add(&src)?;
+            let queue = pipeline
+                .by_name("audioqueue")
+                .expect("Audio queue missing from its description. Shouldn't happen!");
+            src.link(&queue)?;
+        }
+
+        appsrc.set_caps(Some(
+            &gst::Caps::builder("video/x-raw")
+                .field("format", caps_format)
+                .field("width", size.width as i32)
+                .field("height", size.height as i32)
+                .field("framerate", gst::Fraction::new(encoder.framerate as i32, 1))
+                .build(),
+        ));
+        appsrc.set_property("format", gst::Format::Time);
+        appsrc.set_property("is-live", true);
+        appsrc.set_property("do-timestamp", true);
+
+        let webrtc = match sink {
+            OutputSink::WebRtc { signaller, .. } => Some(WebRtcSession::attach(
+                &pipeline,
+                "webrtc",
+                signaller.clone(),
+            )?),
+            _ => None,
+        };
+
+        pipeline.set_state(gst::State::Playing)?;
+
+        Ok(VideoOutput {
+            id,
+            image,
+            width: size.width,
+            height: size.height,
+            pipeline,
+            appsrc,
+            webrtc,
+            paused: false,
+        })
+    }
+
+    /// Whether frames are held back, see [`VideoOutputs::pause_recording`].
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn push_frame(&self, data: Vec<u8>) {
+        if let Err(err) = self.appsrc.push_buffer(gst::Buffer::from_mut_slice(data)) {
+            warn!("Output {:?} refused a frame: {:?}", self.id, err);
+        }
+    }
+
+    /// Sends end-of-stream so muxers can finalize the file, then shuts the
+    /// pipeline down.
+    fn finish(&self) {
+        let _ = self.appsrc.end_of_stream();
+        if let Some(bus) = self.pipeline.bus() {
+            bus.timed_pop_filtered(
+                gst::ClockTime::from_seconds(5),
+                &[gst::MessageType::Eos, gst::MessageType::Error],
+            );
+        }
+        let _ = self.pipeline.set_state(gst::State::Null);
+    }
+}
+
+/// All running outputs. Cloned into the render world every frame.
+#[derive(Debug, Clone, Default)]
+pub struct VideoOutputs {
+    outputs: Vec<VideoOutput>,
+    next_id: u32,
+}
+
+impl ExtractResource for VideoOutputs {
+    type Source = VideoOutputs;
+
+    fn extract_resource(source: &Self::Source) -> Self {
+        source.clone()
+    }
+}
+
+impl VideoOutputs {
+    /// Starts encoding the render target `image` of size `size` and format
+    /// `format` into `sink`.
+    pub fn start(
+        &mut self,
+        image: Handle<Image>,
+        size: Extent3d,
+        format: TextureFormat,
+        encoder: &EncoderConfig,
+        sink: &OutputSink,
+    ) -> Result<OutputId, VideoError> {
+        let id = OutputId(self.next_id);
+        let output = VideoOutput::new(id, image, size, format, encoder, sink)?;
+        self.next_id += 1;
+        self.outputs.push(output);
+        Ok(id)
+    }
+
+    /// Starts recording `image` to a file, see [`OutputSink::file`].
+    pub fn record(
+        &mut self,
+        image: Handle<Image>,
+        size: Extent3d,
+        format: TextureFormat,
+        path: impl AsRef<Path>,
+    ) -> Result<OutputId, VideoError> {
+        self.start(
+            image,
+            size,
+            format,
+            &EncoderConfig::default(),
+            &OutputSink::file(path),
+        )
+    }
+
+    /// Starts broadcasting `image` to `sink`, see [`OutputSink::rtmp`] and
+    /// [`OutputSink::srt`].
+    pub fn stream(
+        &mut self,
+        image: Handle<Image>,
+        size: Extent3d,
+        format: TextureFormat,
+        sink: OutputSink,
+        bitrate_kbps: u32,
+    ) -> Result<OutputId, VideoError> {
+        self.start(
+            image,
+            size,
+            format,
+            &EncoderConfig::streaming(bitrate_kbps),
+            &sink,
+        )
+    }
+
+    /// Starts publishing `image` to the WebRTC peer reached through
+    /// `signaller`.
+    pub fn publish(
+        &mut self,
+        image: Handle<Image>,
+        size: Extent3d,
+        format: TextureFormat,
+        signaller: Arc<dyn Signaller>,
+    ) -> Result<OutputId, VideoError> {
+        self.start(
+            image,
+            size,
+            format,
+            &EncoderConfig::webrtc(2000),
+            &OutputSink::webrtc(signaller),
+        )
+    }
+
+    /// Stops pushing frames to the output `id` without closing its file.
+    ///
+    /// The pipeline is paused too, so its running time stops and the
+    /// recording continues seamlessly after [`VideoOutputs::resume_recording`].
+    pub fn pause_recording(&mut self, id: OutputId) -> Result<(), VideoError> {
+        let output = self.get_mut(id).ok_or(VideoError::NotRunning)?;
+        output.pipeline.set_state(gst::State::Paused)?;
+        output.paused = true;
+        Ok(())
+    }
+
+    pub fn resume_recording(&mut self, id: OutputId) -> Result<(), VideoError> {
+        let output = self.get_mut(id).ok_or(VideoError::NotRunning)?;
+        output.pipeline.set_state(gst::State::Playing)?;
+        output.paused = false;
+        Ok(())
+    }
+
+    /// Finalizes and removes the output `id`.
+    pub fn stop(&mut self, id: OutputId) {
+        if let Some(index) = self.outputs.iter().position(|output| output.id == id) {
+            self.outputs.remove(index).finish();
+        }
+    }
+
+    pub fn get(&self, id: OutputId) -> Option<&VideoOutput> {
+        self.outputs.iter().find(|output| output.id == id)
+    }
+
+    fn get_mut(&mut self, id: OutputId) -> Option<&mut VideoOutput> {
+        self.outputs.iter_mut().find(|output| output.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &VideoOutput> {
+        self.outputs.iter()
+    }
+}
+
+/// Routes the render target of the [`Camera`] on the same entity into an
+/// output, so a secondary view of the scene can be recorded or streamed on
+/// its own.
+///
+/// The camera must render to a [`RenderTarget::Image`] with `COPY_SRC`
+/// usage. Removing the component finalizes the output.
+#[derive(Component, Debug, Clone)]
+pub struct VideoOutputCamera {
+    pub encoder: EncoderConfig,
+    pub sink: OutputSink,
+    /// Set once the output has been started.
+    pub id: Option<OutputId>,
+}
+
+impl VideoOutputCamera {
+    pub fn new(encoder: EncoderConfig, sink: OutputSink) -> Self {
+        VideoOutputCamera {
+            encoder,
+            sink,
+            id: None,
+        }
+    }
+
+    /// Records the camera to `path`, see [`OutputSink::file`].
+    pub fn record(path: impl AsRef<Path>) -> Self {
+        VideoOutputCamera::new(EncoderConfig::default(), OutputSink::file(path))
+    }
+}
+
+/// Started output of each camera entity, to stop it once the component goes.
+#[derive(Default)]
+struct CameraOutputIds(HashMap<Entity, OutputId>);
+
+fn start_camera_outputs(
+    mut cameras: Query<(Entity, &Camera, &mut VideoOutputCamera)>,
+    images: Res<Assets<Image>>,
+    mut outputs: ResMut<VideoOutputs>,
+    mut camera_outputs: ResMut<CameraOutputIds>,
+    mut failed: Local<Vec<Entity>>,
+) {
+    for (entity, camera, mut output) in &mut cameras {
+        if output.id.is_some() || failed.contains(&entity) {
+            continue;
+        }
+        let handle = match &camera.target {
+            RenderTarget::Image(handle) => handle,
+            RenderTarget::Window(_) => {
+                warn!(
+                    "Camera {:?} does not render to an image, not starting its output",
+                    entity
+                );
+                failed.push(entity);
+                continue;
+            }
+        };
+        // The image may still be loading.
+        let image = match images.get(handle) {
+            Some(image) => image,
+            None => continue,
+        };
+        let descriptor = &image.texture_descriptor;
+        match outputs.start(
+            handle.clone_weak(),
+            descriptor.size,
+            descriptor.format,
+            &output.encoder,
+            &output.sink,
+        ) {
+            Ok(id) => {
+                info!("Camera {:?} feeds output {:?}", entity, id);
+                output.id = Some(id);
+                camera_outputs.0.insert(entity, id);
+            }
+            Err(error) => {
+                error!("Failed to start output for camera {:?}: {}", entity, error);
+                failed.push(entity);
+            }
+        }
+    }
+    failed.retain(|entity| cameras.contains(*entity));
+}
+
+fn stop_camera_outputs(
+    removed: RemovedComponents<VideoOutputCamera>,
+    mut outputs: ResMut<VideoOutputs>,
+    mut camera_outputs: ResMut<CameraOutputIds>,
+) {
+    for entity in removed.iter() {
+        if let Some(id) = camera_outputs.0.remove(&entity) {
+            outputs.stop(id);
+        }
+    }
+}
+
+/// Sent when a segmented recording closed a file.
+pub struct VideoSegmentFinished {
+    pub id: OutputId,
+    pub path: PathBuf,
+}
+
+/// Sent when an output pipeline failed. The output is removed.
+pub struct VideoOutputError {
+    pub id: OutputId,
+    pub error: VideoError,
+}
+
+fn poll_output_buses(
+    mut outputs: ResMut<VideoOutputs>,
+    mut segment_events: EventWriter<VideoSegmentFinished>,
+    mut error_events: EventWriter<VideoOutputError>,
+) {
+    let mut failed = Vec::new();
+    for output in outputs.iter() {
+        let mut error = output
+            .webrtc
+            .as_ref()
+            .and_then(|session| session.handle_remote().err());
+        if let Some(bus) = output.pipeline.bus() {
+            for msg in bus.iter() {
+                if let gst::MessageView::Element(element) = msg.view() {
+                    let location = element
+                        .structure()
+                        .filter(|s| s.name() == "splitmuxsink-fragment-closed")
+                        .and_then(|s| s.get::<String>("location").ok());
+                    if let Some(location) = location {
+                        info!("Output {:?} finished segment {}", output.id, location);
+                        segment_events.send(VideoSegmentFinished {
+                            id: output.id,
+                            path: PathBuf::from(location),
+                        });
+                    }
+                }
+                if error.is_none() {
+                    error = VideoError::from_message(&msg);
+                }
+            }
+            if let Some(error) = error {
+                error!("Output {:?} failed: {}", output.id, error);
+                failed.push(output.id);
+                error_events.send(VideoOutputError {
+                    id: output.id,
+                    error,
+                });
+            }
+        }
+    }
+    for id in failed {
+        outputs.stop(id);
+    }
+}
+
+fn read_back_outputs(
+    outputs: Res<VideoOutputs>,
+    gpu_images: Res<RenderAssets<Image>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    for output in outputs.iter().filter(|output| !output.paused) {
+        let gpu_image = match gpu_images.get(&output.image) {
+            Some(gpu_image) => gpu_image,
+            None => continue,
+        };
+
+        // Rows of a texture copy must be aligned, so the buffer is padded and
+        // the padding stripped again on the CPU.
+        let row_bytes = output.width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_row_bytes = (row_bytes + align - 1) / align * align;
+
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("video_output_readback"),
+            size: (padded_row_bytes * output.height) as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder =
+            render_device.create_command_encoder(&CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            gpu_image.texture.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &buffer,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            Extent3d {
+                width: output.width,
+                height: output.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        render_queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |_| ());
+        render_device.wgpu_device().poll(wgpu::Maintain::Wait);
+
+        let mut frame = Vec::with_capacity((row_bytes * output.height) as usize);
+        for row in slice
+            .get_mapped_range()
+            .chunks_exact(padded_row_bytes as usize)
+        {
+            frame.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        buffer.unmap();
+
+        output.push_frame(frame);
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::player::VideoPlayer;
+
+/// Shows a red box with the error kind on top of every [`VideoPlayer`] whose
+/// stream is in an error state.
+///
+/// Text is only drawn once [`ErrorOverlayConfig::font`] points at a loaded
+/// font; without one the colored box is still shown.
+#[derive(Default)]
+pub struct ErrorOverlayPlugin;
+
+impl Plugin for ErrorOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ErrorOverlayConfig>()
+            .add_system(update_error_overlays);
+    }
+}
+
+pub struct ErrorOverlayConfig {
+    pub font: Handle<Font>,
+    pub font_size: f32,
+    pub background: Color,
+    pub text_color: Color,
+}
+
+impl Default for ErrorOverlayConfig {
+    fn default() -> Self {
+        ErrorOverlayConfig {
+            font: Handle::default(),
+            font_size: 16.0,
+            background: Color::rgba(0.8, 0.0, 0.0, 0.8),
+            text_color: Color::WHITE,
+        }
+    }
+}
+
+/// UI node showing the error of the stream displayed by `target`.
+#[derive(Component)]
+pub struct ErrorOverlay {
+    pub target: Entity,
+    pub message: String,
+}
+
+fn update_error_overlays(
+    mut commands: Commands,
+    config: Res<ErrorOverlayConfig>,
+    appsinks: Res<Assets<AppSinkImage>>,
+    players: Query<(Entity, &VideoPlayer, &GlobalTransform)>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut overlays: Query<(Entity, &ErrorOverlay, &mut Style)>,
+) {
+    let camera = cameras.iter().find(|(camera, _)| camera.is_active);
+
+    let mut wanted: HashMap<Entity, (String, Option<Vec2>)> = HashMap::new();
+    for (entity, player, transform) in players.iter() {
+        let error = match appsinks.get(&player.stream).and_then(|a| a.error.as_ref()) {
+            Some(error) => error,
+            None => continue,
+        };
+        let position = camera.and_then(|(camera, camera_transform)| {
+            camera.world_to_viewport(camera_transform, transform.translation())
+        });
+        wanted.insert(entity, (format!("{}: {}", error.kind(), error), position));
+    }
+
+    for (overlay_entity, overlay, mut style) in overlays.iter_mut() {
+        match wanted.get(&overlay.target) {
+            Some((message, position)) if *message == overlay.message => {
+                match position {
+                    Some(position) => {
+                        style.display = Display::Flex;
+                        style.position.left = Val::Px(position.x);
+                        style.position.bottom = Val::Px(position.y);
+                    }
+                    None => style.display = Display::None,
+                }
+                wanted.remove(&overlay.target);
+            }
+            _ => {
+                commands.entity(overlay_entity).despawn_recursive();
+            }
+        }
+    }
+
+    for (target, (message, position)) in wanted {
+        let position = position.unwrap_or_default();
+        commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(position.x),
+                        bottom: Val::Px(position.y),
+                        ..default()
+                    },
+                    padding: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                color: config.background.into(),
+                ..default()
+            })
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle::from_section(
+                    message.clone(),
+                    TextStyle {
+                        font: config.font.clone(),
+                        font_size: config.font_size,
+                        color: config.text_color,
+                    },
+                ));
+            })
+            .insert(ErrorOverlay { target, message });
+    }
+}
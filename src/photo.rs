@@ -0,0 +1,258 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use gst::prelude::*;
+
+use crate::appsink::{handle_failure, AppSinkImage, StreamEvents};
+use crate::error::VideoError;
+use crate::snapshot::write_image;
+use crate::timeline::TimelineEvent;
+
+/// Frames pulled from the camera before the still is taken, so exposure and
+/// white balance have settled.
+const WARMUP_FRAMES: i32 = 10;
+
+/// A still requested with [`AppSinkImage::capture_photo`].
+pub struct PhotoCapture {
+    pub path: PathBuf,
+    task: Option<Task<Result<(u32, u32), VideoError>>>,
+}
+
+impl fmt::Debug for PhotoCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PhotoCapture")
+            .field("path", &self.path)
+            .field("running", &self.task.is_some())
+            .finish()
+    }
+}
+
+/// Sent when the stream was paused to take a still.
+pub struct VideoPhotoStarted {
+    pub handle: Handle<AppSinkImage>,
+    pub path: PathBuf,
+}
+
+/// Sent when a still has been written, before streaming resumes.
+pub struct VideoPhotoCaptured {
+    pub handle: Handle<AppSinkImage>,
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Sent when taking a still failed. Streaming resumes anyway.
+pub struct VideoPhotoFailed {
+    pub handle: Handle<AppSinkImage>,
+    pub path: PathBuf,
+    pub error: VideoError,
+}
+
+impl AppSinkImage {
+    /// Takes a still at the highest resolution the camera supports and writes
+    /// it to `path`.
+    ///
+    /// Most cameras cannot stream and capture at different sizes at once, so
+    /// the stream is stopped while the still is taken and restarted after.
+    pub fn capture_photo(&mut self, path: impl Into<PathBuf>) -> Result<(), VideoError> {
+        if self.device_path().is_none() {
+            return Err(VideoError::Unsupported(String::from(
+                "photos can only be taken from cameras",
+            )));
+        }
+        if self.photo.is_some() {
+            return Err(VideoError::Unsupported(String::from(
+                "a photo is already being taken",
+            )));
+        }
+        self.photo = Some(PhotoCapture {
+            path: path.into(),
+            task: None,
+        });
+        Ok(())
+    }
+
+    pub fn is_capturing_photo(&self) -> bool {
+        self.photo.is_some()
+    }
+}
+
+pub(crate) fn capture_photos(
+    time: Res<Time>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut started_events: EventWriter<VideoPhotoStarted>,
+    mut captured_events: EventWriter<VideoPhotoCaptured>,
+    mut failed_events: EventWriter<VideoPhotoFailed>,
+    mut events: StreamEvents,
+) {
+    let ids: Vec<HandleId> = appsinks
+        .iter()
+        .filter(|(_, appsink)| appsink.photo.is_some())
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in ids {
+        let handle = Handle::weak(id);
+        let appsink = match appsinks.get_mut(&handle) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+        let device = appsink.device_path().unwrap_or_default().to_string();
+        let photo = appsink.photo.as_mut().unwrap();
+
+        let task = match &mut photo.task {
+            Some(task) => task,
+            None => {
+                let path = photo.path.clone();
+                let task_path = path.clone();
+                // The still pipeline needs the device, which the preview only
+                // releases in `Null`. Changing to `Null` never happens
+                // asynchronously, so it is released once `stop` returns.
+                appsink.stop();
+                appsink.photo.as_mut().unwrap().task = Some(
+                    AsyncComputeTaskPool::get()
+                        .spawn(async move { capture_still(&device, &task_path) }),
+                );
+                started_events.send(VideoPhotoStarted {
+                    handle: handle.clone_weak(),
+                    path,
+                });
+                continue;
+            }
+        };
+
+        let result = match future::block_on(future::poll_once(task)) {
+            Some(result) => result,
+            None => continue,
+        };
+        let path = appsink.photo.take().unwrap().path;
+        match result {
+            Ok((width, height)) => {
+                info!(
+                    "Photo of {}x{} written to {}",
+                    width,
+                    height,
+                    path.display()
+                );
+                captured_events.send(VideoPhotoCaptured {
+                    handle: handle.clone_weak(),
+                    path,
+                    width,
+                    height,
+                });
+            }
+            Err(error) => {
+                error!("Failed to take photo: {}", error);
+                appsink
+                    .timeline
+                    .lock()
+                    .unwrap()
+                    .push(TimelineEvent::Error(error.to_string()));
+                failed_events.send(VideoPhotoFailed {
+                    handle: handle.clone_weak(),
+                    path,
+                    error,
+                });
+            }
+        }
+
+        if let Err(err) = appsink.start() {
+            handle_failure(
+                appsink,
+                &handle,
+                err,
+                time.time_since_startup(),
+                &mut events,
+            );
+        }
+    }
+}
+
+/// Opens `device` on its own at the largest size it offers, waits for a few
+/// frames and writes the last one to `path`. Blocks until done.
+fn capture_still(device: &str, path: &Path) -> Result<(u32, u32), VideoError> {
+    gst::init().map_err(VideoError::Init)?;
+
+    let description = format!(
+        "v4l2src name=src device=\"{}\" num-buffers={} ! capsfilter name=filter ! decodebin \
+         ! videoconvert ! video/x-raw,format=RGBA ! appsink name=sink sync=false",
+        device, WARMUP_FRAMES
+    );
+    let pipeline = gst::parse_launch(&description)
+        .map_err(|err| VideoError::Unsupported(format!("{}: {}", description, err)))?
+        .downcast::<gst::Pipeline>()
+        .expect("Launch description without pipeline. Shouldn't happen!");
+    let src = pipeline.by_name("src").expect("Missing source element");
+    let filter = pipeline.by_name("filter").expect("Missing capsfilter");
+    let appsink = pipeline
+        .by_name("sink")
+        .and_then(|sink| sink.downcast::<gst_app::AppSink>().ok())
+        .expect("Sink element is expected to be an appsink!");
+
+    let result = (|| -> Result<(u32, u32), VideoError> {
+        // The device only reports what it supports once it is opened.
+        pipeline.set_state(gst::State::Ready)?;
+        if let Some(caps) = src.static_pad("src").map(|pad| pad.query_caps(None)) {
+            if let Some(largest) = largest_size(&caps) {
+                filter.set_property("caps", largest);
+            }
+        }
+        pipeline.set_state(gst::State::Playing)?;
+
+        let mut last = None;
+        while let Ok(sample) = appsink.pull_sample() {
+            last = Some(sample);
+        }
+        if let Some(err) = pipeline
+            .bus()
+            .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]))
+            .and_then(|msg| VideoError::from_message(&msg))
+        {
+            return Err(err);
+        }
+        let sample = last.ok_or(VideoError::NotRunning)?;
+
+        let info = sample
+            .caps()
+            .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+            .ok_or_else(|| VideoError::Unsupported(String::from("photo without caps")))?;
+        let buffer = sample
+            .buffer()
+            .ok_or_else(|| VideoError::Unsupported(String::from("photo without buffer")))?;
+        let map = buffer.map_readable()?;
+
+        let (width, height) = (info.width(), info.height());
+        let stride = info.stride()[0] as usize;
+        let row_bytes = width as usize * 4;
+        let mut data = Vec::with_capacity(row_bytes * height as usize);
+        for row in map.chunks(stride).take(height as usize) {
+            data.extend_from_slice(&row[..row_bytes]);
+        }
+        write_image(path, &data, width, height)?;
+        Ok((width, height))
+    })();
+
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
+
+/// Caps restricted to the largest frame size listed in `caps`.
+fn largest_size(caps: &gst::Caps) -> Option<gst::Caps> {
+    caps.iter()
+        .filter_map(|s| {
+            let width = s.get::<i32>("width").ok()?;
+            let height = s.get::<i32>("height").ok()?;
+            Some((s.name().to_string(), width, height))
+        })
+        .max_by_key(|(_, width, height)| width * height)
+        .map(|(name, width, height)| {
+            gst::Caps::builder(&name)
+                .field("width", width)
+                .field("height", height)
+                .build()
+        })
+}
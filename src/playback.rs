@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use gst::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::error::VideoError;
+
+impl AppSinkImage {
+    /// Pauses the pipeline, keeping the current frame.
+    pub fn pause(&self) -> Result<(), VideoError> {
+        set_pipeline_state(self.pipeline.as_ref(), gst::State::Paused)
+    }
+
+    /// Resumes a paused pipeline.
+    pub fn play(&self) -> Result<(), VideoError> {
+        set_pipeline_state(self.pipeline.as_ref(), gst::State::Playing)
+    }
+
+    /// Whether the pipeline is paused or about to be.
+    pub fn is_paused(&self) -> bool {
+        self.pipeline.as_ref().map_or(false, |pipeline| {
+            let (_, current, pending) = pipeline.state(gst::ClockTime::ZERO);
+            pending == gst::State::Paused
+                || (current == gst::State::Paused && pending == gst::State::VoidPending)
+        })
+    }
+
+    /// Jumps to `position`, to the closest key frame for speed.
+    pub fn seek(&self, position: Duration) -> Result<(), VideoError> {
+        seek_pipeline(self.pipeline.as_ref(), position)?;
+        // Sound from before the seek would play over the new position.
+        if let Some(audio) = &self.audio {
+            audio.clear();
+        }
+        if let Some(av_sync) = self.av_sync.lock().unwrap().as_mut() {
+            av_sync.clear();
+        }
+        Ok(())
+    }
+
+    /// Current playback position, `None` if not running or unknown.
+    pub fn position(&self) -> Option<Duration> {
+        pipeline_position(self.pipeline.as_ref()?)
+    }
+
+    /// Length of the media, `None` for live sources or if unknown.
+    pub fn duration(&self) -> Option<Duration> {
+        pipeline_duration(self.pipeline.as_ref()?)
+    }
+}
+
+/// Changes the state of the pipeline of a video or audio-only stream.
+pub(crate) fn set_pipeline_state(
+    pipeline: Option<&gst::Pipeline>,
+    state: gst::State,
+) -> Result<(), VideoError> {
+    pipeline.ok_or(VideoError::NotRunning)?.set_state(state)?;
+    Ok(())
+}
+
+/// Seeks the pipeline of a video or audio-only stream to the key frame
+/// closest to `position`.
+pub(crate) fn seek_pipeline(
+    pipeline: Option<&gst::Pipeline>,
+    position: Duration,
+) -> Result<(), VideoError> {
+    pipeline.ok_or(VideoError::NotRunning)?.seek_simple(
+        gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
+        gst::ClockTime::from_nseconds(position.as_nanos() as u64),
+    )?;
+    Ok(())
+}
+
+pub(crate) fn pipeline_position(pipeline: &gst::Pipeline) -> Option<Duration> {
+    let position = pipeline.query_position::<gst::ClockTime>()?;
+    Some(Duration::from_nanos(position.nseconds()))
+}
+
+pub(crate) fn pipeline_duration(pipeline: &gst::Pipeline) -> Option<Duration> {
+    let duration = pipeline.query_duration::<gst::ClockTime>()?;
+    Some(Duration::from_nanos(duration.nseconds()))
+}
+
+/// Sets the pipeline to `Null` and drops it with its bus. Returns whether a
+/// pipeline was running.
+pub(crate) fn stop_pipeline(
+    pipeline: &mut Option<gst::Pipeline>,
+    bus: &mut Option<gst::Bus>,
+) -> bool {
+    *bus = None;
+    match pipeline.take() {
+        Some(pipeline) => {
+            let _ = pipeline.set_state(gst::State::Null);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,12 @@
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Marks an entity as displaying the stream `stream`.
+///
+/// Systems that report on a stream (error overlays, status badges, ...) use
+/// this to find where in the scene the stream is shown.
+#[derive(Component, Debug, Clone)]
+pub struct VideoPlayer {
+    pub stream: Handle<AppSinkImage>,
+}
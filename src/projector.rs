@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use bevy::asset::{load_internal_asset, HandleId};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef, ShaderType};
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::material::VideoTexture;
+use crate::player::VideoPlayer;
+
+const PROJECTOR_SHADER_HANDLE: HandleUntyped =
+    HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x5c3a_94e1_7b02_d6f8);
+
+/// Lets [`VideoProjector`]s light up surfaces using a [`ProjectedMaterial`].
+#[derive(Default)]
+pub struct VideoProjectorPlugin;
+
+impl Plugin for VideoProjectorPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            PROJECTOR_SHADER_HANDLE,
+            "projector.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugin(MaterialPlugin::<ProjectedMaterial>::default())
+            .add_system_to_stage(CoreStage::PostUpdate, update_projected_materials);
+    }
+}
+
+/// Projects the [`VideoTexture`] of the entity along its forward axis, like a
+/// movie projector or a gobo on a stage light.
+#[derive(Component, Debug, Clone)]
+pub struct VideoProjector {
+    /// Vertical field of view in radians.
+    pub fov: f32,
+    /// Width over height of the projected image.
+    pub aspect_ratio: f32,
+    pub near: f32,
+    pub far: f32,
+    /// Brightness of the projected image.
+    pub intensity: f32,
+}
+
+impl Default for VideoProjector {
+    fn default() -> Self {
+        VideoProjector {
+            fov: std::f32::consts::FRAC_PI_4,
+            aspect_ratio: WIDTH as f32 / HEIGHT as f32,
+            near: 0.1,
+            far: 100.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl VideoProjector {
+    /// Maps world space into the projector's clip space.
+    pub fn view_projection(&self, transform: &GlobalTransform) -> Mat4 {
+        let projection = Mat4::perspective_rh(self.fov, self.aspect_ratio, self.near, self.far);
+        projection * transform.compute_matrix().inverse()
+    }
+}
+
+/// A projector showing a stream.
+#[derive(Bundle, Clone)]
+pub struct VideoProjectorBundle {
+    pub projector: VideoProjector,
+    pub player: VideoPlayer,
+    pub texture: VideoTexture,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+impl VideoProjectorBundle {
+    /// Creates the texture for `stream`.
+    pub fn new(
+        stream: Handle<AppSinkImage>,
+        projector: VideoProjector,
+        transform: Transform,
+        images: &mut Assets<Image>,
+    ) -> Self {
+        VideoProjectorBundle {
+            projector,
+            player: VideoPlayer { stream },
+            texture: VideoTexture(images.add(VideoTexture::image())),
+            transform,
+            global_transform: GlobalTransform::default(),
+        }
+    }
+}
+
+/// A lit surface that receives the image of a [`VideoProjector`] on top of
+/// its own color.
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "a4d1e7f2-35c8-4b9e-8f06-1c2b7d9e4a53"]
+#[uniform(0, ProjectedMaterialUniform)]
+pub struct ProjectedMaterial {
+    pub base_color: Color,
+    #[texture(1)]
+    #[sampler(2)]
+    pub base_color_texture: Option<Handle<Image>>,
+    /// The projector whose image falls onto the surface.
+    pub projector: Option<Entity>,
+    /// Kept up to date from `projector`.
+    #[texture(3)]
+    #[sampler(4)]
+    pub projection: Option<Handle<Image>>,
+    pub view_projection: Mat4,
+    pub projector_position: Vec3,
+    pub intensity: f32,
+}
+
+impl Default for ProjectedMaterial {
+    fn default() -> Self {
+        ProjectedMaterial {
+            base_color: Color::WHITE,
+            base_color_texture: None,
+            projector: None,
+            projection: None,
+            view_projection: Mat4::ZERO,
+            projector_position: Vec3::ZERO,
+            intensity: 0.0,
+        }
+    }
+}
+
+impl ProjectedMaterial {
+    /// A surface of `base_color` lit by `projector`.
+    pub fn new(base_color: Color, projector: Entity) -> Self {
+        ProjectedMaterial {
+            base_color,
+            projector: Some(projector),
+            ..default()
+        }
+    }
+}
+
+const PROJECTED_MATERIAL_BASE_COLOR_TEXTURE: u32 = 1;
+const PROJECTED_MATERIAL_PROJECTION: u32 = 2;
+
+/// GPU representation of the [`ProjectedMaterial`] settings.
+#[derive(Clone, Default, ShaderType)]
+pub struct ProjectedMaterialUniform {
+    pub base_color: Vec4,
+    pub view_projection: Mat4,
+    pub projector_position: Vec3,
+    pub intensity: f32,
+    pub flags: u32,
+}
+
+impl AsBindGroupShaderType<ProjectedMaterialUniform> for ProjectedMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<Image>) -> ProjectedMaterialUniform {
+        let mut flags = 0;
+        if self.base_color_texture.is_some() {
+            flags |= PROJECTED_MATERIAL_BASE_COLOR_TEXTURE;
+        }
+        if self.projection.is_some() {
+            flags |= PROJECTED_MATERIAL_PROJECTION;
+        }
+        ProjectedMaterialUniform {
+            base_color: self.base_color.as_linear_rgba_f32().into(),
+            view_projection: self.view_projection,
+            projector_position: self.projector_position,
+            intensity: self.intensity,
+            flags,
+        }
+    }
+}
+
+impl Material for ProjectedMaterial {
+    fn fragment_shader() -> ShaderRef {
+        PROJECTOR_SHADER_HANDLE.typed().into()
+    }
+}
+
+/// Copies the placement and texture of projectors into the materials they
+/// shine on. Runs every frame, which also makes the materials pick up new
+/// video frames.
+fn update_projected_materials(
+    projectors: Query<(Entity, &VideoProjector, &GlobalTransform, &VideoTexture)>,
+    mut materials: ResMut<Assets<ProjectedMaterial>>,
+) {
+    let projectors: HashMap<Entity, _> = projectors
+        .iter()
+        .map(|(entity, projector, transform, texture)| (entity, (projector, transform, texture)))
+        .collect();
+
+    let ids: Vec<HandleId> = materials
+        .iter()
+        .filter(|(_, material)| material.projector.is_some())
+        .map(|(id, _)| id)
+        .collect();
+    for id in ids {
+        let material = match materials.get_mut(id) {
+            Some(material) => material,
+            None => continue,
+        };
+        match material
+            .projector
+            .and_then(|entity| projectors.get(&entity))
+        {
+            Some((projector, transform, texture)) => {
+                material.projection = Some(texture.0.clone());
+                material.view_projection = projector.view_projection(transform);
+                material.projector_position = transform.translation();
+                material.intensity = projector.intensity;
+            }
+            // The projector was despawned.
+            None => material.projection = None,
+        }
+    }
+}
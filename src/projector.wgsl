@@ -0,0 +1,88 @@
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+
+#import bevy_pbr::pbr_types
+#import bevy_pbr::utils
+#import bevy_pbr::clustered_forward
+#import bevy_pbr::lighting
+#import bevy_pbr::shadows
+#import bevy_pbr::pbr_functions
+
+struct ProjectedMaterial {
+    base_color: vec4<f32>,
+    view_projection: mat4x4<f32>,
+    projector_position: vec3<f32>,
+    intensity: f32,
+    flags: u32,
+};
+
+let PROJECTED_MATERIAL_BASE_COLOR_TEXTURE: u32 = 1u;
+let PROJECTED_MATERIAL_PROJECTION: u32 = 2u;
+
+@group(1) @binding(0)
+var<uniform> material: ProjectedMaterial;
+@group(1) @binding(1)
+var base_color_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var base_color_sampler: sampler;
+@group(1) @binding(3)
+var projection_texture: texture_2d<f32>;
+@group(1) @binding(4)
+var projection_sampler: sampler;
+
+struct FragmentInput {
+    @builtin(front_facing) is_front: bool,
+    @builtin(position) frag_coord: vec4<f32>,
+    #import bevy_pbr::mesh_vertex_output
+};
+
+// Light reaching the surface from the projector, black outside its frustum.
+fn projected_light(world_position: vec4<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let clip = material.view_projection * world_position;
+    if (clip.w <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    let ndc = clip.xyz / clip.w;
+    if (any(abs(ndc.xy) > vec2<f32>(1.0)) || ndc.z < 0.0 || ndc.z > 1.0) {
+        return vec3<f32>(0.0);
+    }
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5);
+    // Sampled without mip maps, the derivatives are not uniform here.
+    let color = textureSampleLevel(projection_texture, projection_sampler, uv, 0.0).rgb;
+    let to_projector = normalize(material.projector_position - world_position.xyz);
+    return color * material.intensity * max(dot(normal, to_projector), 0.0);
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    var base_color = material.base_color;
+    if ((material.flags & PROJECTED_MATERIAL_BASE_COLOR_TEXTURE) != 0u) {
+        base_color = base_color * textureSample(base_color_texture, base_color_sampler, in.uv);
+    }
+
+    var pbr_input: PbrInput = pbr_input_new();
+    pbr_input.material.base_color = base_color;
+    pbr_input.frag_coord = in.frag_coord;
+    pbr_input.world_position = in.world_position;
+    pbr_input.world_normal = in.world_normal;
+    pbr_input.is_orthographic = view.projection[3].w == 1.0;
+    pbr_input.N = prepare_normal(
+        pbr_input.material.flags,
+        in.world_normal,
+#ifdef VERTEX_TANGENTS
+#ifdef STANDARDMATERIAL_NORMAL_MAP
+        in.world_tangent,
+#endif
+#endif
+        in.uv,
+        in.is_front,
+    );
+    pbr_input.V = calculate_view(in.world_position, pbr_input.is_orthographic);
+
+    var output = pbr(pbr_input);
+    if ((material.flags & PROJECTED_MATERIAL_PROJECTION) != 0u) {
+        let light = projected_light(in.world_position, pbr_input.N);
+        output = vec4<f32>(output.rgb + base_color.rgb * light, output.a);
+    }
+    return tone_mapping(output);
+}
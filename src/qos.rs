@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Quality-of-service statistics of every stream, aggregated from the QoS
+/// messages posted by the pipeline elements.
+#[derive(Debug, Default)]
+pub struct VideoQosStats {
+    pub streams: HashMap<Handle<AppSinkImage>, StreamQos>,
+}
+
+impl VideoQosStats {
+    pub fn get(&self, handle: &Handle<AppSinkImage>) -> Option<&StreamQos> {
+        self.streams.get(handle)
+    }
+}
+
+/// QoS of a single stream.
+#[derive(Debug, Clone, Default)]
+pub struct StreamQos {
+    /// Per element statistics, keyed by element name, to tell whether the
+    /// source, the decoder or the sink is falling behind.
+    pub elements: HashMap<String, ElementQos>,
+    /// Frames received by the appsink but overwritten before Bevy uploaded
+    /// them, i.e. the upload path falling behind.
+    pub upload_dropped: u64,
+}
+
+impl StreamQos {
+    /// Total number of processing deadlines missed by the pipeline.
+    pub fn deadline_misses(&self) -> u64 {
+        self.elements.values().map(|element| element.late).sum()
+    }
+
+    /// Total number of buffers dropped by the pipeline for QoS reasons.
+    pub fn dropped(&self) -> u64 {
+        self.elements.values().map(|element| element.dropped).sum()
+    }
+}
+
+/// QoS reported by a single element.
+#[derive(Debug, Clone, Default)]
+pub struct ElementQos {
+    /// QoS messages received.
+    pub messages: u64,
+    /// Messages where the buffer arrived after its deadline.
+    pub late: u64,
+    /// Difference between the deadline and the arrival time of the last
+    /// buffer, in nanoseconds. Positive values are late.
+    pub jitter_ns: i64,
+    /// Worst jitter seen so far, in nanoseconds.
+    pub max_jitter_ns: i64,
+    /// Long-term rate relative to real time, above 1.0 the element is too slow.
+    pub proportion: f64,
+    /// Buffers processed, as reported by the element.
+    pub processed: u64,
+    /// Buffers dropped, as reported by the element.
+    pub dropped: u64,
+}
+
+impl ElementQos {
+    pub(crate) fn record(&mut self, msg: &gst::message::Qos) {
+        let (jitter, proportion, _quality) = msg.values();
+        let (processed, dropped) = msg.stats();
+
+        self.messages += 1;
+        if jitter > 0 {
+            self.late += 1;
+        }
+        self.jitter_ns = jitter;
+        self.max_jitter_ns = self.max_jitter_ns.max(jitter);
+        self.proportion = proportion;
+        self.processed = processed.value().max(0) as u64;
+        self.dropped = dropped.value().max(0) as u64;
+    }
+}
+
+pub(crate) fn update_upload_qos(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut qos_stats: ResMut<VideoQosStats>,
+) {
+    // Forget unloaded streams, the weak handles below don't keep them alive.
+    qos_stats
+        .streams
+        .retain(|handle, _| appsinks.contains(handle));
+    for (id, appsink) in appsinks.iter() {
+        qos_stats
+            .streams
+            .entry(Handle::weak(id))
+            .or_default()
+            .upload_dropped = appsink.stats.snapshot().dropped;
+    }
+}
@@ -0,0 +1,74 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+
+/// Read access to the decoded frames of every stream, for computer vision
+/// systems running alongside rendering. Add it as a system parameter and
+/// call [`VideoFrames::latest`] with the handle of a stream.
+#[derive(SystemParam)]
+pub struct VideoFrames<'w, 's> {
+    appsinks: Res<'w, Assets<AppSinkImage>>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> VideoFrames<'w, 's> {
+    /// The frame the textures of `handle` show during this Bevy frame,
+    /// `None` before the first one.
+    pub fn latest(&self, handle: &Handle<AppSinkImage>) -> Option<FrameRef> {
+        let (serial, pts, data) = self.appsinks.get(handle)?.latch.latest()?;
+        Some(FrameRef {
+            data,
+            width: WIDTH,
+            height: HEIGHT,
+            stride: WIDTH as usize * 4,
+            pts,
+            serial,
+        })
+    }
+}
+
+/// A decoded RGBA frame, shared with the outputs of the stream instead of
+/// copied. Cheap to clone and can be sent to another thread, the pixels
+/// never change once latched.
+#[derive(Debug, Clone)]
+pub struct FrameRef {
+    data: Arc<Vec<u8>>,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from one row to the next.
+    pub stride: usize,
+    /// Presentation timestamp, `None` for frames replayed by time shift.
+    pub pts: Option<gst::ClockTime>,
+    /// Incremented for every new frame of the stream, to skip frames that
+    /// were already processed.
+    pub serial: u64,
+}
+
+impl FrameRef {
+    /// Pixels of row `y`.
+    pub fn row(&self, y: u32) -> &[u8] {
+        let start = y as usize * self.stride;
+        &self.data[start..start + self.width as usize * 4]
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let start = x as usize * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.row(y)[start..start + 4]);
+        pixel
+    }
+}
+
+impl Deref for FrameRef {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
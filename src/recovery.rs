@@ -0,0 +1,192 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::error::VideoError;
+
+/// How a stream tries to come back after its pipeline fails.
+///
+/// On error the pipeline is torn down and rebuilt after an exponential
+/// backoff, up to `max_attempts` times in a row.
+#[derive(Debug, Clone)]
+pub struct RecoveryPolicy {
+    /// Restarts attempted before giving up. Zero disables recovery.
+    pub max_attempts: u32,
+    /// Delay before the first restart.
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between restarts.
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after every failed attempt.
+    pub multiplier: f32,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        RecoveryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RecoveryPolicy {
+    pub fn disabled() -> Self {
+        RecoveryPolicy {
+            max_attempts: 0,
+            ..default()
+        }
+    }
+
+    /// Delay to wait before restart number `attempt` (starting at 1).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let factor = self.multiplier.powi(exponent);
+        let secs =
+            (self.initial_backoff.as_secs_f32() * factor).min(self.max_backoff.as_secs_f32());
+        Duration::from_secs_f32(secs)
+    }
+}
+
+/// Progress of the recovery of a single stream.
+#[derive(Debug, Clone, Default)]
+pub struct RecoveryState {
+    /// Restarts attempted since the stream last reached `Playing`.
+    pub attempts: u32,
+    /// When the next restart is due, measured from app startup.
+    pub retry_at: Option<Duration>,
+}
+
+impl RecoveryState {
+    /// Schedules the next restart after `err` happened at `now`, returning
+    /// its delay, or `None` if the stream gives up.
+    pub(crate) fn schedule(
+        &mut self,
+        policy: &RecoveryPolicy,
+        err: &VideoError,
+        now: Duration,
+    ) -> Option<Duration> {
+        if err.is_recoverable() && self.attempts < policy.max_attempts {
+            self.attempts += 1;
+            let delay = policy.backoff(self.attempts);
+            self.retry_at = Some(now + delay);
+            Some(delay)
+        } else {
+            self.retry_at = None;
+            None
+        }
+    }
+}
+
+/// Sent when a failed stream has been torn down and a restart is scheduled.
+pub struct VideoRecovering {
+    pub handle: Handle<AppSinkImage>,
+    pub attempt: u32,
+    pub delay: Duration,
+    pub error: VideoError,
+}
+
+/// Sent when a restarted stream reaches `Playing` again.
+pub struct VideoRecovered {
+    pub handle: Handle<AppSinkImage>,
+    pub attempts: u32,
+}
+
+/// Sent when a stream failed and will not be restarted anymore.
+pub struct VideoGaveUp {
+    pub handle: Handle<AppSinkImage>,
+    pub attempts: u32,
+    pub error: VideoError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially() {
+        let policy = RecoveryPolicy::default();
+        let cases = [
+            (0, 500),
+            (1, 500),
+            (2, 1000),
+            (3, 2000),
+            (4, 4000),
+            (7, 30_000),
+        ];
+        for (attempt, millis) in cases {
+            assert_eq!(
+                policy.backoff(attempt).as_millis(),
+                millis,
+                "attempt {}",
+                attempt
+            );
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = RecoveryPolicy {
+            max_backoff: Duration::from_secs(3),
+            ..default()
+        };
+        assert_eq!(policy.backoff(3), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(3));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn constant_backoff() {
+        let policy = RecoveryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            multiplier: 1.0,
+            ..default()
+        };
+        for attempt in 1..10 {
+            assert_eq!(policy.backoff(attempt), Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn disabled_policy_makes_no_attempts() {
+        assert_eq!(RecoveryPolicy::disabled().max_attempts, 0);
+        let mut state = RecoveryState::default();
+        let delay = state.schedule(&RecoveryPolicy::disabled(), &VideoError::Ended, default());
+        assert_eq!(delay, None);
+        assert_eq!(state.attempts, 0);
+    }
+
+    #[test]
+    fn schedules_restarts_until_the_budget_is_spent() {
+        let policy = RecoveryPolicy {
+            max_attempts: 2,
+            ..default()
+        };
+        let mut state = RecoveryState::default();
+        let now = Duration::from_secs(10);
+        assert_eq!(
+            state.schedule(&policy, &VideoError::Ended, now),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(state.retry_at, Some(now + Duration::from_millis(500)));
+        assert_eq!(
+            state.schedule(&policy, &VideoError::Ended, now),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(state.schedule(&policy, &VideoError::Ended, now), None);
+        assert_eq!((state.attempts, state.retry_at), (2, None));
+    }
+
+    #[test]
+    fn gives_up_on_unrecoverable_errors() {
+        let mut state = RecoveryState::default();
+        let err = VideoError::Unsupported(String::from("no video"));
+        assert_eq!(
+            state.schedule(&RecoveryPolicy::default(), &err, default()),
+            None
+        );
+        assert_eq!(state.attempts, 0);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::HashMap;
+use std::num::NonZeroU8;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::render::render_resource::{AddressMode, FilterMode, SamplerDescriptor};
+use bevy::render::texture::ImageSampler;
+
+use crate::appsink::AppSinkImage;
+use crate::material::{VideoMaterial, VideoTexture};
+use crate::player::VideoPlayer;
+
+/// How the texture of a stream is filtered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFilter {
+    /// Sharp pixels, for pixel art or low resolution feeds.
+    Nearest,
+    Linear,
+}
+
+/// What is sampled outside of the texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoAddressMode {
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+/// Sampler of the textures showing a stream, set on
+/// [`AppSinkImage::sampler`] or with the `filter`, `anisotropy` and
+/// `address_mode` keys of the `.sinkimage` file. Streams without one use the
+/// app's default sampler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoSampler {
+    pub filter: VideoFilter,
+    /// Anisotropic filtering level, 1, 2, 4, 8 or 16. Helps screens seen at
+    /// grazing angles, only applies to linear filtering.
+    pub anisotropy: u8,
+    pub address_mode: VideoAddressMode,
+}
+
+impl Default for VideoSampler {
+    fn default() -> Self {
+        VideoSampler {
+            filter: VideoFilter::Linear,
+            anisotropy: 1,
+            address_mode: VideoAddressMode::ClampToEdge,
+        }
+    }
+}
+
+impl VideoSampler {
+    pub fn nearest() -> Self {
+        VideoSampler {
+            filter: VideoFilter::Nearest,
+            ..default()
+        }
+    }
+
+    pub fn anisotropic(level: u8) -> Self {
+        VideoSampler {
+            anisotropy: level,
+            ..default()
+        }
+    }
+
+    pub fn descriptor(&self) -> SamplerDescriptor<'static> {
+        let filter = match self.filter {
+            VideoFilter::Nearest => FilterMode::Nearest,
+            VideoFilter::Linear => FilterMode::Linear,
+        };
+        let address_mode = match self.address_mode {
+            VideoAddressMode::ClampToEdge => AddressMode::ClampToEdge,
+            VideoAddressMode::Repeat => AddressMode::Repeat,
+            VideoAddressMode::MirrorRepeat => AddressMode::MirrorRepeat,
+        };
+        let anisotropy_clamp = match self.filter {
+            VideoFilter::Linear if self.anisotropy > 1 => {
+                NonZeroU8::new(self.anisotropy.next_power_of_two().min(16))
+            }
+            _ => None,
+        };
+        SamplerDescriptor {
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            anisotropy_clamp,
+            ..default()
+        }
+    }
+
+    fn image_sampler(sampler: Option<&VideoSampler>) -> ImageSampler {
+        match sampler {
+            Some(sampler) => ImageSampler::Descriptor(sampler.descriptor()),
+            None => ImageSampler::Default,
+        }
+    }
+
+    pub(crate) fn parse_filter(value: &str) -> Option<VideoFilter> {
+        match value {
+            "nearest" => Some(VideoFilter::Nearest),
+            "linear" => Some(VideoFilter::Linear),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn parse_address_mode(value: &str) -> Option<VideoAddressMode> {
+        match value {
+            "clamp" => Some(VideoAddressMode::ClampToEdge),
+            "repeat" => Some(VideoAddressMode::Repeat),
+            "mirror" => Some(VideoAddressMode::MirrorRepeat),
+            _ => None,
+        }
+    }
+}
+
+/// Gives the textures of every player the sampler of its stream.
+pub(crate) fn apply_video_samplers(
+    appsinks: Res<Assets<AppSinkImage>>,
+    players: Query<(
+        &VideoPlayer,
+        &VideoTexture,
+        Option<&Handle<VideoMaterial>>,
+        Option<&Handle<StandardMaterial>>,
+    )>,
+    mut images: ResMut<Assets<Image>>,
+    mut video_materials: ResMut<Assets<VideoMaterial>>,
+    mut standard_materials: ResMut<Assets<StandardMaterial>>,
+    mut applied: Local<HashMap<HandleId, Option<VideoSampler>>>,
+) {
+    for (player, texture, video_material, standard_material) in players.iter() {
+        let sampler = appsinks
+            .get(&player.stream)
+            .and_then(|appsink| appsink.sampler);
+        if applied.get(&texture.0.id).copied().flatten() == sampler {
+            continue;
+        }
+        if let Some(image) = images.get_mut(&texture.0) {
+            image.sampler_descriptor = VideoSampler::image_sampler(sampler.as_ref());
+            applied.insert(texture.0.id, sampler);
+            // See `update_video_textures`.
+            if let Some(material) = video_material {
+                video_materials.get_mut(material);
+            }
+            if let Some(material) = standard_material {
+                standard_materials.get_mut(material);
+            }
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use bevy::asset::HandleId;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::device::{
+    VideoDeviceBusy, VideoDeviceRemoved, VideoDeviceReturned, VideoPermissionDenied,
+};
+use crate::frame::VideoFrameReady;
+use crate::health::{VideoDegraded, VideoHealthy};
+use crate::recovery::{VideoGaveUp, VideoRecovered, VideoRecovering};
+use crate::ui::VideoNodeBundle;
+use crate::watchdog::VideoStalled;
+
+/// Drives the status badges and click-to-fullscreen of grids spawned by
+/// [`spawn_security_grid`].
+#[derive(Default)]
+pub struct SecurityGridPlugin;
+
+impl Plugin for SecurityGridPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_feed_status)
+            .add_system(show_feed_status.after(update_feed_status))
+            .add_system(toggle_fullscreen_feed)
+            .add_system(layout_security_grids.after(toggle_fullscreen_feed));
+    }
+}
+
+/// Whether a feed is delivering frames, as last reported by the health,
+/// watchdog, recovery and device events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedStatus {
+    /// No frame yet.
+    Connecting,
+    Online,
+    /// Stalled or missing frames.
+    Degraded,
+    /// Failed, restarting or unplugged.
+    Offline,
+}
+
+impl FeedStatus {
+    fn label(&self) -> &'static str {
+        match self {
+            FeedStatus::Connecting => "CONNECTING",
+            FeedStatus::Online => "ONLINE",
+            FeedStatus::Degraded => "DEGRADED",
+            FeedStatus::Offline => "OFFLINE",
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            FeedStatus::Connecting => Color::rgba(0.4, 0.4, 0.4, 0.8),
+            FeedStatus::Online => Color::rgba(0.0, 0.6, 0.0, 0.8),
+            FeedStatus::Degraded => Color::rgba(0.8, 0.6, 0.0, 0.8),
+            FeedStatus::Offline => Color::rgba(0.8, 0.0, 0.0, 0.8),
+        }
+    }
+}
+
+/// Root UI node of a surveillance-style grid of feeds.
+#[derive(Component, Debug, Clone)]
+pub struct SecurityGrid {
+    pub columns: u32,
+    /// Feed filling the whole grid, toggled by clicking a feed.
+    pub fullscreen: Option<Entity>,
+}
+
+/// A cell of a [`SecurityGrid`], holding the video, its name and its status
+/// badge.
+#[derive(Component, Debug, Clone)]
+pub struct SecurityFeed {
+    pub name: String,
+    pub stream: Handle<AppSinkImage>,
+    pub status: FeedStatus,
+}
+
+/// The status badge of the feed `feed`.
+#[derive(Component, Debug)]
+struct FeedBadge {
+    feed: Entity,
+}
+
+/// Spawns a full window grid showing `feeds`, given as names and streams,
+/// `columns` per row. Labels and badges are drawn with `font`.
+///
+/// Returns the grid entity.
+pub fn spawn_security_grid(
+    commands: &mut Commands,
+    feeds: Vec<(String, Handle<AppSinkImage>)>,
+    columns: u32,
+    font: Handle<Font>,
+    images: &mut Assets<Image>,
+) -> Entity {
+    let text_style = TextStyle {
+        font,
+        font_size: 14.0,
+        color: Color::WHITE,
+    };
+    let corner = |left: bool| Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            top: Val::Px(4.0),
+            left: if left { Val::Px(4.0) } else { Val::Undefined },
+            right: if left { Val::Undefined } else { Val::Px(4.0) },
+            ..default()
+        },
+        padding: UiRect::all(Val::Px(2.0)),
+        ..default()
+    };
+
+    let grid = commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_wrap: FlexWrap::Wrap,
+                align_content: AlignContent::FlexStart,
+                ..default()
+            },
+            color: Color::BLACK.into(),
+            ..default()
+        })
+        .insert(SecurityGrid {
+            columns: columns.max(1),
+            fullscreen: None,
+        })
+        .id();
+
+    for (name, stream) in feeds {
+        let video_style = Style {
+            size: Size::new(Val::Percent(100.0), Val::Auto),
+            max_size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            ..default()
+        };
+        let video = VideoNodeBundle::new(stream.clone(), video_style, images);
+        let feed = commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    padding: UiRect::all(Val::Px(2.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    overflow: Overflow::Hidden,
+                    ..default()
+                },
+                color: Color::BLACK.into(),
+                ..default()
+            })
+            .insert(SecurityFeed {
+                name: name.clone(),
+                stream,
+                status: FeedStatus::Connecting,
+            })
+            .id();
+        commands.entity(feed).with_children(|parent| {
+            parent.spawn_bundle(video);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: corner(true),
+                    color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section(name, text_style.clone()));
+                });
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: corner(false),
+                    color: FeedStatus::Connecting.color().into(),
+                    ..default()
+                })
+                .insert(FeedBadge { feed })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section(
+                        FeedStatus::Connecting.label(),
+                        text_style.clone(),
+                    ));
+                });
+        });
+        commands.entity(grid).add_child(feed);
+    }
+
+    grid
+}
+
+#[derive(SystemParam)]
+struct HealthEvents<'w, 's> {
+    frame_ready: EventReader<'w, 's, VideoFrameReady>,
+    healthy: EventReader<'w, 's, VideoHealthy>,
+    recovered: EventReader<'w, 's, VideoRecovered>,
+    device_returned: EventReader<'w, 's, VideoDeviceReturned>,
+    degraded: EventReader<'w, 's, VideoDegraded>,
+    stalled: EventReader<'w, 's, VideoStalled>,
+    recovering: EventReader<'w, 's, VideoRecovering>,
+    gave_up: EventReader<'w, 's, VideoGaveUp>,
+    device_removed: EventReader<'w, 's, VideoDeviceRemoved>,
+    device_busy: EventReader<'w, 's, VideoDeviceBusy>,
+    permission_denied: EventReader<'w, 's, VideoPermissionDenied>,
+}
+
+fn update_feed_status(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut events: HealthEvents,
+    mut feeds: Query<&mut SecurityFeed>,
+) {
+    // Later entries win, so a failure reported in the same frame as a
+    // frame still counts.
+    let mut changes: Vec<(HandleId, FeedStatus)> = Vec::new();
+    for event in events.frame_ready.iter() {
+        let degraded = appsinks
+            .get(&event.handle)
+            .map_or(false, |appsink| appsink.degraded);
+        if !degraded {
+            changes.push((event.handle.id, FeedStatus::Online));
+        }
+    }
+    changes.extend(
+        events
+            .healthy
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Online)),
+    );
+    changes.extend(
+        events
+            .recovered
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Online)),
+    );
+    changes.extend(
+        events
+            .device_returned
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Online)),
+    );
+    changes.extend(
+        events
+            .degraded
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Degraded)),
+    );
+    changes.extend(
+        events
+            .stalled
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Degraded)),
+    );
+    changes.extend(
+        events
+            .recovering
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Offline)),
+    );
+    changes.extend(
+        events
+            .gave_up
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Offline)),
+    );
+    changes.extend(
+        events
+            .device_removed
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Offline)),
+    );
+    changes.extend(
+        events
+            .device_busy
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Offline)),
+    );
+    changes.extend(
+        events
+            .permission_denied
+            .iter()
+            .map(|e| (e.handle.id, FeedStatus::Offline)),
+    );
+    if changes.is_empty() {
+        return;
+    }
+
+    for mut feed in feeds.iter_mut() {
+        let status = changes
+            .iter()
+            .rev()
+            .find(|(id, _)| *id == feed.stream.id)
+            .map(|(_, status)| *status);
+        if let Some(status) = status {
+            if feed.status != status {
+                info!("Feed {} is {}", feed.name, status.label().to_lowercase());
+                feed.status = status;
+            }
+        }
+    }
+}
+
+fn show_feed_status(
+    feeds: Query<&SecurityFeed, Changed<SecurityFeed>>,
+    mut badges: Query<(&FeedBadge, &mut UiColor, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (badge, mut color, children) in badges.iter_mut() {
+        let feed = match feeds.get(badge.feed) {
+            Ok(feed) => feed,
+            Err(_) => continue,
+        };
+        color.0 = feed.status.color();
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = feed.status.label().to_string();
+            }
+        }
+    }
+}
+
+fn toggle_fullscreen_feed(
+    feeds: Query<(Entity, &Interaction, &Parent), (Changed<Interaction>, With<SecurityFeed>)>,
+    mut grids: Query<&mut SecurityGrid>,
+) {
+    for (feed, interaction, parent) in feeds.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        if let Ok(mut grid) = grids.get_mut(parent.get()) {
+            grid.fullscreen = if grid.fullscreen == Some(feed) {
+                None
+            } else {
+                Some(feed)
+            };
+        }
+    }
+}
+
+fn layout_security_grids(
+    grids: Query<(&SecurityGrid, &Children), Changed<SecurityGrid>>,
+    mut feeds: Query<&mut Style, With<SecurityFeed>>,
+) {
+    for (grid, children) in grids.iter() {
+        let rows = ((children.len() as u32 + grid.columns - 1) / grid.columns).max(1);
+        let cell = Size::new(
+            Val::Percent(100.0 / grid.columns as f32),
+            Val::Percent(100.0 / rows as f32),
+        );
+        let full = Size::new(Val::Percent(100.0), Val::Percent(100.0));
+        for &child in children.iter() {
+            if let Ok(mut style) = feeds.get_mut(child) {
+                let (display, size) = match grid.fullscreen {
+                    Some(fullscreen) if fullscreen == child => (Display::Flex, full),
+                    Some(_) => (Display::None, cell),
+                    None => (Display::Flex, cell),
+                };
+                style.display = display;
+                style.size = size;
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use std::fmt;
+use std::time::Duration;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+use gst::prelude::*;
+
+use crate::appsink::{make_element, AppSinkImage};
+use crate::config::VideoSource;
+use crate::error::VideoError;
+
+/// Size of a single thumbnail in the strip.
+pub const SEEK_PREVIEW_TILE: (u32, u32) = (160, 90);
+
+/// Widest and highest texture wgpu accepts by default
+/// (`max_texture_dimension_2d`).
+const MAX_TEXTURE_SIZE: u32 = 8192;
+
+/// Columns and rows of the grid holding `count` thumbnails, filling rows
+/// up to the widest texture. `None` if they don't fit in one texture.
+fn strip_grid(count: u32) -> Option<(u32, u32)> {
+    let (width, height) = SEEK_PREVIEW_TILE;
+    let columns = count.min(MAX_TEXTURE_SIZE / width).max(1);
+    let rows = (count + columns - 1) / columns;
+    (rows * height <= MAX_TEXTURE_SIZE).then(|| (columns, rows))
+}
+
+/// Thumbnails taken at regular intervals over a file, for showing previews
+/// above a scrub bar.
+#[derive(Debug, Clone)]
+pub struct SeekPreview {
+    /// One tile per entry of `timestamps`, row by row.
+    pub atlas: Handle<TextureAtlas>,
+    pub image: Handle<Image>,
+    pub timestamps: Vec<Duration>,
+    pub duration: Duration,
+}
+
+impl SeekPreview {
+    /// Index of the tile closest to `position`.
+    pub fn index_at(&self, position: Duration) -> usize {
+        let count = self.timestamps.len().max(1);
+        let fraction = position.as_secs_f64() / self.duration.as_secs_f64().max(f64::EPSILON);
+        ((fraction * count as f64) as usize).min(count - 1)
+    }
+}
+
+/// Thumbnails being generated by a background pipeline.
+pub struct PendingSeekPreview {
+    task: Task<Result<(Image, Vec<Duration>, Duration), VideoError>>,
+}
+
+impl fmt::Debug for PendingSeekPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingSeekPreview").finish()
+    }
+}
+
+/// Sent when the thumbnails requested with
+/// [`AppSinkImage::generate_seek_preview`] are available.
+pub struct VideoSeekPreviewReady {
+    pub handle: Handle<AppSinkImage>,
+    pub preview: SeekPreview,
+}
+
+/// Sent when generating thumbnails failed.
+pub struct VideoSeekPreviewFailed {
+    pub handle: Handle<AppSinkImage>,
+    pub error: VideoError,
+}
+
+impl AppSinkImage {
+    /// Starts generating `count` thumbnails spread over the file in a
+    /// separate pipeline. Playback is not affected.
+    pub fn generate_seek_preview(&mut self, count: u32) -> Result<(), VideoError> {
+        if count == 0 {
+            return Err(VideoError::Unsupported(String::from(
+                "a seek preview needs at least one thumbnail",
+            )));
+        }
+        if strip_grid(count).is_none() {
+            return Err(VideoError::Unsupported(format!(
+                "{} thumbnails don't fit in one texture",
+                count
+            )));
+        }
+        let uri = match &self.source {
+            VideoSource::Uri(uri) => uri.clone(),
+            _ => {
+                return Err(VideoError::Unsupported(String::from(
+                    "seek previews need a file or URI source",
+                )))
+            }
+        };
+        if let Some(info) = &self.media_info {
+            if !info.seekable {
+                return Err(VideoError::Unsupported(format!("{} is not seekable", uri)));
+            }
+        }
+        let task = AsyncComputeTaskPool::get().spawn(async move { render_strip(&uri, count) });
+        self.pending_seek_preview = Some(PendingSeekPreview { task });
+        Ok(())
+    }
+}
+
+pub(crate) fn finish_seek_previews(
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut images: ResMut<Assets<Image>>,
+    mut atlases: ResMut<Assets<TextureAtlas>>,
+    mut ready_events: EventWriter<VideoSeekPreviewReady>,
+    mut failed_events: EventWriter<VideoSeekPreviewFailed>,
+) {
+    let ids: Vec<HandleId> = appsinks
+        .iter()
+        .filter(|(_, appsink)| appsink.pending_seek_preview.is_some())
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in ids {
+        let handle = Handle::weak(id);
+        let appsink = match appsinks.get_mut(&handle) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+        let pending = appsink.pending_seek_preview.as_mut().unwrap();
+        let result = match future::block_on(future::poll_once(&mut pending.task)) {
+            Some(result) => result,
+            None => continue,
+        };
+        appsink.pending_seek_preview = None;
+
+        match result {
+            Ok((image, timestamps, duration)) => {
+                let (width, height) = SEEK_PREVIEW_TILE;
+                let columns = image.texture_descriptor.size.width / width;
+                let rows = image.texture_descriptor.size.height / height;
+                let image = images.add(image);
+                let atlas = atlases.add(TextureAtlas::from_grid(
+                    image.clone(),
+                    Vec2::new(width as f32, height as f32),
+                    columns as usize,
+                    rows as usize,
+                ));
+                let preview = SeekPreview {
+                    atlas,
+                    image,
+                    timestamps,
+                    duration,
+                };
+                appsink.seek_preview = Some(preview.clone());
+                ready_events.send(VideoSeekPreviewReady {
+                    handle: handle.clone_weak(),
+                    preview,
+                });
+            }
+            Err(error) => {
+                warn!("Failed to generate seek preview: {}", error);
+                failed_events.send(VideoSeekPreviewFailed {
+                    handle: handle.clone_weak(),
+                    error,
+                });
+            }
+        }
+    }
+}
+
+/// Seeks to `count` evenly spaced key frames of `uri` and renders them row
+/// by row into one image. Blocks until done.
+fn render_strip(uri: &str, count: u32) -> Result<(Image, Vec<Duration>, Duration), VideoError> {
+    gst::init().map_err(VideoError::Init)?;
+
+    let (width, height) = SEEK_PREVIEW_TILE;
+    let (columns, rows) = strip_grid(count).ok_or_else(|| {
+        VideoError::Unsupported(format!("{} thumbnails don't fit in one texture", count))
+    })?;
+    let pipeline = gst::Pipeline::new(None);
+    let src = make_element("uridecodebin")?;
+    src.set_property("uri", uri);
+    let convert = make_element("videoconvert")?;
+    let scale = make_element("videoscale")?;
+    let sink = make_element("appsink")?;
+    sink.set_property("sync", false);
+    pipeline.add_many(&[&src, &convert, &scale, &sink])?;
+    gst::Element::link_many(&[&convert, &scale, &sink])?;
+    let appsink = sink
+        .dynamic_cast::<gst_app::AppSink>()
+        .expect("Sink element is expected to be an appsink!");
+    appsink.set_caps(Some(
+        &gst::Caps::builder("video/x-raw")
+            .field("format", "RGBA")
+            .field("width", width as i32)
+            .field("height", height as i32)
+            .field("pixel-aspect-ratio", gst::Fraction::new(1, 1))
+            .build(),
+    ));
+    let convert = convert.downgrade();
+    src.connect_pad_added(move |_, pad| {
+        let convert = match convert.upgrade() {
+            Some(convert) => convert,
+            None => return,
+        };
+        let is_video = pad
+            .current_caps()
+            .and_then(|caps| caps.structure(0).map(|s| s.name().starts_with("video/")))
+            .unwrap_or(false);
+        let sink_pad = convert
+            .static_pad("sink")
+            .expect("videoconvert without sink pad. Shouldn't happen!");
+        if is_video && !sink_pad.is_linked() {
+            let _ = pad.link(&sink_pad);
+        }
+    });
+
+    let result = (|| -> Result<(Image, Vec<Duration>, Duration), VideoError> {
+        pipeline.set_state(gst::State::Paused)?;
+        wait_for_preroll(&pipeline)?;
+        let duration = pipeline
+            .query_duration::<gst::ClockTime>()
+            .ok_or_else(|| VideoError::Unsupported(format!("{} has no duration", uri)))?;
+
+        let row_bytes = (width * 4) as usize;
+        let strip_row_bytes = row_bytes * columns as usize;
+        let mut data = vec![0u8; strip_row_bytes * (height * rows) as usize];
+        let mut timestamps = Vec::with_capacity(count as usize);
+        for index in 0..count as u64 {
+            let position = duration * (2 * index + 1) / (2 * count as u64);
+            pipeline.seek_simple(gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT, position)?;
+            wait_for_preroll(&pipeline)?;
+            let sample = appsink.pull_preroll().map_err(|_| VideoError::NotRunning)?;
+            let buffer = sample.buffer().ok_or(VideoError::NotRunning)?;
+            let map = buffer.map_readable()?;
+            let stride = sample
+                .caps()
+                .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok())
+                .map_or(row_bytes, |info| info.stride()[0] as usize);
+
+            let (column, row) = (index as u32 % columns, index as u32 / columns);
+            let offset = (row * height) as usize * strip_row_bytes + column as usize * row_bytes;
+            for (y, row) in map.chunks(stride).take(height as usize).enumerate() {
+                let dest = y * strip_row_bytes + offset;
+                data[dest..dest + row_bytes].copy_from_slice(&row[..row_bytes]);
+            }
+            timestamps.push(Duration::from_nanos(
+                buffer.pts().unwrap_or(position).nseconds(),
+            ));
+        }
+
+        let image = Image::new(
+            Extent3d {
+                width: width * columns,
+                height: height * rows,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        Ok((image, timestamps, Duration::from_nanos(duration.nseconds())))
+    })();
+
+    let _ = pipeline.set_state(gst::State::Null);
+    result
+}
+
+/// Waits for the pipeline to preroll after a state change or seek, turning
+/// a bus error into a typed one.
+fn wait_for_preroll(pipeline: &gst::Pipeline) -> Result<(), VideoError> {
+    let (result, _, _) = pipeline.state(gst::ClockTime::from_seconds(10));
+    if result.is_err() {
+        let error = pipeline
+            .bus()
+            .and_then(|bus| bus.pop_filtered(&[gst::MessageType::Error]))
+            .and_then(|msg| VideoError::from_message(&msg));
+        return Err(error.unwrap_or(VideoError::NotRunning));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_thumbnails_out_in_rows() {
+        assert_eq!(strip_grid(1), Some((1, 1)));
+        assert_eq!(strip_grid(20), Some((20, 1)));
+        assert_eq!(strip_grid(51), Some((51, 1)));
+        assert_eq!(strip_grid(52), Some((51, 2)));
+        assert_eq!(strip_grid(51 * 91), Some((51, 91)));
+        assert_eq!(strip_grid(51 * 91 + 1), None);
+    }
+}
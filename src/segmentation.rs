@@ -0,0 +1,274 @@
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, SyncSender};
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use tract_onnx::prelude::*;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::error::VideoError;
+use crate::material::VideoMaterial;
+use crate::player::VideoPlayer;
+
+/// Runs [`VideoSegmentation`] on the streams of players.
+#[derive(Default)]
+pub struct SegmentationPlugin;
+
+impl Plugin for SegmentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<VideoSegmentationFailed>()
+            .add_system(update_segmentation);
+    }
+}
+
+/// Memory layout of the model's input tensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TensorLayout {
+    /// `[1, height, width, 3]`, as used by MediaPipe selfie segmentation.
+    Nhwc,
+    /// `[1, 3, height, width]`, as used by MODNet and most PyTorch exports.
+    Nchw,
+}
+
+/// A person segmentation model in ONNX format.
+///
+/// The model takes one RGB image and returns one probability per pixel at
+/// the same size. Outputs with several channels use the last one.
+#[derive(Debug, Clone)]
+pub struct SegmentationConfig {
+    pub model: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub layout: TensorLayout,
+    /// Values the color range 0..=255 is mapped to.
+    pub range: (f32, f32),
+}
+
+impl SegmentationConfig {
+    /// Settings for MediaPipe's landscape selfie segmentation model.
+    pub fn selfie(model: impl Into<PathBuf>) -> Self {
+        SegmentationConfig {
+            model: model.into(),
+            width: 256,
+            height: 144,
+            layout: TensorLayout::Nhwc,
+            range: (0.0, 1.0),
+        }
+    }
+}
+
+/// Separates people from the background of the stream of the entity's
+/// [`VideoPlayer`], writing the result into `matte`.
+///
+/// The model runs on a worker thread and skips frames while it is busy. If
+/// the entity has a [`VideoMaterial`], the matte is used as its alpha.
+#[derive(Component)]
+pub struct VideoSegmentation {
+    pub config: SegmentationConfig,
+    /// One channel image, white where a person is.
+    pub matte: Handle<Image>,
+    worker: Option<Worker>,
+    sent_serial: u64,
+}
+
+struct Worker {
+    frames: SyncSender<Vec<u8>>,
+    result: Arc<Mutex<Option<Result<Vec<u8>, VideoError>>>>,
+}
+
+/// Sent when the segmentation model cannot be loaded or run.
+pub struct VideoSegmentationFailed {
+    pub handle: Handle<AppSinkImage>,
+    pub error: VideoError,
+}
+
+impl VideoSegmentation {
+    /// Creates the matte texture. The model is loaded once frames arrive.
+    pub fn new(config: SegmentationConfig, images: &mut Assets<Image>) -> Self {
+        let matte = images.add(Image::new_fill(
+            Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255],
+            TextureFormat::R8Unorm,
+        ));
+        VideoSegmentation {
+            config,
+            matte,
+            worker: None,
+            sent_serial: 0,
+        }
+    }
+}
+
+impl Worker {
+    fn spawn(config: SegmentationConfig) -> Result<Worker, VideoError> {
+        // A single slot, frames arriving while the model runs are dropped.
+        let (frames, receiver) = mpsc::sync_channel::<Vec<u8>>(1);
+        let result = Arc::new(Mutex::new(None));
+        let output = result.clone();
+        std::thread::Builder::new()
+            .name(String::from("segmentation"))
+            .spawn(move || {
+                let model = match load_model(&config) {
+                    Ok(model) => model,
+                    Err(err) => {
+                        *output.lock().unwrap() = Some(Err(err));
+                        return;
+                    }
+                };
+                // Ends when the component is removed and the sender dropped.
+                for frame in receiver {
+                    let matte = run_model(&model, &config, &frame);
+                    let failed = matte.is_err();
+                    *output.lock().unwrap() = Some(matte);
+                    if failed {
+                        return;
+                    }
+                }
+            })?;
+        Ok(Worker { frames, result })
+    }
+}
+
+type Model = TypedRunnableModel<TypedModel>;
+
+fn load_model(config: &SegmentationConfig) -> Result<Model, VideoError> {
+    let (width, height) = (config.width as usize, config.height as usize);
+    let shape = match config.layout {
+        TensorLayout::Nhwc => tvec!(1, height, width, 3),
+        TensorLayout::Nchw => tvec!(1, 3, height, width),
+    };
+    tract_onnx::onnx()
+        .model_for_path(&config.model)
+        .and_then(|model| {
+            model.with_input_fact(0, InferenceFact::dt_shape(f32::datum_type(), shape))
+        })
+        .and_then(|model| model.into_optimized())
+        .and_then(|model| model.into_runnable())
+        .map_err(|err| VideoError::Segmentation(format!("{}: {}", config.model.display(), err)))
+}
+
+/// Scales `frame` to the model's input size, runs it and returns the matte as
+/// one byte per pixel.
+fn run_model(
+    model: &Model,
+    config: &SegmentationConfig,
+    frame: &[u8],
+) -> Result<Vec<u8>, VideoError> {
+    let (width, height) = (config.width as usize, config.height as usize);
+    let (low, high) = config.range;
+    let pixel = |x: usize, y: usize, channel: usize| {
+        let x = x * WIDTH as usize / width;
+        let y = y * HEIGHT as usize / height;
+        let value = frame[(y * WIDTH as usize + x) * 4 + channel] as f32 / 255.0;
+        low + value * (high - low)
+    };
+    let input: Tensor = match config.layout {
+        TensorLayout::Nhwc => {
+            tract_ndarray::Array4::from_shape_fn((1, height, width, 3), |(_, y, x, c)| {
+                pixel(x, y, c)
+            })
+        }
+        TensorLayout::Nchw => {
+            tract_ndarray::Array4::from_shape_fn((1, 3, height, width), |(_, c, y, x)| {
+                pixel(x, y, c)
+            })
+        }
+    }
+    .into();
+
+    let outputs = model
+        .run(tvec!(input))
+        .map_err(|err| VideoError::Segmentation(err.to_string()))?;
+    let output = outputs[0]
+        .as_slice::<f32>()
+        .map_err(|err| VideoError::Segmentation(err.to_string()))?;
+    let pixels = width * height;
+    let channels = output.len() / pixels;
+    if channels == 0 {
+        return Err(VideoError::Segmentation(format!(
+            "expected an output of at least {}x{}, got {} values",
+            width,
+            height,
+            output.len()
+        )));
+    }
+    let last = channels - 1;
+    Ok((0..pixels)
+        .map(|index| {
+            let value = match config.layout {
+                TensorLayout::Nhwc => output[index * channels + last],
+                TensorLayout::Nchw => output[last * pixels + index],
+            };
+            (value.clamp(0.0, 1.0) * 255.0) as u8
+        })
+        .collect())
+}
+
+/// Hands new frames to the workers and uploads the mattes they produce.
+fn update_segmentation(
+    mut query: Query<(
+        &VideoPlayer,
+        &mut VideoSegmentation,
+        Option<&Handle<VideoMaterial>>,
+    )>,
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+    mut failed_events: EventWriter<VideoSegmentationFailed>,
+) {
+    for (player, mut segmentation, material) in query.iter_mut() {
+        let appsink = match appsinks.get(&player.stream) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+        if segmentation.worker.is_none() {
+            match Worker::spawn(segmentation.config.clone()) {
+                Ok(worker) => segmentation.worker = Some(worker),
+                Err(error) => {
+                    failed_events.send(VideoSegmentationFailed {
+                        handle: player.stream.clone_weak(),
+                        error,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        let serial = appsink.stats.serial.load(Ordering::Relaxed);
+        let worker = segmentation.worker.as_ref().unwrap();
+        let result = worker.result.lock().unwrap().take();
+        if serial != segmentation.sent_serial {
+            let frame = appsink.image_raw.read().unwrap().to_vec();
+            if worker.frames.try_send(frame).is_ok() {
+                segmentation.sent_serial = serial;
+            }
+        }
+
+        match result {
+            Some(Ok(matte)) => {
+                if let Some(image) = images.get_mut(&segmentation.matte) {
+                    image.data = matte;
+                }
+                // Also makes the material pick up the re-uploaded texture.
+                if let Some(material) = material.and_then(|handle| materials.get_mut(handle)) {
+                    material.matte = Some(segmentation.matte.clone());
+                }
+            }
+            Some(Err(error)) => {
+                warn!("Segmentation failed: {}", error);
+                failed_events.send(VideoSegmentationFailed {
+                    handle: player.stream.clone_weak(),
+                    error,
+                });
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::{IoTaskPool, Task};
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::error::VideoError;
+use crate::player::VideoPlayer;
+
+impl AppSinkImage {
+    /// Copy of the latest frame as tightly packed RGBA, `None` until the
+    /// first frame arrived.
+    pub fn snapshot(&self) -> Option<Vec<u8>> {
+        self.last_sample.read().unwrap().as_ref()?;
+        Some(self.image_raw.read().unwrap().to_vec())
+    }
+
+    /// The latest frame as a standalone [`Image`], see [`AppSinkImage::snapshot`].
+    pub fn snapshot_image(&self) -> Option<Image> {
+        let data = self.snapshot()?;
+        Some(Image::new(
+            Extent3d {
+                width: WIDTH,
+                height: HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        ))
+    }
+
+    /// Writes the latest frame to `path` on the IO task pool. The format is
+    /// picked from the extension, e.g. `.png` or `.jpg`.
+    pub fn save_snapshot(
+        &self,
+        path: impl Into<PathBuf>,
+    ) -> Result<Task<Result<(), VideoError>>, VideoError> {
+        let data = self.snapshot().ok_or(VideoError::NotRunning)?;
+        let path = path.into();
+        Ok(IoTaskPool::get().spawn(async move { write_image(&path, &data, WIDTH, HEIGHT) }))
+    }
+}
+
+impl VideoPlayer {
+    /// Writes the frame currently shown by the player to `path`, see
+    /// [`AppSinkImage::save_snapshot`].
+    pub fn snapshot(
+        &self,
+        appsinks: &Assets<AppSinkImage>,
+        path: impl Into<PathBuf>,
+    ) -> Result<Task<Result<(), VideoError>>, VideoError> {
+        appsinks
+            .get(&self.stream)
+            .ok_or(VideoError::NotRunning)?
+            .save_snapshot(path)
+    }
+}
+
+/// Encodes a tightly packed RGBA frame to `path`. JPEG has no alpha channel,
+/// so it is dropped for `.jpg` files.
+pub(crate) fn write_image(
+    path: &Path,
+    data: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<(), VideoError> {
+    let jpeg = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| {
+            e.eq_ignore_ascii_case("jpg") || e.eq_ignore_ascii_case("jpeg")
+        });
+    if jpeg {
+        let rgb: Vec<u8> = data
+            .chunks_exact(4)
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2]])
+            .collect();
+        image::save_buffer(path, &rgb, width, height, image::ColorType::Rgb8)?;
+    } else {
+        image::save_buffer(path, data, width, height, image::ColorType::Rgba8)?;
+    }
+    Ok(())
+}
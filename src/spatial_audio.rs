@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::player::VideoPlayer;
+
+/// Pans and attenuates the sound of each [`SpatialAudio`] video by where it
+/// is relative to the [`AudioListener`], every frame.
+#[derive(Default)]
+pub struct SpatialAudioPlugin;
+
+impl Plugin for SpatialAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PostUpdate, update_spatial_audio);
+    }
+}
+
+/// Marks the entity hearing spatial audio, usually the camera. Its right is
+/// the right speaker.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct AudioListener;
+
+/// Makes the `audio` sub-asset of the stream of a [`VideoPlayer`] sound like
+/// it comes from the entity's position.
+///
+/// Volume falls off as `reference_distance / distance` beyond
+/// `reference_distance`, scaled by `rolloff`. Left and right channels are
+/// panned, in GStreamer's default layout for the `audio_channels` of the
+/// stream. Center and LFE channels, and mono streams, are only attenuated.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct SpatialAudio {
+    /// Distance at which the sound plays at full volume.
+    pub reference_distance: f32,
+    /// How fast the volume falls off, 0 keeps it constant.
+    pub rolloff: f32,
+}
+
+impl Default for SpatialAudio {
+    fn default() -> Self {
+        SpatialAudio {
+            reference_distance: 1.0,
+            rolloff: 1.0,
+        }
+    }
+}
+
+impl SpatialAudio {
+    /// Volume of each of the `channels` channels for a source at `position`
+    /// in the listener's space.
+    fn gains(&self, position: Vec3, channels: usize) -> Vec<f32> {
+        let distance = position.length();
+        let excess = (distance - self.reference_distance).max(0.0);
+        let volume = self.reference_distance
+            / (self.reference_distance + self.rolloff * excess).max(f32::EPSILON);
+        let pan = if distance > f32::EPSILON {
+            (position.x / distance).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        let sides = channel_sides(channels);
+        (0..channels)
+            .map(|channel| {
+                let side = sides.get(channel).copied().unwrap_or(0.0);
+                volume * (1.0 + side * pan).min(1.0)
+            })
+            .collect()
+    }
+}
+
+/// Side of each channel in the layout GStreamer picks for `channels`
+/// channels without a channel mask: -1 for left, 1 for right and 0 for
+/// center or LFE.
+fn channel_sides(channels: usize) -> &'static [f32] {
+    match channels {
+        0 | 1 => &[0.0],
+        // Stereo and 2.1.
+        2 => &[-1.0, 1.0],
+        3 => &[-1.0, 1.0, 0.0],
+        // Quad, 5.0, 5.1 and 6.1.
+        4 => &[-1.0, 1.0, -1.0, 1.0],
+        5 => &[-1.0, 1.0, 0.0, -1.0, 1.0],
+        6 => &[-1.0, 1.0, 0.0, 0.0, -1.0, 1.0],
+        7 => &[-1.0, 1.0, 0.0, 0.0, -1.0, 1.0, 0.0],
+        // 7.1, extra channels are not panned.
+        _ => &[-1.0, 1.0, 0.0, 0.0, -1.0, 1.0, -1.0, 1.0],
+    }
+}
+
+fn update_spatial_audio(
+    appsinks: Res<Assets<AppSinkImage>>,
+    listeners: Query<&GlobalTransform, With<AudioListener>>,
+    emitters: Query<(&VideoPlayer, &SpatialAudio, &GlobalTransform)>,
+) {
+    let listener = match listeners.iter().next() {
+        Some(listener) => listener.compute_matrix().inverse(),
+        None => return,
+    };
+    for (player, spatial, transform) in emitters.iter() {
+        let audio = match appsinks
+            .get(&player.stream)
+            .and_then(|appsink| appsink.audio.as_ref())
+        {
+            Some(audio) => audio,
+            None => continue,
+        };
+        let position = listener.transform_point3(transform.translation());
+        audio.set_gains(&spatial.gains(position, audio.channels as usize));
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use gst::prelude::*;
+
+use crate::appsink::{make_element, AppSinkImage};
+use crate::error::VideoError;
+use crate::levels::LEVEL_INTERVAL;
+
+/// Label of the spectrum texture sub-asset, load it with
+/// `asset_server.load("movie.sinkimage#spectrum")`.
+pub const SPECTRUM_LABEL: &str = "spectrum";
+
+/// Bands used for `spectrum = true`.
+pub const DEFAULT_BANDS: u32 = 64;
+
+/// Magnitude of silent bands, in dB.
+const THRESHOLD: i32 = -80;
+
+/// Frequency spectrum of every stream with `spectrum` set, for audio-reactive
+/// visuals. Updated from the `spectrum` messages of the pipelines every frame.
+#[derive(Debug, Default)]
+pub struct VideoAudioSpectra {
+    pub streams: HashMap<Handle<AppSinkImage>, AudioSpectrum>,
+}
+
+impl VideoAudioSpectra {
+    pub fn get(&self, handle: &Handle<AppSinkImage>) -> Option<&AudioSpectrum> {
+        self.streams.get(handle)
+    }
+}
+
+/// Magnitude of evenly spaced frequency bands, from 0 Hz to half the sample
+/// rate.
+#[derive(Debug, Clone, Default)]
+pub struct AudioSpectrum {
+    /// Magnitude of each band in dB, down to -80.
+    pub magnitudes: Vec<f32>,
+    /// Stream time of the analyzed samples.
+    pub timestamp: Option<Duration>,
+}
+
+impl AudioSpectrum {
+    /// Magnitudes scaled from 0 for silence to 1 for full scale.
+    pub fn normalized(&self) -> impl Iterator<Item = f32> + '_ {
+        self.magnitudes
+            .iter()
+            .map(|db| (1.0 - db / THRESHOLD as f32).clamp(0.0, 1.0))
+    }
+
+    /// Reads a `spectrum` element message, `None` for any other message.
+    pub(crate) fn from_structure(structure: &gst::StructureRef) -> Option<AudioSpectrum> {
+        if structure.name() != "spectrum" {
+            return None;
+        }
+        let magnitudes = structure
+            .get::<gst::List>("magnitude")
+            .ok()?
+            .iter()
+            .filter_map(|value| value.get::<f32>().ok())
+            .collect();
+        let timestamp = structure
+            .get::<u64>("stream-time")
+            .ok()
+            .map(Duration::from_nanos);
+        Some(AudioSpectrum {
+            magnitudes,
+            timestamp,
+        })
+    }
+
+    /// The `spectrum` element posting `bands` magnitudes every frame.
+    pub(crate) fn element(bands: u32) -> Result<gst::Element, VideoError> {
+        let spectrum = make_element("spectrum")?;
+        spectrum.set_property("bands", bands);
+        spectrum.set_property("threshold", THRESHOLD);
+        spectrum.set_property("interval", LEVEL_INTERVAL.as_nanos() as u64);
+        spectrum.set_property("post-messages", true);
+        Ok(spectrum)
+    }
+
+    /// Blank `bands`x1 texture to register as the sub-asset before the stream
+    /// starts. Each texel holds the normalized magnitude of a band in red.
+    pub fn placeholder(bands: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: bands,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0],
+            TextureFormat::R8Unorm,
+        )
+    }
+}
+
+/// Writes the spectrum of each stream into its texture sub-asset.
+pub(crate) fn update_spectrum_textures(
+    appsinks: Res<Assets<AppSinkImage>>,
+    spectra: Res<VideoAudioSpectra>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !spectra.is_changed() {
+        return;
+    }
+    for (id, appsink) in appsinks.iter() {
+        let texture = match &appsink.spectrum_texture {
+            Some(texture) => texture,
+            None => continue,
+        };
+        let spectrum = match spectra.get(&Handle::weak(id)) {
+            Some(spectrum) => spectrum,
+            None => continue,
+        };
+        if let Some(image) = images.get_mut(texture) {
+            for (texel, magnitude) in image.data.iter_mut().zip(spectrum.normalized()) {
+                *texel = (magnitude * 255.0) as u8;
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bevy::asset::HandleId;
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Counters updated from the streaming thread and the upload path.
+#[derive(Debug, Default)]
+pub struct StreamStats {
+    /// Samples handed over by the appsink.
+    pub received: AtomicU64,
+    /// Frames copied into a Bevy `Image`.
+    pub uploaded: AtomicU64,
+    /// Frames that were overwritten before they could be uploaded.
+    pub dropped: AtomicU64,
+    /// Bytes produced by the source element, before decoding.
+    pub bytes: AtomicU64,
+    /// Latency of the last sample, from capture to the appsink.
+    pub latency_ns: AtomicU64,
+    /// Incremented every time the frame buffer changes.
+    pub(crate) serial: AtomicU64,
+    /// Value of `serial` at the last upload.
+    pub(crate) uploaded_serial: AtomicU64,
+    /// Dropped, rejected or late frames since the last frame that made it
+    /// to the texture on time.
+    pub consecutive_missed: AtomicU64,
+}
+
+/// Point-in-time copy of [`StreamStats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StatsSnapshot {
+    pub received: u64,
+    pub uploaded: u64,
+    pub dropped: u64,
+    pub bytes: u64,
+    pub latency_ns: u64,
+}
+
+impl StreamStats {
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            received: self.received.load(Ordering::Relaxed),
+            uploaded: self.uploaded.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            latency_ns: self.latency_ns.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Marks the frame buffer as holding a new frame.
+    pub(crate) fn frame_written(&self) {
+        self.serial.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an upload and returns whether there was anything new to upload.
+    pub(crate) fn frame_uploaded(&self) -> bool {
+        let serial = self.serial.load(Ordering::Relaxed);
+        let previous = self.uploaded_serial.swap(serial, Ordering::Relaxed);
+        if serial == previous {
+            return false;
+        }
+        let dropped = serial - previous - 1;
+        self.uploaded.fetch_add(1, Ordering::Relaxed);
+        self.dropped.fetch_add(dropped, Ordering::Relaxed);
+        if dropped > 0 {
+            self.frame_missed(dropped);
+        } else {
+            self.consecutive_missed.store(0, Ordering::Relaxed);
+        }
+        true
+    }
+
+    /// Records frames that were dropped, rejected or arrived late.
+    pub(crate) fn frame_missed(&self, count: u64) {
+        self.consecutive_missed.fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+/// Publishes per-stream [`Diagnostic`]s: received and uploaded frames per
+/// second, dropped frames, latency and source bitrate.
+///
+/// Add `LogDiagnosticsPlugin` to see them in the log.
+#[derive(Default)]
+pub struct VideoDiagnosticsPlugin;
+
+impl Plugin for VideoDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(update_stream_diagnostics);
+    }
+}
+
+const STREAM_DIAGNOSTICS_BASE: u128 = 0x5f1c_32a9_7e04_4b8d_9c61_0d2e_8a47_0000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum StreamMetric {
+    ReceivedFps,
+    UploadedFps,
+    Dropped,
+    LatencyMs,
+    BitrateKbps,
+}
+
+impl StreamMetric {
+    pub const ALL: [StreamMetric; 5] = [
+        StreamMetric::ReceivedFps,
+        StreamMetric::UploadedFps,
+        StreamMetric::Dropped,
+        StreamMetric::LatencyMs,
+        StreamMetric::BitrateKbps,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            StreamMetric::ReceivedFps => "in fps",
+            StreamMetric::UploadedFps => "up fps",
+            StreamMetric::Dropped => "dropped",
+            StreamMetric::LatencyMs => "latency",
+            StreamMetric::BitrateKbps => "bitrate",
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            StreamMetric::ReceivedFps | StreamMetric::UploadedFps => "fps",
+            StreamMetric::Dropped => "",
+            StreamMetric::LatencyMs => "ms",
+            StreamMetric::BitrateKbps => "kbit/s",
+        }
+    }
+}
+
+/// Id of the diagnostic tracking `metric` for the stream `handle`, for use
+/// with [`Diagnostics::get`].
+pub fn stream_diagnostic_id(handle: impl Into<HandleId>, metric: StreamMetric) -> DiagnosticId {
+    let mut hasher = DefaultHasher::new();
+    handle.into().hash(&mut hasher);
+    DiagnosticId::from_u128(
+        STREAM_DIAGNOSTICS_BASE ^ ((hasher.finish() as u128) << 16) ^ metric as u128,
+    )
+}
+
+fn update_stream_diagnostics(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut diagnostics: ResMut<Diagnostics>,
+    mut previous: Local<HashMap<HandleId, StatsSnapshot>>,
+) {
+    let dt = time.delta_seconds_f64();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (id, appsink) in appsinks.iter() {
+        let now = appsink.stats.snapshot();
+        let before = match previous.insert(id, now) {
+            Some(before) => before,
+            None => {
+                let label = asset_server
+                    .get_handle_path(id)
+                    .and_then(|path| {
+                        path.path()
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().into_owned())
+                    })
+                    .unwrap_or_else(|| String::from("video"));
+                for metric in StreamMetric::ALL {
+                    diagnostics.add(
+                        Diagnostic::new(
+                            stream_diagnostic_id(id, metric),
+                            format!("{} {}", label, metric.name()),
+                            20,
+                        )
+                        .with_suffix(metric.suffix()),
+                    );
+                }
+                continue;
+            }
+        };
+
+        let rate = |now: u64, before: u64| now.saturating_sub(before) as f64 / dt;
+        for metric in StreamMetric::ALL {
+            let value = match metric {
+                StreamMetric::ReceivedFps => rate(now.received, before.received),
+                StreamMetric::UploadedFps => rate(now.uploaded, before.uploaded),
+                StreamMetric::Dropped => now.dropped as f64,
+                StreamMetric::LatencyMs => now.latency_ns as f64 / 1_000_000.0,
+                StreamMetric::BitrateKbps => rate(now.bytes, before.bytes) * 8.0 / 1000.0,
+            };
+            diagnostics.add_measurement(stream_diagnostic_id(id, metric), value);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use gst::prelude::*;
+
+use crate::appsink::AppSinkImage;
+
+/// Sent when a stream received new tags from its pipeline.
+pub struct VideoTagsUpdated {
+    pub handle: Handle<AppSinkImage>,
+}
+
+/// Merges `tags` into `into`, returning whether anything changed.
+pub(crate) fn merge_tags(into: &mut HashMap<String, String>, tags: &gst::TagListRef) -> bool {
+    let mut changed = false;
+    for (name, value) in tags.iter() {
+        let value = match value.get::<String>() {
+            Ok(value) => value,
+            Err(_) => match value.serialize() {
+                Ok(value) => value.to_string(),
+                Err(_) => continue,
+            },
+        };
+        if into.get(name).map_or(true, |old| *old != value) {
+            into.insert(name.to_string(), value);
+            changed = true;
+        }
+    }
+    changed
+}
+
+impl AppSinkImage {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.get(name).map(String::as_str)
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.tag("title")
+    }
+
+    pub fn artist(&self) -> Option<&str> {
+        self.tag("artist")
+    }
+
+    /// Video codec if the stream has one, the audio codec otherwise.
+    pub fn codec(&self) -> Option<&str> {
+        self.tag("video-codec").or_else(|| self.tag("audio-codec"))
+    }
+
+    pub fn container_format(&self) -> Option<&str> {
+        self.tag("container-format")
+    }
+
+    /// Nominal bitrate in bits per second.
+    pub fn bitrate(&self) -> Option<u32> {
+        self.tag("bitrate")
+            .or_else(|| self.tag("nominal-bitrate"))
+            .and_then(|bitrate| bitrate.parse().ok())
+    }
+}
@@ -0,0 +1,241 @@
+use gst::prelude::*;
+
+use crate::appsink::{make_element, AppSinkImage};
+use crate::error::VideoError;
+
+/// Name of the `textoverlay` element in the pipeline.
+const TEXT_OVERLAY_NAME: &str = "label";
+/// Name of the `timeoverlay` or `clockoverlay` element in the pipeline.
+const TIME_OVERLAY_NAME: &str = "time";
+
+/// Where on the frame a [`TextOverlay`] is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlayPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    Center,
+}
+
+impl OverlayPosition {
+    pub fn parse(value: &str) -> Option<OverlayPosition> {
+        match value {
+            "top-left" => Some(OverlayPosition::TopLeft),
+            "top-right" => Some(OverlayPosition::TopRight),
+            "bottom-left" => Some(OverlayPosition::BottomLeft),
+            "bottom-right" => Some(OverlayPosition::BottomRight),
+            "center" => Some(OverlayPosition::Center),
+            _ => None,
+        }
+    }
+
+    /// Values of the `valignment` and `halignment` properties.
+    fn alignment(&self) -> (&'static str, &'static str) {
+        match self {
+            OverlayPosition::TopLeft => ("top", "left"),
+            OverlayPosition::TopRight => ("top", "right"),
+            OverlayPosition::BottomLeft => ("bottom", "left"),
+            OverlayPosition::BottomRight => ("bottom", "right"),
+            OverlayPosition::Center => ("center", "center"),
+        }
+    }
+}
+
+/// Text drawn onto every frame by `textoverlay`, before the frame reaches the
+/// appsink, thumbnails or anything else reading the stream.
+#[derive(Debug, Clone)]
+pub struct TextOverlay {
+    pub text: String,
+    /// Pango font description such as `Sans Bold 12`, the element's default
+    /// if `None`.
+    pub font: Option<String>,
+    pub position: OverlayPosition,
+    pub outline: bool,
+}
+
+impl Default for TextOverlay {
+    fn default() -> Self {
+        TextOverlay {
+            text: String::new(),
+            font: None,
+            position: OverlayPosition::TopLeft,
+            outline: true,
+        }
+    }
+}
+
+impl TextOverlay {
+    pub fn new(text: impl Into<String>) -> Self {
+        TextOverlay {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Inserts a `textoverlay` between `sink` and the element feeding it.
+    pub(crate) fn attach(
+        &self,
+        pipeline: &gst::Pipeline,
+        sink: &gst::Element,
+    ) -> Result<(), VideoError> {
+        let overlay = make_element("textoverlay")?;
+        overlay.set_property("name", TEXT_OVERLAY_NAME);
+        self.apply(&overlay);
+        insert_before(pipeline, sink, &overlay)
+    }
+
+    fn apply(&self, overlay: &gst::Element) {
+        let (valignment, halignment) = self.position.alignment();
+        overlay.set_property("text", &self.text);
+        if let Some(font) = &self.font {
+            overlay.set_property("font-desc", font);
+        }
+        overlay.set_property_from_str("valignment", valignment);
+        overlay.set_property_from_str("halignment", halignment);
+        overlay.set_property("draw-outline", self.outline);
+    }
+}
+
+/// What a [`TimeOverlay`] shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeSource {
+    /// Timestamp of each buffer, with `timeoverlay`. Useful for spotting
+    /// latency and dropped frames.
+    BufferTime,
+    /// Current local time formatted with a `strftime` format such as
+    /// `%Y-%m-%d %H:%M:%S`, with `clockoverlay`.
+    WallClock { format: String },
+}
+
+impl TimeSource {
+    fn factory(&self) -> &'static str {
+        match self {
+            TimeSource::BufferTime => "timeoverlay",
+            TimeSource::WallClock { .. } => "clockoverlay",
+        }
+    }
+}
+
+/// Time drawn onto every frame, for debugging or surveillance-style
+/// displays.
+#[derive(Debug, Clone)]
+pub struct TimeOverlay {
+    pub source: TimeSource,
+    pub position: OverlayPosition,
+}
+
+impl TimeOverlay {
+    pub fn buffer_time() -> Self {
+        TimeOverlay {
+            source: TimeSource::BufferTime,
+            position: OverlayPosition::BottomLeft,
+        }
+    }
+
+    pub fn wall_clock(format: impl Into<String>) -> Self {
+        TimeOverlay {
+            source: TimeSource::WallClock {
+                format: format.into(),
+            },
+            position: OverlayPosition::BottomLeft,
+        }
+    }
+
+    /// Inserts a `timeoverlay` or `clockoverlay` between `sink` and the
+    /// element feeding it.
+    pub(crate) fn attach(
+        &self,
+        pipeline: &gst::Pipeline,
+        sink: &gst::Element,
+    ) -> Result<(), VideoError> {
+        let overlay = make_element(self.source.factory())?;
+        overlay.set_property("name", TIME_OVERLAY_NAME);
+        self.apply(&overlay);
+        insert_before(pipeline, sink, &overlay)
+    }
+
+    fn apply(&self, overlay: &gst::Element) {
+        let (valignment, halignment) = self.position.alignment();
+        if let TimeSource::WallClock { format } = &self.source {
+            overlay.set_property("time-format", format);
+        }
+        overlay.set_property_from_str("valignment", valignment);
+        overlay.set_property_from_str("halignment", halignment);
+        overlay.set_property("silent", false);
+    }
+}
+
+/// Links `element` between `sink` and the element feeding it.
+fn insert_before(
+    pipeline: &gst::Pipeline,
+    sink: &gst::Element,
+    element: &gst::Element,
+) -> Result<(), VideoError> {
+    let sink_pad = sink
+        .static_pad("sink")
+        .expect("appsink without sink pad. Shouldn't happen!");
+    let upstream = sink_pad
+        .peer()
+        .and_then(|pad| pad.parent_element())
+        .ok_or(VideoError::NotRunning)?;
+    upstream.unlink(sink);
+    pipeline.add(element)?;
+    gst::Element::link_many(&[&upstream, element, sink])?;
+    Ok(())
+}
+
+impl AppSinkImage {
+    /// Changes the text overlay. Applied to the running pipeline if it has
+    /// one, otherwise when the stream next starts.
+    pub fn set_text_overlay(&mut self, text_overlay: Option<TextOverlay>) {
+        let overlay = self
+            .pipeline
+            .as_ref()
+            .and_then(|pipeline| pipeline.by_name(TEXT_OVERLAY_NAME));
+        match (&text_overlay, overlay) {
+            (Some(text_overlay), Some(overlay)) => text_overlay.apply(&overlay),
+            (None, Some(overlay)) => overlay.set_property("text", ""),
+            // Adding the element needs a restart.
+            (Some(_), None) if self.pipeline.is_some() => {
+                self.text_overlay = text_overlay;
+                if let Err(err) = self.start() {
+                    self.error = Some(err);
+                }
+                return;
+            }
+            _ => {}
+        }
+        self.text_overlay = text_overlay;
+    }
+
+    /// Shows, changes or hides the time overlay. Applied to the running
+    /// pipeline if it has a matching element, otherwise by restarting it.
+    pub fn set_time_overlay(&mut self, time_overlay: Option<TimeOverlay>) {
+        let overlay = self
+            .pipeline
+            .as_ref()
+            .and_then(|pipeline| pipeline.by_name(TIME_OVERLAY_NAME));
+        let factory = overlay
+            .as_ref()
+            .and_then(|overlay| overlay.factory())
+            .map(|factory| factory.name().to_string());
+        match (&time_overlay, overlay) {
+            (Some(time_overlay), Some(overlay))
+                if factory.as_deref() == Some(time_overlay.source.factory()) =>
+            {
+                time_overlay.apply(&overlay)
+            }
+            (None, Some(overlay)) => overlay.set_property("silent", true),
+            (Some(_), _) if self.pipeline.is_some() => {
+                self.time_overlay = time_overlay;
+                if let Err(err) = self.start() {
+                    self.error = Some(err);
+                }
+                return;
+            }
+            _ => {}
+        }
+        self.time_overlay = time_overlay;
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use gst::prelude::*;
+
+use crate::appsink::{make_element, AppSinkImage};
+use crate::error::VideoError;
+
+/// Label of the thumbnail sub-asset, load it with
+/// `asset_server.load("camera.sinkimage#thumbnail")`.
+pub const THUMBNAIL_LABEL: &str = "thumbnail";
+
+/// A downscaled copy of the stream, produced by a second appsink so lists of
+/// many streams can show previews without uploading full frames.
+#[derive(Debug)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    /// The sub-asset the thumbnail is uploaded to.
+    pub image: Handle<Image>,
+    raw: Arc<RwLock<Vec<u8>>>,
+    serial: Arc<AtomicU64>,
+    uploaded_serial: u64,
+}
+
+impl Thumbnail {
+    pub fn new(width: u32, height: u32, image: Handle<Image>) -> Self {
+        Thumbnail {
+            width,
+            height,
+            image,
+            raw: Arc::new(RwLock::new(vec![0; (width * height * 4) as usize])),
+            serial: Arc::new(AtomicU64::new(0)),
+            uploaded_serial: 0,
+        }
+    }
+
+    /// Blank image to register as the sub-asset before the stream starts.
+    pub fn placeholder(width: u32, height: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// Splits the stream feeding `sink` with a tee and adds a branch that
+    /// scales it down into a second appsink.
+    pub(crate) fn attach(
+        &self,
+        pipeline: &gst::Pipeline,
+        sink: &gst::Element,
+    ) -> Result<(), VideoError> {
+        let sink_pad = sink
+            .static_pad("sink")
+            .expect("appsink without sink pad. Shouldn't happen!");
+        let upstream = sink_pad
+            .peer()
+            .and_then(|pad| pad.parent_element())
+            .ok_or(VideoError::NotRunning)?;
+        upstream.unlink(sink);
+
+        let tee = make_element("tee")?;
+        let main_queue = make_element("queue")?;
+        let queue = make_element("queue")?;
+        let scale = make_element("videoscale")?;
+        let convert = make_element("videoconvert")?;
+        let thumbnail_sink = make_element("appsink")?;
+        pipeline.add_many(&[&tee, &main_queue, &queue, &scale, &convert, &thumbnail_sink])?;
+
+        gst::Element::link_many(&[&upstream, &tee, &main_queue, sink])?;
+        gst::Element::link_many(&[&tee, &queue, &scale, &convert, &thumbnail_sink])?;
+        // A slow thumbnail must never hold back the main stream.
+        queue.set_property_from_str("leaky", "downstream");
+        queue.set_property("max-size-buffers", 1u32);
+
+        let appsink = thumbnail_sink
+            .dynamic_cast::<gst_app::AppSink>()
+            .expect("Sink element is expected to be an appsink!");
+        appsink.set_caps(Some(
+            &gst::Caps::builder("video/x-raw")
+                .field("format", "RGBA")
+                .field("width", self.width as i32)
+                .field("height", self.height as i32)
+                .build(),
+        ));
+        appsink.set_property("drop", true);
+        appsink.set_property("max-buffers", 1u32);
+
+        let raw = self.raw.clone();
+        let serial = self.serial.clone();
+        let row_bytes = self.width as usize * 4;
+        appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |appsink| {
+                    let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                    let (buffer, info) = match (
+                        sample.buffer(),
+                        sample
+                            .caps()
+                            .and_then(|caps| gst_video::VideoInfo::from_caps(caps).ok()),
+                    ) {
+                        (Some(buffer), Some(info)) => (buffer, info),
+                        _ => return Ok(gst::FlowSuccess::Ok),
+                    };
+                    let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+                    let stride = info.stride()[0] as usize;
+                    let mut raw = raw.write().unwrap();
+                    for (dest, src) in raw.chunks_exact_mut(row_bytes).zip(map.chunks(stride)) {
+                        if src.len() >= row_bytes {
+                            dest.copy_from_slice(&src[..row_bytes]);
+                        }
+                    }
+                    serial.fetch_add(1, Ordering::Relaxed);
+                    Ok(gst::FlowSuccess::Ok)
+                })
+                .build(),
+        );
+        Ok(())
+    }
+}
+
+/// Uploads new thumbnails into their sub-assets, together with the frame of
+/// the main stream so both change in the same Bevy frame.
+pub(crate) fn update_thumbnails(
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let ids: Vec<HandleId> = appsinks.ids().collect();
+    for id in ids {
+        let (thumbnail, latch) = match appsinks.get_mut(id) {
+            Some(AppSinkImage {
+                thumbnail: Some(thumbnail),
+                latch,
+                ..
+            }) if latch.is_fresh() => (thumbnail, latch),
+            _ => continue,
+        };
+        let serial = thumbnail.serial.load(Ordering::Relaxed);
+        if serial == thumbnail.uploaded_serial {
+            continue;
+        }
+        if let Some(image) = images.get_mut(&thumbnail.image) {
+            image.data.clear();
+            image
+                .data
+                .extend_from_slice(&thumbnail.raw.read().unwrap()[..]);
+            thumbnail.uploaded_serial = serial;
+            if let Some((frame_serial, _)) = latch.fresh() {
+                latch.shown(thumbnail.image.id, frame_serial);
+            }
+        }
+    }
+}
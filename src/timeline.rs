@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::VideoError;
+
+/// Entries kept per stream when no other capacity is configured.
+pub const DEFAULT_TIMELINE_CAPACITY: usize = 256;
+
+/// Something that happened to a stream, worth having in a bug report.
+#[derive(Debug, Clone)]
+pub enum TimelineEvent {
+    Started,
+    Stopped,
+    StateChanged { old: gst::State, new: gst::State },
+    Caps(String),
+    Warning(String),
+    Error(String),
+    Eos,
+}
+
+impl fmt::Display for TimelineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineEvent::Started => write!(f, "started"),
+            TimelineEvent::Stopped => write!(f, "stopped"),
+            TimelineEvent::StateChanged { old, new } => write!(f, "state {:?} -> {:?}", old, new),
+            TimelineEvent::Caps(caps) => write!(f, "caps {}", caps),
+            TimelineEvent::Warning(warning) => write!(f, "warning {}", warning),
+            TimelineEvent::Error(error) => write!(f, "error {}", error),
+            TimelineEvent::Eos => write!(f, "end of stream"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelineEntry {
+    pub at: SystemTime,
+    pub event: TimelineEvent,
+}
+
+impl fmt::Display for TimelineEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let since_epoch = self.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        write!(
+            f,
+            "{}.{:03} {}",
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis(),
+            self.event
+        )
+    }
+}
+
+/// Ring buffer of the most recent events of a stream.
+#[derive(Debug)]
+pub struct Timeline {
+    entries: VecDeque<TimelineEntry>,
+    capacity: usize,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline::with_capacity(DEFAULT_TIMELINE_CAPACITY)
+    }
+}
+
+impl Timeline {
+    pub fn with_capacity(capacity: usize) -> Timeline {
+        Timeline {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, event: TimelineEvent) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TimelineEntry {
+            at: SystemTime::now(),
+            event,
+        });
+    }
+
+    /// Entries from oldest to newest.
+    pub fn entries(&self) -> impl Iterator<Item = &TimelineEntry> {
+        self.entries.iter()
+    }
+
+    /// Writes one entry per line to `path`, timestamps in seconds since the
+    /// Unix epoch.
+    pub fn dump(&self, path: impl AsRef<Path>) -> Result<(), VideoError> {
+        let mut file = BufWriter::new(File::create(path)?);
+        for entry in self.entries() {
+            writeln!(file, "{}", entry)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
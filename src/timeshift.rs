@@ -0,0 +1,346 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use gst::prelude::*;
+
+use crate::appsink::{make_element, AppSinkImage, HEIGHT, WIDTH};
+use crate::error::VideoError;
+
+/// Most encoded bytes kept by [`AppSinkImage::enable_time_shift`], a few
+/// minutes of JPEG frames at texture size and 30 fps.
+pub const DEFAULT_TIME_SHIFT_BYTES: usize = 32 * 1024 * 1024;
+
+/// Name of the `valve` that lets frames into the recording branch.
+const TIME_SHIFT_VALVE_NAME: &str = "time_shift_valve";
+
+/// Keeps the last `duration` of frames so a live stream can be paused,
+/// rewound and played back with a delay.
+///
+/// Frames are recorded by a branch of the pipeline that encodes them to JPEG
+/// at texture size. Every frame is a key frame, so going back in time needs
+/// no seeking, and the history is a ring buffer bounded by `max_bytes` of
+/// encoded data as well as by `duration`. Frames are only decoded when
+/// played back.
+#[derive(Debug)]
+pub struct TimeShift {
+    pub duration: Duration,
+    pub max_bytes: usize,
+    /// JPEG frames with their arrival time, oldest first.
+    frames: VecDeque<(Instant, Arc<Vec<u8>>)>,
+    /// Sum of the sizes of `frames`.
+    bytes: usize,
+    mode: TimeShiftMode,
+    /// Arrival time of the frame currently shown while not live.
+    shown: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeShiftMode {
+    Live,
+    Paused { position: Instant },
+    Delayed { delay: Duration },
+}
+
+impl TimeShift {
+    pub fn new(duration: Duration, max_bytes: usize) -> Self {
+        TimeShift {
+            duration,
+            max_bytes,
+            frames: VecDeque::new(),
+            bytes: 0,
+            mode: TimeShiftMode::Live,
+            shown: None,
+        }
+    }
+
+    /// Whether the stream is shown as it arrives.
+    pub fn is_live(&self) -> bool {
+        self.mode == TimeShiftMode::Live
+    }
+
+    /// How far playback is behind the live stream.
+    pub fn delay(&self) -> Duration {
+        match self.mode {
+            TimeShiftMode::Live => Duration::ZERO,
+            TimeShiftMode::Paused { position } => position.elapsed(),
+            TimeShiftMode::Delayed { delay } => delay,
+        }
+    }
+
+    /// Length of the recorded history.
+    pub fn available(&self) -> Duration {
+        self.frames
+            .front()
+            .map_or(Duration::ZERO, |(at, _)| at.elapsed())
+    }
+
+    /// Bytes of encoded frames in the history.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Records an encoded frame, dropping the ones that are too old or don't
+    /// fit anymore.
+    pub(crate) fn push(&mut self, jpeg: Vec<u8>) {
+        let now = Instant::now();
+        self.bytes += jpeg.len();
+        self.frames.push_back((now, Arc::new(jpeg)));
+        while let Some((at, frame)) = self.frames.front() {
+            if now.duration_since(*at) <= self.duration && self.bytes <= self.max_bytes {
+                break;
+            }
+            self.bytes -= frame.len();
+            self.frames.pop_front();
+        }
+    }
+
+    fn position(&self) -> Option<Instant> {
+        let position = match self.mode {
+            TimeShiftMode::Live => return None,
+            TimeShiftMode::Paused { position } => position,
+            TimeShiftMode::Delayed { delay } => Instant::now().checked_sub(delay)?,
+        };
+        let oldest = self.frames.front()?.0;
+        Some(position.max(oldest))
+    }
+
+    /// The encoded frame to show at the current playback position, if it
+    /// differs from the one shown last.
+    pub(crate) fn next_frame(&mut self) -> Option<Arc<Vec<u8>>> {
+        let position = self.position()?;
+        let (at, frame) = self
+            .frames
+            .iter()
+            .rev()
+            .find(|(at, _)| *at <= position)
+            .or_else(|| self.frames.front())
+            .map(|(at, frame)| (*at, frame.clone()))?;
+        if self.shown == Some(at) {
+            return None;
+        }
+        self.shown = Some(at);
+        Some(frame)
+    }
+
+    fn pause(&mut self) {
+        let position = self
+            .position()
+            .unwrap_or_else(|| self.frames.back().map_or_else(Instant::now, |(at, _)| *at));
+        self.mode = TimeShiftMode::Paused { position };
+    }
+
+    fn resume(&mut self) {
+        if let TimeShiftMode::Paused { position } = self.mode {
+            self.mode = TimeShiftMode::Delayed {
+                delay: position.elapsed(),
+            };
+        }
+    }
+
+    fn seek_back(&mut self, offset: Duration) {
+        let delay = (self.delay() + offset).min(self.available());
+        self.set_delay(delay);
+    }
+
+    fn seek_forward(&mut self, offset: Duration) {
+        let delay = self.delay().saturating_sub(offset);
+        self.set_delay(delay);
+    }
+
+    fn set_delay(&mut self, delay: Duration) {
+        self.shown = None;
+        self.mode = match self.mode {
+            _ if delay.is_zero() => TimeShiftMode::Live,
+            TimeShiftMode::Paused { .. } => TimeShiftMode::Paused {
+                position: Instant::now()
+                    .checked_sub(delay)
+                    .unwrap_or_else(Instant::now),
+            },
+            _ => TimeShiftMode::Delayed { delay },
+        };
+    }
+}
+
+impl AppSinkImage {
+    /// Starts keeping the last `duration` of frames, allowing to pause and
+    /// rewind the stream. Replaces any existing history.
+    pub fn enable_time_shift(&self, duration: Duration) {
+        self.enable_time_shift_with_limit(duration, DEFAULT_TIME_SHIFT_BYTES);
+    }
+
+    /// Like [`AppSinkImage::enable_time_shift`], keeping at most `max_bytes`
+    /// of encoded frames.
+    pub fn enable_time_shift_with_limit(&self, duration: Duration, max_bytes: usize) {
+        *self.time_shift.lock().unwrap() = Some(TimeShift::new(duration, max_bytes));
+        self.set_recording(true);
+    }
+
+    pub fn disable_time_shift(&self) {
+        *self.time_shift.lock().unwrap() = None;
+        self.set_recording(false);
+    }
+
+    /// Opens or closes the recording branch of the running pipeline, so
+    /// frames are only encoded while time shift is enabled.
+    fn set_recording(&self, recording: bool) {
+        if let Some(valve) = self
+            .pipeline
+            .as_ref()
+            .and_then(|pipeline| pipeline.by_name(TIME_SHIFT_VALVE_NAME))
+        {
+            valve.set_property("drop", !recording);
+        }
+    }
+
+    /// Freezes playback. The stream keeps being recorded in the background.
+    pub fn pause_live(&self) -> Result<(), VideoError> {
+        self.with_time_shift(TimeShift::pause)
+    }
+
+    /// Continues playback from where it was paused, behind the live stream.
+    pub fn resume_live(&self) -> Result<(), VideoError> {
+        self.with_time_shift(TimeShift::resume)
+    }
+
+    /// Moves playback `offset` further into the past, up to the oldest
+    /// recorded frame.
+    pub fn rewind(&self, offset: Duration) -> Result<(), VideoError> {
+        self.with_time_shift(|time_shift| time_shift.seek_back(offset))
+    }
+
+    /// Moves playback `offset` closer to live, jumping back to live when it
+    /// is reached.
+    pub fn fast_forward(&self, offset: Duration) -> Result<(), VideoError> {
+        self.with_time_shift(|time_shift| time_shift.seek_forward(offset))
+    }
+
+    pub fn go_live(&self) -> Result<(), VideoError> {
+        self.with_time_shift(|time_shift| time_shift.set_delay(Duration::ZERO))
+    }
+
+    /// How far playback is behind the live stream, `None` without time shift.
+    pub fn time_shift_delay(&self) -> Option<Duration> {
+        self.time_shift
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(TimeShift::delay)
+    }
+
+    fn with_time_shift(&self, f: impl FnOnce(&mut TimeShift)) -> Result<(), VideoError> {
+        let mut time_shift = self.time_shift.lock().unwrap();
+        let time_shift = time_shift
+            .as_mut()
+            .ok_or_else(|| VideoError::Unsupported(String::from("time shift is not enabled")))?;
+        f(time_shift);
+        Ok(())
+    }
+}
+
+/// Splits the stream feeding `sink` with a tee and adds a branch encoding
+/// the frames into the history of `time_shift`. The branch is closed while
+/// time shift is disabled.
+pub(crate) fn attach_recorder(
+    pipeline: &gst::Pipeline,
+    sink: &gst::Element,
+    time_shift: Arc<Mutex<Option<TimeShift>>>,
+) -> Result<(), VideoError> {
+    // Everything is created first, so a missing plugin leaves the pipeline
+    // untouched.
+    let tee = make_element("tee")?;
+    let main_queue = make_element("queue")?;
+    let queue = make_element("queue")?;
+    let valve = make_element("valve")?;
+    let convert = make_element("videoconvert")?;
+    let encoder = make_element("jpegenc")?;
+    let recorder = make_element("appsink")?;
+
+    let sink_pad = sink
+        .static_pad("sink")
+        .expect("appsink without sink pad. Shouldn't happen!");
+    let upstream = sink_pad
+        .peer()
+        .and_then(|pad| pad.parent_element())
+        .ok_or_else(|| VideoError::Unsupported(String::from("appsink is not linked")))?;
+    upstream.unlink(sink);
+
+    valve.set_property("name", TIME_SHIFT_VALVE_NAME);
+    pipeline.add_many(&[
+        &tee,
+        &main_queue,
+        &queue,
+        &valve,
+        &convert,
+        &encoder,
+        &recorder,
+    ])?;
+
+    gst::Element::link_many(&[&upstream, &tee, &main_queue, sink])?;
+    gst::Element::link_many(&[&tee, &queue, &valve, &convert, &encoder, &recorder])?;
+    // A slow encoder must never hold back the main stream.
+    queue.set_property_from_str("leaky", "downstream");
+    queue.set_property("max-size-buffers", 2u32);
+    valve.set_property("drop", time_shift.lock().unwrap().is_none());
+
+    let appsink = recorder
+        .dynamic_cast::<gst_app::AppSink>()
+        .expect("Sink element is expected to be an appsink!");
+    appsink.set_callbacks(
+        gst_app::AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                let buffer = match sample.buffer() {
+                    Some(buffer) => buffer,
+                    None => return Ok(gst::FlowSuccess::Ok),
+                };
+                let map = buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+                if let Some(time_shift) = time_shift.lock().unwrap().as_mut() {
+                    time_shift.push(map.to_vec());
+                }
+                Ok(gst::FlowSuccess::Ok)
+            })
+            .build(),
+    );
+    Ok(())
+}
+
+/// Decodes a recorded frame into the RGBA layout of `image_raw`.
+fn decode(jpeg: &[u8]) -> Result<Vec<u8>, VideoError> {
+    let image = image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg)?;
+    if (image.width(), image.height()) != (WIDTH, HEIGHT) {
+        return Err(VideoError::Unsupported(format!(
+            "recorded frame of {}x{}",
+            image.width(),
+            image.height()
+        )));
+    }
+    Ok(image.to_rgba8().into_raw())
+}
+
+/// Shows the recorded frame at the playback position of streams that are
+/// not live.
+pub(crate) fn play_time_shift(appsinks: Res<Assets<AppSinkImage>>) {
+    for (_, appsink) in appsinks.iter() {
+        let jpeg = match appsink.time_shift.lock().unwrap().as_mut() {
+            Some(time_shift) => time_shift.next_frame(),
+            None => None,
+        };
+        // Decoded without holding the lock the recorder writes through.
+        let frame = match jpeg.map(|jpeg| decode(&jpeg)) {
+            Some(Ok(frame)) => frame,
+            Some(Err(err)) => {
+                warn!("Failed to play back recorded frame: {}", err);
+                continue;
+            }
+            None => continue,
+        };
+        let mut image_raw = appsink.image_raw.write().unwrap();
+        image_raw.copy_from_slice(&frame);
+        // The history keeps arrival times only.
+        *appsink.frame_pts.write().unwrap() = None;
+        drop(image_raw);
+        appsink.stats.frame_written();
+    }
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::material::{VideoMaterial, VideoTexture};
+use crate::player::VideoPlayer;
+
+/// How the old stream gives way to the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransitionKind {
+    Crossfade,
+    /// The new stream slides in from the left.
+    Wipe,
+    /// Fade the old stream to black, then the new one in.
+    DipToBlack,
+}
+
+/// Animates switching the stream of a [`VideoPlayer`] with a
+/// [`VideoMaterial`] instead of cutting.
+///
+/// The last frame of the old stream is kept and blended with the new
+/// stream over `duration`.
+#[derive(Component, Debug, Clone)]
+pub struct VideoTransition {
+    pub kind: TransitionKind,
+    pub duration: Duration,
+    stream: Option<Handle<AppSinkImage>>,
+    from: Option<Handle<Image>>,
+    elapsed: Option<Duration>,
+}
+
+impl VideoTransition {
+    pub fn new(kind: TransitionKind, duration: Duration) -> Self {
+        VideoTransition {
+            kind,
+            duration,
+            stream: None,
+            from: None,
+            elapsed: None,
+        }
+    }
+
+    /// Whether a transition is playing.
+    pub fn is_running(&self) -> bool {
+        self.elapsed.is_some()
+    }
+}
+
+/// Freezes the current frame and starts the transition when the stream of a
+/// player changes.
+pub(crate) fn start_transitions(
+    mut query: Query<
+        (
+            &VideoPlayer,
+            &VideoTexture,
+            &Handle<VideoMaterial>,
+            &mut VideoTransition,
+        ),
+        Changed<VideoPlayer>,
+    >,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    for (player, texture, material, mut transition) in query.iter_mut() {
+        let previous = transition.stream.replace(player.stream.clone_weak());
+        if previous.map_or(true, |previous| previous == player.stream) {
+            continue;
+        }
+        let frame = match images.get(&texture.0) {
+            Some(image) => image.clone(),
+            None => continue,
+        };
+        let from = match transition.from.clone() {
+            Some(from) => {
+                if let Some(image) = images.get_mut(&from) {
+                    *image = frame;
+                }
+                from
+            }
+            None => images.add(frame),
+        };
+        transition.from = Some(from.clone());
+        transition.elapsed = Some(Duration::ZERO);
+        if let Some(material) = materials.get_mut(material) {
+            material.previous = Some(from);
+            material.transition = Some((transition.kind, 0.0));
+        }
+    }
+}
+
+pub(crate) fn advance_transitions(
+    time: Res<Time>,
+    mut query: Query<(&Handle<VideoMaterial>, &mut VideoTransition)>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    for (material, mut transition) in query.iter_mut() {
+        let elapsed = match transition.elapsed {
+            Some(elapsed) => elapsed + time.delta(),
+            None => continue,
+        };
+        let progress = elapsed.as_secs_f32() / transition.duration.as_secs_f32().max(f32::EPSILON);
+        let material = materials.get_mut(material);
+        if progress >= 1.0 {
+            transition.elapsed = None;
+            if let Some(material) = material {
+                material.previous = None;
+                material.transition = None;
+            }
+        } else {
+            transition.elapsed = Some(elapsed);
+            if let Some(material) = material {
+                material.transition = Some((transition.kind, progress));
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use bevy::prelude::*;
+
+use crate::appsink::AppSinkImage;
+use crate::material::VideoTexture;
+use crate::player::VideoPlayer;
+
+/// Keeps [`VideoNodeBundle`]s at the aspect ratio of their streams.
+#[derive(Default)]
+pub struct VideoUiPlugin;
+
+impl Plugin for VideoUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(fit_video_nodes)
+            .add_system(layout_picture_in_picture);
+    }
+}
+
+/// Marks a UI node whose aspect ratio follows its stream.
+#[derive(Component, Debug, Clone, Default)]
+pub struct VideoNode;
+
+/// A stream shown in a bevy_ui layout.
+///
+/// Give the node a width or a height and leave the other `Val::Auto`, it is
+/// derived from the aspect ratio of the video and follows it when the source
+/// changes.
+#[derive(Bundle, Clone)]
+pub struct VideoNodeBundle {
+    pub video_node: VideoNode,
+    pub player: VideoPlayer,
+    pub texture: VideoTexture,
+    #[bundle]
+    pub image: ImageBundle,
+}
+
+impl VideoNodeBundle {
+    /// Creates the texture for `stream`.
+    pub fn new(stream: Handle<AppSinkImage>, style: Style, images: &mut Assets<Image>) -> Self {
+        let texture = images.add(VideoTexture::image());
+        VideoNodeBundle {
+            video_node: VideoNode,
+            player: VideoPlayer { stream },
+            texture: VideoTexture(texture.clone()),
+            image: ImageBundle {
+                style,
+                image: UiImage(texture),
+                ..default()
+            },
+        }
+    }
+}
+
+fn fit_video_nodes(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut nodes: Query<(&VideoPlayer, &mut Style), With<VideoNode>>,
+) {
+    for (player, mut style) in nodes.iter_mut() {
+        let aspect = match appsinks.get(&player.stream) {
+            Some(appsink) => appsink.aspect_ratio(),
+            None => continue,
+        };
+        if style.aspect_ratio != Some(aspect) {
+            style.aspect_ratio = Some(aspect);
+        }
+    }
+}
+
+/// Corner of the main video a picture-in-picture sits in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Placement of a small stream over a [`VideoNode`], such as a self-view in
+/// the corner of a call. Changes are applied to the layout at any time.
+#[derive(Component, Debug, Clone)]
+pub struct PictureInPicture {
+    pub corner: PipCorner,
+    /// Width as a fraction of the main video's width.
+    pub size: f32,
+    /// Distance to the edges of the main video, in pixels.
+    pub margin: f32,
+    /// Width of the frame around the inset, in pixels. Zero for none.
+    pub border: f32,
+    pub border_color: Color,
+}
+
+impl Default for PictureInPicture {
+    fn default() -> Self {
+        PictureInPicture {
+            corner: PipCorner::BottomRight,
+            size: 0.25,
+            margin: 16.0,
+            border: 2.0,
+            border_color: Color::WHITE,
+        }
+    }
+}
+
+impl PictureInPicture {
+    fn style(&self) -> Style {
+        let margin = Val::Px(self.margin);
+        let mut position = UiRect::default();
+        match self.corner {
+            PipCorner::TopLeft => {
+                position.top = margin;
+                position.left = margin;
+            }
+            PipCorner::TopRight => {
+                position.top = margin;
+                position.right = margin;
+            }
+            PipCorner::BottomLeft => {
+                position.bottom = margin;
+                position.left = margin;
+            }
+            PipCorner::BottomRight => {
+                position.bottom = margin;
+                position.right = margin;
+            }
+        }
+        Style {
+            position_type: PositionType::Absolute,
+            position,
+            size: Size::new(Val::Percent(self.size * 100.0), Val::Auto),
+            padding: UiRect::all(Val::Px(self.border)),
+            ..default()
+        }
+    }
+}
+
+/// Spawns `main` as a [`VideoNodeBundle`] with `inset` shown over it as
+/// described by `pip`. Returns the main node; the framed inset is its child
+/// holding the [`PictureInPicture`].
+pub fn spawn_picture_in_picture(
+    commands: &mut Commands,
+    main: Handle<AppSinkImage>,
+    inset: Handle<AppSinkImage>,
+    style: Style,
+    pip: PictureInPicture,
+    images: &mut Assets<Image>,
+) -> Entity {
+    let inset_style = Style {
+        size: Size::new(Val::Percent(100.0), Val::Auto),
+        ..default()
+    };
+    let inset = VideoNodeBundle::new(inset, inset_style, images);
+    let frame = NodeBundle {
+        style: pip.style(),
+        color: UiColor(pip.border_color),
+        ..default()
+    };
+    commands
+        .spawn_bundle(VideoNodeBundle::new(main, style, images))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(frame)
+                .insert(pip)
+                .with_children(|parent| {
+                    parent.spawn_bundle(inset);
+                });
+        })
+        .id()
+}
+
+fn layout_picture_in_picture(
+    mut frames: Query<(&PictureInPicture, &mut Style, &mut UiColor), Changed<PictureInPicture>>,
+) {
+    for (pip, mut style, mut color) in frames.iter_mut() {
+        *style = pip.style();
+        color.0 = pip.border_color;
+    }
+}
@@ -0,0 +1,7 @@
+#import itsamecube::video_material
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let color = video_process(video_sample(in.uv), in.uv);
+    return video_shade(in, color);
+}
@@ -0,0 +1,279 @@
+// Bindings and stages of the video material, for effect shaders.
+#define_import_path itsamecube::video_material
+
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+
+#import bevy_pbr::pbr_types
+#import bevy_pbr::utils
+#import bevy_pbr::clustered_forward
+#import bevy_pbr::lighting
+#import bevy_pbr::shadows
+#import bevy_pbr::pbr_functions
+
+struct VideoMaterial {
+    tint: vec4<f32>,
+    key_color: vec4<f32>,
+    effect_params: vec4<f32>,
+    // Brightness, contrast, saturation and hue.
+    balance: vec4<f32>,
+    // Focal lengths and principal point in UV units.
+    lens_intrinsics: vec4<f32>,
+    // k1, k2, p1 and p2.
+    lens_distortion: vec4<f32>,
+    // Input range of the LUT, the last component is unused.
+    lut_domain_min: vec4<f32>,
+    lut_domain_max: vec4<f32>,
+    emissive: f32,
+    similarity: f32,
+    smoothness: f32,
+    spill: f32,
+    transition: u32,
+    transition_progress: f32,
+    time: f32,
+    flags: u32,
+    mask: u32,
+    mask_radius: f32,
+    mask_softness: f32,
+    lens_k3: f32,
+};
+
+let VIDEO_MATERIAL_UNLIT: u32 = 1u;
+let VIDEO_MATERIAL_CHROMA_KEY: u32 = 2u;
+let VIDEO_MATERIAL_MATTE: u32 = 4u;
+let VIDEO_MATERIAL_LUT: u32 = 8u;
+let VIDEO_MATERIAL_BALANCE: u32 = 16u;
+let VIDEO_MATERIAL_MASK_TEXTURE: u32 = 32u;
+let VIDEO_MATERIAL_UNDISTORT: u32 = 64u;
+
+// Width of the soft edge of a wipe, in UV units.
+let VIDEO_WIPE_SOFTNESS: f32 = 0.05;
+
+@group(1) @binding(0)
+var<uniform> material: VideoMaterial;
+@group(1) @binding(1)
+var video_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var video_sampler: sampler;
+@group(1) @binding(3)
+var matte_texture: texture_2d<f32>;
+@group(1) @binding(4)
+var matte_sampler: sampler;
+@group(1) @binding(5)
+var previous_texture: texture_2d<f32>;
+@group(1) @binding(6)
+var previous_sampler: sampler;
+@group(1) @binding(7)
+var lut_texture: texture_3d<f32>;
+@group(1) @binding(8)
+var lut_sampler: sampler;
+@group(1) @binding(9)
+var mask_texture: texture_2d<f32>;
+@group(1) @binding(10)
+var mask_sampler: sampler;
+
+struct FragmentInput {
+    @builtin(front_facing) is_front: bool,
+    @builtin(position) frag_coord: vec4<f32>,
+    #import bevy_pbr::mesh_vertex_output
+};
+
+// Chroma (Cb, Cr) of an sRGB color.
+fn chroma(rgb: vec3<f32>) -> vec2<f32> {
+    return vec2<f32>(
+        dot(rgb, vec3<f32>(-0.1687, -0.3313, 0.5)),
+        dot(rgb, vec3<f32>(0.5, -0.4187, -0.0813)),
+    );
+}
+
+// Makes pixels close to the key color transparent and desaturates the key
+// color spilling onto the rest.
+fn chroma_key(color: vec4<f32>) -> vec4<f32> {
+    // Thresholds are tuned for sRGB values, the texture samples are linear.
+    let srgb = pow(color.rgb, vec3<f32>(1.0 / 2.2));
+    let d = distance(chroma(srgb), chroma(material.key_color.rgb)) - material.similarity;
+    let alpha = clamp(pow(max(d, 0.0) / material.smoothness, 1.5), 0.0, 1.0);
+
+    var rgb = color.rgb;
+    if (material.spill > 0.0) {
+        let spill = clamp(pow(max(d, 0.0) / material.spill, 1.5), 0.0, 1.0);
+        let luma = dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+        rgb = mix(vec3<f32>(luma), rgb, spill);
+    }
+    return vec4<f32>(rgb, color.a * alpha);
+}
+
+// Where the lens moved the point an ideal pinhole camera sees at `uv`, with
+// OpenCV's distortion model. Sampling there undistorts the frame.
+fn distort(uv: vec2<f32>) -> vec2<f32> {
+    let focal = material.lens_intrinsics.xy;
+    let center = material.lens_intrinsics.zw;
+    let k = material.lens_distortion;
+    let p = (uv - center) / focal;
+    let r2 = dot(p, p);
+    let radial = 1.0 + k.x * r2 + k.y * r2 * r2 + material.lens_k3 * r2 * r2 * r2;
+    let tangential = vec2<f32>(
+        2.0 * k.z * p.x * p.y + k.w * (r2 + 2.0 * p.x * p.x),
+        k.z * (r2 + 2.0 * p.y * p.y) + 2.0 * k.w * p.x * p.y,
+    );
+    return (p * radial + tangential) * focal + center;
+}
+
+// Blends the frame kept from the previous stream with the current one.
+fn transition(current: vec4<f32>, previous: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+    let progress = material.transition_progress;
+    // Case selectors must be literals, see the VIDEO_TRANSITION_* constants.
+    switch (material.transition) {
+        // Crossfade
+        case 1u: {
+            return mix(previous, current, progress);
+        }
+        // Wipe
+        case 2u: {
+            let edge = progress * (1.0 + VIDEO_WIPE_SOFTNESS);
+            let amount = clamp((edge - uv.x) / VIDEO_WIPE_SOFTNESS, 0.0, 1.0);
+            return mix(previous, current, amount);
+        }
+        // Dip to black
+        case 3u: {
+            if (progress < 0.5) {
+                return vec4<f32>(previous.rgb * (1.0 - 2.0 * progress), previous.a);
+            }
+            return vec4<f32>(current.rgb * (2.0 * progress - 1.0), current.a);
+        }
+        default: {
+            return current;
+        }
+    }
+}
+
+// Coverage of the mask shape at `uv`, from the signed distance to its edge.
+fn mask_shape(uv: vec2<f32>) -> f32 {
+    let radius = material.mask_radius;
+    // Case selectors must be literals, see the VIDEO_MASK_* constants.
+    switch (material.mask) {
+        // Circle
+        case 1u: {
+            let d = length(uv - 0.5) - radius;
+            return clamp(-d / material.mask_softness, 0.0, 1.0);
+        }
+        // Rounded rectangle
+        case 2u: {
+            let q = abs(uv - 0.5) - vec2<f32>(0.5 - radius);
+            let d = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+            return clamp(-d / material.mask_softness, 0.0, 1.0);
+        }
+        default: {
+            return 1.0;
+        }
+    }
+}
+
+// Adjusts the color like videobalance does, on luma and chroma of the sRGB
+// values.
+fn balance(color: vec4<f32>) -> vec4<f32> {
+    let srgb = pow(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+    var y = dot(srgb, vec3<f32>(0.299, 0.587, 0.114));
+    var c = chroma(srgb);
+
+    y = (y - 0.5) * material.balance.y + 0.5 + material.balance.x;
+    let angle = material.balance.w * 3.14159265;
+    let rotation = mat2x2<f32>(cos(angle), sin(angle), -sin(angle), cos(angle));
+    c = rotation * c * material.balance.z;
+
+    let rgb = vec3<f32>(
+        y + 1.402 * c.y,
+        y - 0.344136 * c.x - 0.714136 * c.y,
+        y + 1.772 * c.x,
+    );
+    return vec4<f32>(pow(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2)), color.a);
+}
+
+// Looks the color up in the grading LUT. LUTs are authored for sRGB values,
+// the texture samples are linear.
+fn grade(color: vec4<f32>) -> vec4<f32> {
+    let size = f32(textureDimensions(lut_texture).x);
+    let srgb = pow(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+    // The entries cover the domain of the LUT, not always 0 to 1.
+    let domain_min = material.lut_domain_min.rgb;
+    let domain = (srgb - domain_min) / (material.lut_domain_max.rgb - domain_min);
+    let input = clamp(domain, vec3<f32>(0.0), vec3<f32>(1.0));
+    // Sample texel centers, so 0 and 1 hit the first and last entry.
+    let coords = input * ((size - 1.0) / size) + 0.5 / size;
+    let graded = textureSampleLevel(lut_texture, lut_sampler, coords, 0.0).rgb;
+    return vec4<f32>(pow(graded, vec3<f32>(2.2)), color.a);
+}
+
+// The frame at `uv`, blended with the previous stream during a transition.
+fn video_sample(uv: vec2<f32>) -> vec4<f32> {
+    var coords = uv;
+    if ((material.flags & VIDEO_MATERIAL_UNDISTORT) != 0u) {
+        coords = distort(uv);
+    }
+    var color = textureSample(video_texture, video_sampler, coords);
+    // Sampled unconditionally, texture samples must be in uniform control flow.
+    let previous = textureSample(previous_texture, previous_sampler, coords);
+    // Black outside of what the camera saw, rather than stretched edges.
+    if (any(coords < vec2<f32>(0.0)) || any(coords > vec2<f32>(1.0))) {
+        color = vec4<f32>(0.0, 0.0, 0.0, color.a);
+    }
+    if (material.transition != 0u) {
+        return transition(color, previous, uv);
+    }
+    return color;
+}
+
+// Applies keying, grading, the matte, the mask and the tint.
+fn video_process(sampled: vec4<f32>, uv: vec2<f32>) -> vec4<f32> {
+    var color = sampled;
+    let matte = textureSample(matte_texture, matte_sampler, uv).r;
+    let mask = textureSample(mask_texture, mask_sampler, uv).a;
+    if ((material.flags & VIDEO_MATERIAL_CHROMA_KEY) != 0u) {
+        color = chroma_key(color);
+    }
+    if ((material.flags & VIDEO_MATERIAL_BALANCE) != 0u) {
+        color = balance(color);
+    }
+    if ((material.flags & VIDEO_MATERIAL_LUT) != 0u) {
+        color = grade(color);
+    }
+    if ((material.flags & VIDEO_MATERIAL_MATTE) != 0u) {
+        color.a = color.a * matte;
+    }
+    if ((material.flags & VIDEO_MATERIAL_MASK_TEXTURE) != 0u) {
+        color.a = color.a * mask;
+    }
+    color.a = color.a * mask_shape(uv);
+    return color * material.tint;
+}
+
+// Lights the color like the scene, or passes it through when unlit.
+fn video_shade(in: FragmentInput, color: vec4<f32>) -> vec4<f32> {
+    if ((material.flags & VIDEO_MATERIAL_UNLIT) != 0u) {
+        return color;
+    }
+
+    var pbr_input: PbrInput = pbr_input_new();
+    pbr_input.material.base_color = color;
+    if ((material.flags & (VIDEO_MATERIAL_CHROMA_KEY | VIDEO_MATERIAL_MATTE | VIDEO_MATERIAL_MASK_TEXTURE)) != 0u || material.mask != 0u) {
+        pbr_input.material.flags = STANDARD_MATERIAL_FLAGS_ALPHA_MODE_BLEND;
+    }
+    pbr_input.material.emissive = vec4<f32>(color.rgb * material.emissive, 1.0);
+    pbr_input.frag_coord = in.frag_coord;
+    pbr_input.world_position = in.world_position;
+    pbr_input.world_normal = in.world_normal;
+    pbr_input.is_orthographic = view.projection[3].w == 1.0;
+    pbr_input.N = prepare_normal(
+        pbr_input.material.flags,
+        in.world_normal,
+#ifdef VERTEX_TANGENTS
+#ifdef STANDARDMATERIAL_NORMAL_MAP
+        in.world_tangent,
+#endif
+#endif
+        in.uv,
+        in.is_front,
+    );
+    pbr_input.V = calculate_view(in.world_position, pbr_input.is_orthographic);
+    return tone_mapping(pbr(pbr_input));
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+
+use crate::appsink::{AppSinkImage, HEIGHT, WIDTH};
+use crate::error::VideoError;
+use crate::material::{VideoBundle, VideoMaterial};
+use crate::player::VideoPlayer;
+
+/// Reports failing tiles of [`VideoWall`]s.
+#[derive(Default)]
+pub struct VideoWallPlugin;
+
+impl Plugin for VideoWallPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<VideoWallTileFailed>()
+            .add_system(watch_wall_tiles);
+    }
+}
+
+/// A grid of streams spawned by [`spawn_video_wall`], centered on its
+/// transform. Also the layout passed to it.
+#[derive(Component, Debug, Clone)]
+pub struct VideoWall {
+    pub rows: u32,
+    pub columns: u32,
+    /// Gap between tiles, in units of the tile height.
+    pub spacing: f32,
+}
+
+/// One screen of a [`VideoWall`], a child of the wall entity.
+#[derive(Component, Debug, Clone)]
+pub struct VideoWallTile {
+    pub row: u32,
+    pub column: u32,
+    /// Set once the failure of the stream has been reported.
+    failed: bool,
+}
+
+/// Sent when the stream of a wall tile fails. Sent again if it fails after
+/// recovering.
+pub struct VideoWallTileFailed {
+    pub wall: Entity,
+    pub tile: Entity,
+    pub row: u32,
+    pub column: u32,
+    pub handle: Handle<AppSinkImage>,
+    pub error: VideoError,
+}
+
+/// Spawns a wall of `layout.rows` by `layout.columns` unlit quads showing
+/// `sources` row by row. Tiles are one unit high at the aspect ratio of the
+/// frames, with `layout.spacing` between them. Tiles beyond the number of
+/// sources are left out.
+///
+/// Returns the wall entity, the parent of every tile.
+pub fn spawn_video_wall(
+    commands: &mut Commands,
+    layout: VideoWall,
+    sources: Vec<Handle<AppSinkImage>>,
+    meshes: &mut Assets<Mesh>,
+    images: &mut Assets<Image>,
+    materials: &mut Assets<VideoMaterial>,
+) -> Entity {
+    let VideoWall {
+        rows,
+        columns,
+        spacing,
+    } = layout;
+    if sources.len() > (rows * columns) as usize {
+        warn!(
+            "Video wall of {}x{} tiles can't show {} sources",
+            rows,
+            columns,
+            sources.len()
+        );
+    }
+    let tile = Vec2::new(WIDTH as f32 / HEIGHT as f32, 1.0);
+    let mesh = meshes.add(Mesh::from(shape::Quad::new(tile)));
+    let step = tile + Vec2::splat(spacing);
+    let origin = Vec2::new(
+        -step.x * (columns as f32 - 1.0) / 2.0,
+        step.y * (rows as f32 - 1.0) / 2.0,
+    );
+
+    let mut tiles = Vec::new();
+    for (index, stream) in sources.into_iter().enumerate() {
+        let (row, column) = (index as u32 / columns.max(1), index as u32 % columns.max(1));
+        if row >= rows {
+            break;
+        }
+        let mut bundle = VideoBundle::new(
+            stream,
+            mesh.clone(),
+            VideoMaterial::default(),
+            images,
+            materials,
+        );
+        bundle.mesh.transform = Transform::from_xyz(
+            origin.x + step.x * column as f32,
+            origin.y - step.y * row as f32,
+            0.0,
+        );
+        let tile = commands
+            .spawn_bundle(bundle)
+            .insert(VideoWallTile {
+                row,
+                column,
+                failed: false,
+            })
+            .id();
+        tiles.push(tile);
+    }
+
+    commands
+        .spawn_bundle(SpatialBundle::default())
+        .insert(layout)
+        .push_children(&tiles)
+        .id()
+}
+
+fn watch_wall_tiles(
+    appsinks: Res<Assets<AppSinkImage>>,
+    mut tiles: Query<(Entity, &Parent, &VideoPlayer, &mut VideoWallTile)>,
+    mut failed: EventWriter<VideoWallTileFailed>,
+) {
+    for (entity, parent, player, mut tile) in tiles.iter_mut() {
+        let error = appsinks
+            .get(&player.stream)
+            .and_then(|appsink| appsink.error.as_ref());
+        match error {
+            Some(error) if !tile.failed => {
+                tile.failed = true;
+                failed.send(VideoWallTileFailed {
+                    wall: parent.get(),
+                    tile: entity,
+                    row: tile.row,
+                    column: tile.column,
+                    handle: player.stream.clone_weak(),
+                    error: error.clone(),
+                });
+            }
+            None if tile.failed => tile.failed = false,
+            _ => {}
+        }
+    }
+}
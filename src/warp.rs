@@ -0,0 +1,444 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+
+use crate::error::VideoError;
+
+/// Rebuilds the meshes of entities whose [`VideoWarp`] changed.
+#[derive(Default)]
+pub struct VideoWarpPlugin;
+
+impl Plugin for VideoWarpPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(rebuild_warp_meshes);
+    }
+}
+
+/// Subdivisions of each grid cell used by [`VideoWarp::new`] and
+/// [`VideoWarp::keystone`], enough to hide the interpolation of the texture
+/// across each quad.
+const DEFAULT_SUBDIVISIONS: u32 = 8;
+
+/// A grid of control points the video is stretched over, for aligning a
+/// projector with a physical surface from inside the app.
+///
+/// Add it next to the `Handle<Mesh>` of a video entity, the mesh is replaced
+/// with the warped grid whenever the points change. Points are in the local
+/// space of the entity, row by row from the top left.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct VideoWarp {
+    columns: u32,
+    rows: u32,
+    points: Vec<Vec2>,
+    /// Quads each cell is split into along each axis when building the mesh.
+    pub subdivisions: u32,
+}
+
+impl VideoWarp {
+    /// An unwarped `size` rectangle centered on the origin, with `columns` by
+    /// `rows` cells.
+    pub fn new(size: Vec2, columns: u32, rows: u32) -> Self {
+        let half = size / 2.0;
+        let mut warp = VideoWarp {
+            columns: columns.max(1),
+            rows: rows.max(1),
+            points: Vec::new(),
+            subdivisions: DEFAULT_SUBDIVISIONS,
+        };
+        warp.set_corners([
+            Vec2::new(-half.x, half.y),
+            Vec2::new(half.x, half.y),
+            Vec2::new(half.x, -half.y),
+            Vec2::new(-half.x, -half.y),
+        ]);
+        warp
+    }
+
+    /// Four corner keystone correction: a single cell with its corners at
+    /// top left, top right, bottom right and bottom left. The video is
+    /// mapped in perspective, as a projector tilted against the surface
+    /// would show it.
+    pub fn keystone(corners: [Vec2; 4]) -> Self {
+        let mut warp = VideoWarp {
+            columns: 1,
+            rows: 1,
+            points: Vec::new(),
+            subdivisions: DEFAULT_SUBDIVISIONS * 2,
+        };
+        warp.set_corners(corners);
+        warp
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// Control point at `column` and `row`, from 0 to `columns` and `rows`
+    /// inclusive.
+    pub fn point(&self, column: u32, row: u32) -> Option<Vec2> {
+        self.index(column, row).map(|index| self.points[index])
+    }
+
+    /// Moves a control point. Returns `false` if it doesn't exist.
+    pub fn set_point(&mut self, column: u32, row: u32, position: Vec2) -> bool {
+        match self.index(column, row) {
+            Some(index) => {
+                self.points[index] = position;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The control point closest to `position` within `radius`, for picking
+    /// handles with the mouse.
+    pub fn nearest_point(&self, position: Vec2, radius: f32) -> Option<(u32, u32)> {
+        self.points
+            .iter()
+            .enumerate()
+            .map(|(index, point)| (index, point.distance(position)))
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| {
+                let stride = self.columns as usize + 1;
+                ((index % stride) as u32, (index / stride) as u32)
+            })
+    }
+
+    /// Spreads every control point evenly between the four corners, in the
+    /// same order as [`VideoWarp::keystone`].
+    pub fn set_corners(&mut self, corners: [Vec2; 4]) {
+        let [top_left, top_right, bottom_right, bottom_left] = corners;
+        self.points.clear();
+        for row in 0..=self.rows {
+            let v = row as f32 / self.rows as f32;
+            let left = top_left.lerp(bottom_left, v);
+            let right = top_right.lerp(bottom_right, v);
+            for column in 0..=self.columns {
+                self.points
+                    .push(left.lerp(right, column as f32 / self.columns as f32));
+            }
+        }
+    }
+
+    fn index(&self, column: u32, row: u32) -> Option<usize> {
+        (column <= self.columns && row <= self.rows)
+            .then(|| (row * (self.columns + 1) + column) as usize)
+    }
+
+    /// Position at `u`, `v` within the cell at `column` and `row`. A single
+    /// cell is mapped in perspective, cells of a grid bilinearly.
+    fn interpolate(&self, column: u32, row: u32, u: f32, v: f32) -> Vec2 {
+        let point = |column, row| self.points[self.index(column, row).unwrap()];
+        if self.columns == 1 && self.rows == 1 {
+            let corners = [point(0, 0), point(1, 0), point(1, 1), point(0, 1)];
+            if let Some(homography) = homography(corners) {
+                let mapped = homography * Vec3::new(u, v, 1.0);
+                return mapped.truncate() / mapped.z;
+            }
+        }
+        let top = point(column, row).lerp(point(column + 1, row), u);
+        let bottom = point(column, row + 1).lerp(point(column + 1, row + 1), u);
+        top.lerp(bottom, v)
+    }
+
+    /// Builds the warped grid, facing +Z with the video's UVs.
+    pub fn mesh(&self) -> Mesh {
+        let subdivisions = self.subdivisions.max(1);
+        let width = self.columns * subdivisions;
+        let height = self.rows * subdivisions;
+
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        for y in 0..=height {
+            let (row, v) = split(y, subdivisions, self.rows);
+            for x in 0..=width {
+                let (column, u) = split(x, subdivisions, self.columns);
+                positions.push(self.interpolate(column, row, u, v).extend(0.0).to_array());
+                uvs.push([x as f32 / width as f32, y as f32 / height as f32]);
+            }
+        }
+        let normals = vec![[0.0, 0.0, 1.0]; positions.len()];
+
+        let mut indices = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let top_left = y * (width + 1) + x;
+                let top_right = top_left + 1;
+                let bottom_left = top_left + width + 1;
+                let bottom_right = bottom_left + 1;
+                indices.extend_from_slice(&[bottom_left, bottom_right, top_right]);
+                indices.extend_from_slice(&[bottom_left, top_right, top_left]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+
+    /// Writes the warp in the format read by [`VideoWarp::load`].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), VideoError> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<VideoWarp, VideoError> {
+        VideoWarp::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses a warp saved by [`VideoWarp::save`]:
+    ///
+    /// ```text
+    /// grid = 2x1
+    /// subdivisions = 8
+    /// point = -1.0 0.5
+    /// ...
+    /// ```
+    ///
+    /// with one `point` per control point, row by row from the top left.
+    pub fn parse(text: &str) -> Result<VideoWarp, VideoError> {
+        let mut grid = None;
+        let mut subdivisions = DEFAULT_SUBDIVISIONS;
+        let mut points = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: String| VideoError::Config {
+                line: index + 1,
+                message,
+            };
+            let (key, value) = line
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| invalid(format!("expected `key = value`, got `{}`", line)))?;
+            match key {
+                "grid" => {
+                    grid = value
+                        .split_once('x')
+                        .and_then(|(columns, rows)| {
+                            Some((columns.trim().parse().ok()?, rows.trim().parse().ok()?))
+                        })
+                        .filter(|&(columns, rows): &(u32, u32)| columns > 0 && rows > 0);
+                    if grid.is_none() {
+                        return Err(invalid(format!(
+                            "expected `<columns>x<rows>`, got `{}`",
+                            value
+                        )));
+                    }
+                }
+                "subdivisions" => {
+                    subdivisions = value
+                        .parse()
+                        .ok()
+                        .filter(|subdivisions| *subdivisions > 0)
+                        .ok_or_else(|| invalid(format!("invalid subdivisions `{}`", value)))?
+                }
+                "point" => {
+                    let coords: Vec<f32> = value
+                        .split_whitespace()
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| invalid(format!("expected `<x> <y>`, got `{}`", value)))?;
+                    match coords[..] {
+                        [x, y] => points.push(Vec2::new(x, y)),
+                        _ => return Err(invalid(format!("expected `<x> <y>`, got `{}`", value))),
+                    }
+                }
+                _ => return Err(invalid(format!("unknown key `{}`", key))),
+            }
+        }
+
+        let (columns, rows) = grid.ok_or_else(|| VideoError::Config {
+            line: 0,
+            message: String::from("missing `grid`"),
+        })?;
+        let expected = ((columns + 1) * (rows + 1)) as usize;
+        if points.len() != expected {
+            return Err(VideoError::Config {
+                line: 0,
+                message: format!("expected {} points, got {}", expected, points.len()),
+            });
+        }
+        Ok(VideoWarp {
+            columns,
+            rows,
+            points,
+            subdivisions,
+        })
+    }
+}
+
+impl std::fmt::Display for VideoWarp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "grid = {}x{}", self.columns, self.rows)?;
+        writeln!(f, "subdivisions = {}", self.subdivisions)?;
+        for point in &self.points {
+            writeln!(f, "point = {} {}", point.x, point.y)?;
+        }
+        Ok(())
+    }
+}
+
+/// Projective mapping of the unit square onto `corners`, in the order of
+/// [`VideoWarp::keystone`] with `v` going down. `None` if three corners are
+/// aligned.
+fn homography(corners: [Vec2; 4]) -> Option<Mat3> {
+    let [p0, p1, p2, p3] = corners;
+    let sum = p0 - p1 + p2 - p3;
+    let (g, h) = if sum == Vec2::ZERO {
+        // A parallelogram, the mapping is affine.
+        (0.0, 0.0)
+    } else {
+        let d1 = p1 - p2;
+        let d2 = p3 - p2;
+        let det = d1.perp_dot(d2);
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+        (sum.perp_dot(d2) / det, d1.perp_dot(sum) / det)
+    };
+    let a = p1 - p0 + g * p1;
+    let b = p3 - p0 + h * p3;
+    Some(Mat3::from_cols(a.extend(g), b.extend(h), p0.extend(1.0)))
+}
+
+/// Cell and position within it of vertex `index` along an axis with `cells`
+/// cells of `subdivisions` vertices. The last vertex belongs to the last cell.
+fn split(index: u32, subdivisions: u32, cells: u32) -> (u32, f32) {
+    let cell = (index / subdivisions).min(cells - 1);
+    (
+        cell,
+        (index - cell * subdivisions) as f32 / subdivisions as f32,
+    )
+}
+
+fn rebuild_warp_meshes(
+    warps: Query<(&VideoWarp, &Handle<Mesh>), Changed<VideoWarp>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (warp, mesh) in warps.iter() {
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            *mesh = warp.mesh();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Line and message of the error `text` fails with.
+    fn error(text: &str) -> (usize, String) {
+        match VideoWarp::parse(text) {
+            Err(VideoError::Config { line, message }) => (line, message),
+            Err(err) => panic!("{:?}: unexpected {}", text, err),
+            Ok(_) => panic!("{:?}: parsed", text),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_the_save_format() {
+        let mut warp = VideoWarp::new(Vec2::new(4.0, 2.0), 3, 2);
+        warp.subdivisions = 5;
+        assert!(warp.set_point(1, 1, Vec2::new(-0.25, 0.125)));
+        assert_eq!(VideoWarp::parse(&warp.to_string()).unwrap(), warp);
+
+        let keystone = VideoWarp::keystone([
+            Vec2::new(-1.0, 1.0),
+            Vec2::new(1.5, 0.75),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(-1.25, -0.5),
+        ]);
+        assert_eq!(VideoWarp::parse(&keystone.to_string()).unwrap(), keystone);
+    }
+
+    #[test]
+    fn parses_comments_and_default_subdivisions() {
+        let warp = VideoWarp::parse(
+            "# projector 2\n\ngrid = 1x1\npoint = 0 1\npoint = 1 1\npoint = 0 0\npoint = 1 0\n",
+        )
+        .unwrap();
+        assert_eq!((warp.columns(), warp.rows()), (1, 1));
+        assert_eq!(warp.subdivisions, DEFAULT_SUBDIVISIONS);
+        assert_eq!(warp.point(1, 0), Some(Vec2::new(1.0, 1.0)));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let cases = [
+            ("grid = 2", 1, "expected `<columns>x<rows>`, got `2`"),
+            ("grid = 0x1", 1, "expected `<columns>x<rows>`, got `0x1`"),
+            ("grid = ax1", 1, "expected `<columns>x<rows>`, got `ax1`"),
+            ("grid = 1x1\npoint = 1", 2, "expected `<x> <y>`, got `1`"),
+            (
+                "grid = 1x1\npoint = 1 2 3",
+                2,
+                "expected `<x> <y>`, got `1 2 3`",
+            ),
+            (
+                "grid = 1x1\npoint = 1 y",
+                2,
+                "expected `<x> <y>`, got `1 y`",
+            ),
+            ("\nsubdivisions = 0", 2, "invalid subdivisions `0`"),
+            ("grid 1x1", 1, "expected `key = value`, got `grid 1x1`"),
+            ("size = 1x1", 1, "unknown key `size`"),
+        ];
+        for (text, line, message) in cases {
+            assert_eq!(error(text), (line, String::from(message)), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_missing_grid_and_points() {
+        assert_eq!(error("point = 0 0"), (0, String::from("missing `grid`")));
+        assert_eq!(
+            error("grid = 1x1\npoint = 0 0"),
+            (0, String::from("expected 4 points, got 1"))
+        );
+    }
+
+    #[test]
+    fn keystone_maps_corners_and_keeps_perspective() {
+        let corners = [
+            Vec2::new(-1.0, 1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, -1.0),
+            Vec2::new(-2.0, -1.0),
+        ];
+        let warp = VideoWarp::keystone(corners);
+        for (corner, (u, v)) in corners
+            .iter()
+            .zip([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)])
+        {
+            assert!(warp.interpolate(0, 0, u, v).abs_diff_eq(*corner, 1e-5));
+        }
+        // The center of the picture lands where the diagonals cross, a third
+        // of the way down this trapezoid, not halfway as bilinear would.
+        let center = warp.interpolate(0, 0, 0.5, 0.5);
+        assert!(
+            center.abs_diff_eq(Vec2::new(0.0, 1.0 / 3.0), 1e-5),
+            "{}",
+            center
+        );
+    }
+
+    #[test]
+    fn rectangles_map_linearly() {
+        let warp = VideoWarp::new(Vec2::new(4.0, 2.0), 1, 1);
+        let point = warp.interpolate(0, 0, 0.25, 0.75);
+        assert!(point.abs_diff_eq(Vec2::new(-1.0, -0.5), 1e-5), "{}", point);
+    }
+}
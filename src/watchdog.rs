@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use bevy::asset::HandleId;
+use bevy::prelude::*;
+
+use crate::appsink::{handle_failure, AppSinkImage, StreamEvents};
+use crate::error::VideoError;
+
+/// Detects streams that stop delivering frames without reporting an error.
+#[derive(Debug, Clone)]
+pub struct WatchdogConfig {
+    /// How long a running stream may go without a sample before it is
+    /// considered stalled. `None` disables the watchdog.
+    pub timeout: Option<Duration>,
+    /// Whether a stall is handed to the recovery policy like any other error.
+    pub restart: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            timeout: Some(Duration::from_secs(5)),
+            restart: true,
+        }
+    }
+}
+
+/// Sent once when a stream has not produced a sample for longer than its
+/// watchdog timeout.
+pub struct VideoStalled {
+    pub handle: Handle<AppSinkImage>,
+    pub since: Duration,
+}
+
+pub(crate) fn watch_stalls(
+    time: Res<Time>,
+    mut appsinks: ResMut<Assets<AppSinkImage>>,
+    mut stalled: EventWriter<VideoStalled>,
+    mut events: StreamEvents,
+) {
+    let mut changed: Vec<(HandleId, Option<Duration>)> = Vec::new();
+    for (id, appsink) in appsinks.iter() {
+        // A paused stream is expected to stop delivering frames.
+        if appsink.is_paused() {
+            continue;
+        }
+        let (timeout, since) = match (appsink.watchdog.timeout, appsink.time_since_last_sample()) {
+            (Some(timeout), Some(since)) => (timeout, since),
+            _ => continue,
+        };
+        if since > timeout && !appsink.stalled {
+            changed.push((id, Some(since)));
+        } else if since <= timeout && appsink.stalled {
+            changed.push((id, None));
+        }
+    }
+
+    for (id, since) in changed {
+        let handle = Handle::weak(id);
+        let appsink = match appsinks.get_mut(&handle) {
+            Some(appsink) => appsink,
+            None => continue,
+        };
+
+        let since = match since {
+            Some(since) => since,
+            None => {
+                appsink.stalled = false;
+                continue;
+            }
+        };
+
+        warn!("No frames received for {:.1}s", since.as_secs_f32());
+        appsink.stalled = true;
+        stalled.send(VideoStalled {
+            handle: handle.clone_weak(),
+            since,
+        });
+        if appsink.watchdog.restart {
+            handle_failure(
+                appsink,
+                &handle,
+                VideoError::Stalled(since),
+                time.time_since_startup(),
+                &mut events,
+            );
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use gst::prelude::*;
+
+use crate::error::VideoError;
+
+/// Which side of the offer/answer exchange an SDP belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdpKind {
+    Offer,
+    Answer,
+}
+
+/// A message received from the remote peer through the signaling channel.
+#[derive(Debug, Clone)]
+pub enum SignalMessage {
+    Sdp { kind: SdpKind, sdp: String },
+    IceCandidate { mline_index: u32, candidate: String },
+}
+
+/// Carries session descriptions and ICE candidates between `webrtcbin` and
+/// the remote peer.
+///
+/// The transport is up to the application: a websocket to a signaling
+/// server, a game server connection, or anything else. Outgoing messages may
+/// be sent from GStreamer threads; incoming messages are polled once per
+/// frame.
+pub trait Signaller: fmt::Debug + Send + Sync + 'static {
+    fn send_sdp(&self, kind: SdpKind, sdp: &str);
+    fn send_ice_candidate(&self, mline_index: u32, candidate: &str);
+    /// Next message received from the remote peer, if any.
+    fn poll(&self) -> Option<SignalMessage>;
+}
+
+/// The `webrtcbin` of a pipeline hooked up to a [`Signaller`].
+#[derive(Clone)]
+pub struct WebRtcSession {
+    webrtcbin: gst::Element,
+    signaller: Arc<dyn Signaller>,
+}
+
+impl fmt::Debug for WebRtcSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebRtcSession")
+            .field("webrtcbin", &self.webrtcbin.name())
+            .field("signaller", &self.signaller)
+            .finish()
+    }
+}
+
+impl WebRtcSession {
+    /// Connects the `webrtcbin` named `name` in `pipeline` to `signaller`.
+    /// Must be called before the pipeline starts, so the offer created on
+    /// negotiation is not missed.
+    pub fn attach(
+        pipeline: &gst::Pipeline,
+        name: &str,
+        signaller: Arc<dyn Signaller>,
+    ) -> Result<WebRtcSession, VideoError> {
+        let webrtcbin = pipeline
+            .by_name(name)
+            .ok_or_else(|| VideoError::Unsupported(format!("no webrtcbin named {}", name)))?;
+
+        let offer_signaller = signaller.clone();
+        webrtcbin.connect("on-negotiation-needed", false, move |values| {
+            let webrtcbin = values[0].get::<gst::Element>().expect("Invalid argument");
+            let weak = webrtcbin.downgrade();
+            let signaller = offer_signaller.clone();
+            let promise = gst::Promise::with_change_func(move |reply| {
+                let webrtcbin = match weak.upgrade() {
+                    Some(webrtcbin) => webrtcbin,
+                    None => return,
+                };
+                let offer = match reply {
+                    Ok(Some(reply)) => reply
+                        .get::<gst_webrtc::WebRTCSessionDescription>("offer")
+                        .ok(),
+                    _ => None,
+                };
+                let offer = match offer {
+                    Some(offer) => offer,
+                    None => {
+                        error!("webrtcbin failed to create an offer");
+                        return;
+                    }
+                };
+                webrtcbin
+                    .emit_by_name::<()>("set-local-description", &[&offer, &None::<gst::Promise>]);
+                match offer.sdp().as_text() {
+                    Ok(sdp) => signaller.send_sdp(SdpKind::Offer, &sdp),
+                    Err(err) => error!("Failed to serialize offer: {}", err),
+                }
+            });
+            webrtcbin.emit_by_name::<()>("create-offer", &[&None::<gst::Structure>, &promise]);
+            None
+        });
+
+        let ice_signaller = signaller.clone();
+        webrtcbin.connect("on-ice-candidate", false, move |values| {
+            let mline_index = values[1].get::<u32>().expect("Invalid argument");
+            let candidate = values[2].get::<String>().expect("Invalid argument");
+            ice_signaller.send_ice_candidate(mline_index, &candidate);
+            None
+        });
+
+        Ok(WebRtcSession {
+            webrtcbin,
+            signaller,
+        })
+    }
+
+    /// Applies the messages the remote peer sent since the last call.
+    pub fn handle_remote(&self) -> Result<(), VideoError> {
+        while let Some(message) = self.signaller.poll() {
+            match message {
+                SignalMessage::Sdp { kind, sdp } => {
+                    let sdp_type = match kind {
+                        SdpKind::Offer => gst_webrtc::WebRTCSDPType::Offer,
+                        SdpKind::Answer => gst_webrtc::WebRTCSDPType::Answer,
+                    };
+                    let sdp = gst_sdp::SDPMessage::parse_buffer(sdp.as_bytes())?;
+                    let description = gst_webrtc::WebRTCSessionDescription::new(sdp_type, sdp);
+                    self.webrtcbin.emit_by_name::<()>(
+                        "set-remote-description",
+                        &[&description, &None::<gst::Promise>],
+                    );
+                }
+                SignalMessage::IceCandidate {
+                    mline_index,
+                    candidate,
+                } => {
+                    self.webrtcbin
+                        .emit_by_name::<()>("add-ice-candidate", &[&mline_index, &candidate]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
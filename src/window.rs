@@ -0,0 +1,179 @@
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::render::view::RenderLayers;
+use bevy::window::{CreateWindow, WindowClosed, WindowId};
+
+use crate::appsink::AppSinkImage;
+use crate::material::VideoTexture;
+use crate::player::VideoPlayer;
+
+/// Render layer of the first [`VideoWindow`]. Further windows need layers of
+/// their own, so the scene and other windows don't show up in them.
+pub const VIDEO_WINDOW_LAYER: u8 = 30;
+
+/// Opens a window for every [`VideoWindow`] and lays out its streams.
+#[derive(Default)]
+pub struct VideoWindowPlugin;
+
+impl Plugin for VideoWindowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(open_video_windows)
+            .add_system(close_video_windows)
+            .add_system(layout_video_windows);
+    }
+}
+
+/// A separate OS window showing `streams` on a grid, such as raw feeds on the
+/// operator's second monitor while the main window shows the scene.
+///
+/// The window opens when the component is added. Despawning the entity
+/// recursively removes the camera and the streams, closing the window
+/// despawns the entity.
+#[derive(Component, Debug, Clone)]
+pub struct VideoWindow {
+    pub title: String,
+    pub width: f32,
+    pub height: f32,
+    pub streams: Vec<Handle<AppSinkImage>>,
+    /// Streams per row, a square-ish grid if `None`.
+    pub columns: Option<u32>,
+    pub layer: u8,
+    window: Option<WindowId>,
+}
+
+impl VideoWindow {
+    pub fn new(title: impl Into<String>, streams: Vec<Handle<AppSinkImage>>) -> Self {
+        VideoWindow {
+            title: title.into(),
+            width: 1280.0,
+            height: 720.0,
+            streams,
+            columns: None,
+            layer: VIDEO_WINDOW_LAYER,
+            window: None,
+        }
+    }
+
+    /// The OS window, once opened.
+    pub fn window(&self) -> Option<WindowId> {
+        self.window
+    }
+
+    fn columns(&self) -> u32 {
+        self.columns
+            .unwrap_or_else(|| (self.streams.len() as f32).sqrt().ceil() as u32)
+            .max(1)
+    }
+}
+
+/// One stream of a [`VideoWindow`], a child of the window entity.
+#[derive(Component, Debug, Clone)]
+pub struct VideoWindowTile {
+    pub index: usize,
+}
+
+fn open_video_windows(
+    mut commands: Commands,
+    mut windows: Query<(Entity, &mut VideoWindow), Added<VideoWindow>>,
+    mut create_window: EventWriter<CreateWindow>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for (entity, mut video_window) in windows.iter_mut() {
+        let id = WindowId::new();
+        video_window.window = Some(id);
+        create_window.send(CreateWindow {
+            id,
+            descriptor: WindowDescriptor {
+                title: video_window.title.clone(),
+                width: video_window.width,
+                height: video_window.height,
+                ..default()
+            },
+        });
+
+        let layer = RenderLayers::layer(video_window.layer);
+        let streams = video_window.streams.clone();
+        commands
+            .entity(entity)
+            .insert_bundle(SpatialBundle::default())
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(Camera2dBundle {
+                        camera: Camera {
+                            target: RenderTarget::Window(id),
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    // Every camera draws the UI in Bevy 0.8, keep it on the
+                    // main window.
+                    .insert(UiCameraConfig { show_ui: false })
+                    .insert(layer);
+                for (index, stream) in streams.into_iter().enumerate() {
+                    let texture = images.add(VideoTexture::image());
+                    parent
+                        .spawn_bundle(SpriteBundle {
+                            texture: texture.clone(),
+                            ..default()
+                        })
+                        .insert(VideoPlayer { stream })
+                        .insert(VideoTexture(texture))
+                        .insert(VideoWindowTile { index })
+                        .insert(layer);
+                }
+            });
+    }
+}
+
+fn close_video_windows(
+    mut commands: Commands,
+    mut closed: EventReader<WindowClosed>,
+    windows: Query<(Entity, &VideoWindow)>,
+) {
+    for closed in closed.iter() {
+        for (entity, video_window) in windows.iter() {
+            if video_window.window == Some(closed.id) {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Fits each stream into its grid cell, keeping its aspect ratio.
+fn layout_video_windows(
+    windows: Res<Windows>,
+    appsinks: Res<Assets<AppSinkImage>>,
+    video_windows: Query<(&VideoWindow, &Children)>,
+    mut tiles: Query<(&VideoWindowTile, &VideoPlayer, &mut Sprite, &mut Transform)>,
+) {
+    for (video_window, children) in video_windows.iter() {
+        let window = match video_window.window.and_then(|id| windows.get(id)) {
+            Some(window) => Vec2::new(window.width(), window.height()),
+            None => continue,
+        };
+        let columns = video_window.columns();
+        let rows = ((video_window.streams.len() as u32 + columns - 1) / columns).max(1);
+        let cell = window / Vec2::new(columns as f32, rows as f32);
+        for &child in children.iter() {
+            let (tile, player, mut sprite, mut transform) = match tiles.get_mut(child) {
+                Ok(tile) => tile,
+                Err(_) => continue,
+            };
+            let aspect = appsinks
+                .get(&player.stream)
+                .map_or(1.0, AppSinkImage::aspect_ratio);
+            let size = Vec2::new(aspect, 1.0) * (cell / Vec2::new(aspect, 1.0)).min_element();
+            let (column, row) = (tile.index as u32 % columns, tile.index as u32 / columns);
+            let center = Vec2::new(
+                (column as f32 + 0.5) * cell.x - window.x / 2.0,
+                window.y / 2.0 - (row as f32 + 0.5) * cell.y,
+            );
+            if sprite.custom_size != Some(size) {
+                sprite.custom_size = Some(size);
+            }
+            if transform.translation.truncate() != center {
+                transform.translation = center.extend(0.0);
+            }
+        }
+    }
+}